@@ -0,0 +1,71 @@
+use std::{collections::HashMap, fs::File, io::BufWriter};
+
+use vct::{command_encoder::voxelization::*, *};
+
+// Voxelizes a glTF file without a window and writes the chosen mip level of the colour volume as
+// <output>.vox, <output>.bin with its <output>.json header and the sparse <output>.txt.
+// The greedy meshed surface at mip level 1 goes to <output>.obj, <output>.ply and <output>.glb
+fn main() {
+	let args: Vec<String> = std::env::args().collect();
+	if args.len() < 3 {
+		eprintln!("usage: {} <gltf> <output> [resolution] [mip level]", args[0]);
+		std::process::exit(1);
+	}
+	let output = &args[2];
+	let resolution = args.get(3).map_or(128, |arg| arg.parse().expect("resolution"));
+	let mip_level = args.get(4).map_or(0, |arg| arg.parse().expect("mip level"));
+
+	let render_settings = RendererSettings {
+		render_scale: 1.0,
+		extras: HashMap::new(),
+	};
+	let mut renderer = pollster::block_on(Renderer::new_headless(
+		[1, 1],
+		wgpu::TextureFormat::Rgba8Unorm,
+		render_settings,
+	)).unwrap();
+	renderer.load_gltf(&args[1], true);
+
+	let volume = VoxelVolumeDescriptor::fit_to_meshes(&renderer, resolution).expect("no meshes to voxelize");
+	let mut voxelization_pass = VoxelizationPass::new(&renderer, &volume);
+	let mut meshify_pass = MeshifyPass::new(&renderer);
+	meshify_pass.set_settings(MeshifySettings {
+		mode: MeshifyMode::Greedy,
+		..Default::default()
+	});
+
+	renderer.update();
+	let mut command_encoder = renderer.new_command_encoder(None);
+	command_encoder.begin_pass(&mut voxelization_pass);
+	command_encoder.begin_pass(&mut meshify_pass);
+	command_encoder.finish();
+
+	let voxels = renderer.get_resource::<VoxelsResource>().unwrap();
+	let data = voxels.read_color(&renderer, mip_level).unwrap();
+	println!("{} of {:?} voxels occupied", data.occupied().count(), data.size);
+
+	let create = |extension: &str| BufWriter::new(File::create(format!("{output}.{extension}")).unwrap());
+	let mut vox = Vec::new();
+	match data.write_vox(&mut vox) {
+		Ok(()) => std::fs::write(format!("{output}.vox"), vox).unwrap(),
+		Err(error) => eprintln!("skipped .vox: {error}"),
+	}
+	data.write_raw(&mut create("bin"), &mut create("json")).unwrap();
+	data.write_sparse(&mut create("txt")).unwrap();
+
+	let mesh = renderer.get_resource::<VoxelsMeshResource>().unwrap().read_mesh(&renderer).unwrap();
+	println!("{} quads in the surface mesh", mesh.indices.len() / 6);
+	if mesh.dropped_quads > 0 {
+		eprintln!(
+			"{} quads didn't fit the meshify capacity of {}, the exported surface has holes",
+			mesh.dropped_quads,
+			MeshifyPass::DEFAULT_CAPACITY
+		);
+	}
+	if mesh.indices.is_empty() {
+		return;
+	}
+	mesh.write_obj(&mut create("obj")).unwrap();
+	mesh.write_ply(&mut create("ply")).unwrap();
+	mesh.write_glb(&mut create("glb")).unwrap();
+}
@@ -27,12 +27,21 @@ fn main() {
 
 	let mut renderer = pollster::block_on(Renderer::new(window.clone(), render_settings));
 	let _cameras = renderer.load_gltf("examples/Sponza/Sponza.gltf", true);
+	renderer.add_point_light(vct::lights::PointLight {
+		position: [0.0, 1.5, 0.0],
+		color: [1.0, 1.0, 1.0],
+		// Falls off with the square of the distance
+		intensity: 20.0,
+	});
 	// .load_gltf("examples/Box.glb", true);
 
-	let mut voxelization_pass = vct::command_encoder::voxelization::VoxelizationPass::new(&renderer);
-	let mut meshify_pass = vct::command_encoder::voxelization::MeshifyPass::new(&renderer);
-	let mut render_meshify_pass = vct::command_encoder::voxelization::RenderMeshifyPass::new(&renderer);
+	let volume = vct::command_encoder::voxelization::VoxelVolumeDescriptor::fit_to_meshes(&renderer, 512).unwrap();
+	let mut voxelization_pass = vct::command_encoder::voxelization::VoxelizationPass::new(&renderer, &volume);
+	let mut radiance_injection_pass = vct::command_encoder::voxelization::RadianceInjectionPass::new(&renderer);
+	let mut voxel_debug_pass = vct::command_encoder::voxelization::VoxelDebugPass::new(&renderer);
 	let mut forward_render_pass = vct::command_encoder::forward::ForwardRenderingPass::new(&renderer);
+	let mut gbuffer_pass = vct::command_encoder::deferred::GBufferPass::new(&renderer);
+	let mut deferred_lighting_pass = vct::command_encoder::deferred::DeferredLightingPass::new(&renderer);
 
 	let camera = renderer.new_camera(&CameraDescriptor {
 		position: [0.0, 0.0, 0.0].into(),
@@ -44,14 +53,28 @@ fn main() {
 	});
 
 	renderer.update();
-	let mut command_encoder = renderer.new_command_encoder(Some(&camera));
-	command_encoder.begin_pass(&mut voxelization_pass);
-	command_encoder.finish();
+	// Voxelizing is skipped when VCT_VOXEL_CACHE names a cache of this scene, otherwise the cache is written there
+	let cache_path = std::env::var_os("VCT_VOXEL_CACHE");
+	let source_hash = vct::command_encoder::voxelization::VoxelCache::source_hash(["examples/Sponza/Sponza.gltf"]).unwrap();
+	let cached = cache_path.as_ref().is_some_and(|path| {
+		let cache = std::fs::File::open(path)
+			.and_then(|file| vct::command_encoder::voxelization::VoxelCache::read(&mut std::io::BufReader::new(file)));
+		cache.is_ok_and(|cache| voxelization_pass.load_cache(&renderer, &cache, source_hash))
+	});
 
 	let mut command_encoder = renderer.new_command_encoder(Some(&camera));
-	command_encoder.begin_pass(&mut meshify_pass);
+	if !cached {
+		command_encoder.begin_pass(&mut voxelization_pass);
+	}
+	command_encoder.begin_pass(&mut radiance_injection_pass);
 	command_encoder.finish();
 
+	if let (Some(path), false) = (&cache_path, cached) {
+		let voxels = renderer.get_resource::<vct::command_encoder::voxelization::VoxelsResource>().unwrap();
+		let cache = vct::command_encoder::voxelization::VoxelCache::capture(&renderer, &voxels, source_hash).unwrap();
+		cache.write(&mut std::io::BufWriter::new(std::fs::File::create(path).unwrap())).unwrap();
+	}
+
 	let mut instant = std::time::Instant::now();
 
 	let mut w = ElementState::Released;
@@ -61,6 +84,7 @@ fn main() {
 	let mut c = ElementState::Released;
 	let mut space = ElementState::Released;
 	let mut render_voxels = false;
+	let mut deferred = false;
 
 	event_loop.run(move |event, event_loop| {
 		let new_instant = std::time::Instant::now();
@@ -235,6 +259,50 @@ fn main() {
 						},
 					..
 				} => println!("{:#?}", camera.position()),
+				// G switches between forward and deferred shading
+				WindowEvent::KeyboardInput {
+					event:
+						KeyEvent {
+							state: ElementState::Pressed,
+							physical_key: PhysicalKey::Code(KeyCode::KeyG),
+							..
+						},
+					..
+				} => deferred = !deferred,
+				// V cycles the style, B the channel and M the mip level of the voxel view
+				WindowEvent::KeyboardInput {
+					event:
+						KeyEvent {
+							state: ElementState::Pressed,
+							physical_key: PhysicalKey::Code(key @ (KeyCode::KeyV | KeyCode::KeyB | KeyCode::KeyM)),
+							..
+						},
+					..
+				} => {
+					use vct::command_encoder::voxelization::{VoxelChannel, VoxelDebugStyle};
+
+					let mut settings = voxel_debug_pass.settings();
+					match key {
+						KeyCode::KeyV => {
+							settings.style = match settings.style {
+								VoxelDebugStyle::Cubes => VoxelDebugStyle::Points,
+								VoxelDebugStyle::Points => VoxelDebugStyle::RayMarch,
+								VoxelDebugStyle::RayMarch => VoxelDebugStyle::Cubes,
+							}
+						}
+						KeyCode::KeyB => {
+							settings.channel = match settings.channel {
+								VoxelChannel::Albedo => VoxelChannel::Opacity,
+								VoxelChannel::Opacity => VoxelChannel::Normal,
+								VoxelChannel::Normal => VoxelChannel::Emissive,
+								VoxelChannel::Emissive => VoxelChannel::Radiance,
+								VoxelChannel::Radiance => VoxelChannel::Albedo,
+							}
+						}
+						_ => settings.mip_level = (settings.mip_level + 1) % volume.extent().max_mips(wgpu::TextureDimension::D3),
+					}
+					voxel_debug_pass.set_settings(settings);
+				}
 
 				_ => {}
 			},
@@ -259,7 +327,10 @@ fn main() {
 				renderer.update();
 				let mut command_encoder = renderer.new_command_encoder(Some(&camera));
 				if render_voxels {
-					command_encoder.begin_pass(&mut render_meshify_pass);
+					command_encoder.begin_pass(&mut voxel_debug_pass);
+				} else if deferred {
+					command_encoder.begin_pass(&mut gbuffer_pass);
+					command_encoder.begin_pass(&mut deferred_lighting_pass);
 				} else {
 					command_encoder.begin_pass(&mut forward_render_pass);
 				}
@@ -16,22 +16,31 @@ pub const CAMERA_BIND_GROUP_LAYOUT: &'static wgpu::BindGroupLayoutDescriptor =
 		label: Some("Camera Bind group layout"),
 		entries: &[wgpu::BindGroupLayoutEntry {
 			binding: 0,
-			visibility: wgpu::ShaderStages::VERTEX,
+			visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
 			ty: wgpu::BindingType::Buffer {
 				ty: wgpu::BufferBindingType::Uniform,
 				has_dynamic_offset: false,
 				min_binding_size: core::num::NonZeroU64::new(
-					core::mem::size_of::<types::mat4x4f>() as u64,
+					core::mem::size_of::<CameraUniform>() as u64,
 				),
 			},
 			count: None,
 		}],
 	};
 
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct CameraUniform {
+	pub view_proj: types::mat4x4f,
+	pub position: types::vec4f,
+}
+
+impl HostShareable for CameraUniform {}
+
 #[derive(BindGroup)]
 #[layout(CAMERA_BIND_GROUP_LAYOUT)]
 pub struct CameraBindGroup<'a> {
-	pub camera: &'a Buffer<types::mat4x4f>,
+	pub camera: &'a Buffer<CameraUniform>,
 }
 
 pub struct Camera {
@@ -151,6 +160,11 @@ impl Camera {
 		inner.dirty = true;
 	}
 
+	// The matrix the camera uniform holds once the renderer was updated
+	pub fn view_proj(&self) -> glm::Mat4 {
+		self.renderer.cameras.get(&self.id).unwrap().view_proj(&self.renderer)
+	}
+
 	pub fn zfar(&self) -> f32 {
 		self.renderer.cameras.get(&self.id).unwrap().zfar
 	}
@@ -180,13 +194,13 @@ pub(crate) struct InternalCamera {
 
 	pub dirty: bool,
 
-	pub buffer: Buffer<types::mat4x4f>,
+	pub buffer: Buffer<CameraUniform>,
 	pub bind_group: CameraBindGroupNT,
 }
 
 impl InternalCamera {
 	pub fn new(renderer: &crate::InternalRenderer, descriptor: &CameraDescriptor) -> Self {
-		let buffer = Buffer::<types::mat4x4f>::new(
+		let buffer = Buffer::<CameraUniform>::new(
 			&renderer.device,
 			wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
 			false,
@@ -213,13 +227,7 @@ impl InternalCamera {
 		}
 	}
 
-	pub fn update(&mut self, renderer: &crate::InternalRenderer) {
-		if !self.dirty {
-			return;
-		}
-
-		self.dirty = false;
-
+	pub fn view_proj(&self, renderer: &crate::InternalRenderer) -> glm::Mat4 {
 		let mut view = glm::translate(&glm::Mat4x4::identity(), &self.position);
 		view = glm::quat_to_mat4(&self.rotation) * view;
 
@@ -238,9 +246,25 @@ impl InternalCamera {
 			self.zfar,
 		);
 
-		let matrix: [[f32; 4]; 4] = (proj * view).into();
+		proj * view
+	}
+
+	pub fn update(&mut self, renderer: &crate::InternalRenderer) {
+		if !self.dirty {
+			return;
+		}
+
+		self.dirty = false;
+
+		let matrix: [[f32; 4]; 4] = self.view_proj(renderer).into();
+
+		// The position translates the world, so the eye sits at its inverse
+		let uniform = CameraUniform {
+			view_proj: matrix.into(),
+			position: [-self.position.x, -self.position.y, -self.position.z, 1.0].into(),
+		};
 
-		self.buffer.write_to(&renderer.queue, &matrix.into());
+		self.buffer.write_to(&renderer.queue, &uniform);
 	}
 }
 
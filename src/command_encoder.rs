@@ -2,6 +2,7 @@ use std::{collections::HashMap, sync::Mutex};
 
 use dashmap::DashMap;
 
+pub mod deferred;
 pub mod forward;
 pub mod voxelization;
 pub mod present;
@@ -12,7 +13,21 @@ pub struct CommandEncoder<'renderer, 'camera> {
 	inner_camera: Option<dashmap::mapref::one::Ref<'renderer, u64, crate::camera::InternalCamera>>,
 	encoders: Vec<wgpu::CommandBuffer>,
 	resources: DashMap<core::any::TypeId, Box<dyn crate::Resource>>,
-	surface: Mutex<Option<wgpu::SurfaceTexture>>,
+	surface: Mutex<Option<FrameTarget>>,
+}
+
+enum FrameTarget {
+	Surface(wgpu::SurfaceTexture),
+	Offscreen(std::sync::Arc<wgpu::Texture>),
+}
+
+impl FrameTarget {
+	fn texture(&self) -> &wgpu::Texture {
+		match self {
+			FrameTarget::Surface(surface_texture) => &surface_texture.texture,
+			FrameTarget::Offscreen(texture) => texture,
+		}
+	}
 }
 
 impl<'renderer, 'camera> CommandEncoder<'renderer, 'camera> {
@@ -71,18 +86,35 @@ impl<'renderer, 'camera> CommandEncoder<'renderer, 'camera> {
 		MaterialData { materials }
 	}
 
+	pub fn get_lights(&self) -> Vec<crate::lights::PointLight> {
+		self.renderer.renderer.lights.iter().map(|light| *light).collect()
+	}
+
 	pub(crate) fn get_surface_texture_view(&self) -> wgpu::TextureView {
 		// TODO: Replace with camera/global resolution based function
 
+		self.acquire_frame()
+			.as_ref()
+			.unwrap()
+			.texture()
+			.create_view(&wgpu::TextureViewDescriptor::default())
+	}
+
+	fn acquire_frame(&self) -> std::sync::MutexGuard<'_, Option<FrameTarget>> {
 		let mut lock = self.surface.lock().unwrap();
-		
-		if lock.is_none() {
-			*lock = Some(self.renderer.renderer.surface.get_current_texture().unwrap());
 
-			lock.as_ref().unwrap().texture.create_view(&wgpu::TextureViewDescriptor::default())
-		} else {
-			lock.as_ref().unwrap().texture.create_view(&wgpu::TextureViewDescriptor::default())
+		if lock.is_none() {
+			*lock = Some(match &*self.renderer.renderer.target.lock().unwrap() {
+				crate::RenderTarget::Surface(surface) => {
+					FrameTarget::Surface(surface.get_current_texture().unwrap())
+				}
+				crate::RenderTarget::Offscreen(texture) => {
+					FrameTarget::Offscreen(std::sync::Arc::clone(texture))
+				}
+			});
 		}
+
+		lock
 	}
 
 	pub fn begin_pass<T: RenderPassTrait>(&mut self, pass: &mut T) {
@@ -101,10 +133,47 @@ impl<'renderer, 'camera> CommandEncoder<'renderer, 'camera> {
 
 		let mut lock = self.surface.lock().unwrap();
 
-		if lock.is_some() {
-			lock.take().unwrap().present();
+		if let Some(FrameTarget::Surface(surface_texture)) = lock.take() {
+			surface_texture.present();
 		}
 	}
+
+	// The frame is still submitted and presented when it can't be read back
+	pub fn finish_and_read(mut self) -> Result<image::RgbaImage, crate::readback::ReadbackError> {
+		let mut encoder = self
+			.device()
+			.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+				label: Some("Frame Readback Encoder"),
+			});
+
+		let readback = crate::readback::TextureReadback::new(
+			self.device(),
+			&mut encoder,
+			self.acquire_frame().as_ref().unwrap().texture(),
+		);
+		let readback = match readback {
+			Ok(readback) => readback,
+			Err(error) => {
+				self.finish();
+				return Err(error);
+			}
+		};
+
+		self.encoders.push(encoder.finish());
+
+		let renderer = self.renderer;
+		self.finish();
+
+		readback.read(renderer.device())
+	}
+
+	pub fn finish_and_save_png<P: AsRef<std::path::Path>>(self, path: P) -> image::ImageResult<()> {
+		let image = self
+			.finish_and_read()
+			.map_err(|error| image::ImageError::IoError(std::io::Error::other(error)))?;
+
+		crate::readback::save_png(&image, path)
+	}
 }
 
 pub trait RenderPassTrait {
@@ -0,0 +1,500 @@
+use crate::command_encoder::*;
+use wgpu_helper::bind_group::{BindGroup, BindGroupType};
+
+use super::forward::{draw_meshes, ConeTracingBuffer, ConeTracingSettings, VoxelBackend, VoxelReadLayouts};
+
+pub const GBUFFER_NORMAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+pub const GBUFFER_MATERIAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+// Render targets the GBufferPass fills and the DeferredLightingPass shades, reallocated on resize
+pub struct GBufferResource {
+	// Alpha is 1 wherever a mesh was drawn
+	pub albedo: crate::mesh::Texture,
+	// The world space normal after normal mapping
+	pub normal: crate::mesh::Texture,
+	// Roughness in green and metalness in blue, as glTF packs them
+	pub material: crate::mesh::Texture,
+	pub depth: crate::mesh::Texture,
+	pub resolution: [u32; 2],
+}
+
+impl GBufferResource {
+	pub fn new(device: &wgpu::Device, resolution: [u32; 2]) -> Self {
+		let new_target = |label, format| {
+			let texture = device.create_texture(&wgpu::TextureDescriptor {
+				label: Some(label),
+				size: wgpu::Extent3d {
+					width: resolution[0],
+					height: resolution[1],
+					depth_or_array_layers: 1,
+				},
+				mip_level_count: 1,
+				sample_count: 1,
+				dimension: wgpu::TextureDimension::D2,
+				format,
+				usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+				view_formats: &[],
+			});
+
+			crate::mesh::Texture {
+				view: texture.create_view(&Default::default()),
+				sampler: device.create_sampler(&wgpu::SamplerDescriptor::default()),
+				texture,
+			}
+		};
+
+		Self {
+			albedo: new_target("G-Buffer Albedo", crate::DIFFUSE_BUFFER_FORMAT),
+			normal: new_target("G-Buffer Normal", GBUFFER_NORMAL_FORMAT),
+			material: new_target("G-Buffer Material", GBUFFER_MATERIAL_FORMAT),
+			depth: new_target("G-Buffer Depth", crate::DEPTH_FORMAT),
+			resolution,
+		}
+	}
+}
+
+impl crate::Resource for GBufferResource {
+	// The GBufferPass reallocates it whenever the camera resolution differs
+	fn updated_settings(&mut self, _renderer: &crate::Renderer) {}
+}
+
+// Draws every mesh into the GBufferResource, nothing is lit until the DeferredLightingPass
+pub struct GBufferPass {
+	render_pipeline: wgpu::RenderPipeline,
+}
+
+impl GBufferPass {
+	pub fn new(renderer: &crate::Renderer) -> Self {
+		let shader = renderer
+			.device()
+			.create_shader_module(wgpu::ShaderModuleDescriptor {
+				label: Some("G-Buffer Shader"),
+				source: wgpu::ShaderSource::Wgsl(
+					format!(
+						"{}\n{}",
+						include_str!("./shaders/_mesh_vertex.wgsl"),
+						include_str!("./shaders/gbuffer.wgsl")
+					)
+					.into(),
+				),
+			});
+
+		let render_pipeline_layout =
+			renderer
+				.device()
+				.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+					label: Some("G-Buffer Pipeline layout"),
+					bind_group_layouts: &[
+						crate::camera::CameraBindGroup::get_bind_group_layout(renderer.device()),
+						crate::ModelBindGroup::get_bind_group_layout(renderer.device()),
+						crate::MaterialBindGroup::get_bind_group_layout(renderer.device()),
+					],
+					push_constant_ranges: &[],
+				});
+
+		let target = |format| {
+			Some(wgpu::ColorTargetState {
+				format,
+				blend: Some(wgpu::BlendState::REPLACE),
+				write_mask: wgpu::ColorWrites::ALL,
+			})
+		};
+
+		let render_pipeline =
+			renderer
+				.device()
+				.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+					label: Some("G-Buffer Pipeline"),
+					layout: Some(&render_pipeline_layout),
+					vertex: wgpu::VertexState {
+						module: &shader,
+						entry_point: "vs_main",
+						buffers: &[
+							crate::mesh::VertexPosition::desc(),
+							crate::mesh::VertexNormals::desc(),
+							crate::mesh::VertexColors::desc(),
+						],
+					},
+					fragment: Some(wgpu::FragmentState {
+						module: &shader,
+						entry_point: "fs_gbuffer",
+						targets: &[
+							target(crate::DIFFUSE_BUFFER_FORMAT),
+							target(GBUFFER_NORMAL_FORMAT),
+							target(GBUFFER_MATERIAL_FORMAT),
+						],
+					}),
+					primitive: wgpu::PrimitiveState {
+						topology: wgpu::PrimitiveTopology::TriangleList,
+						strip_index_format: None,
+						front_face: wgpu::FrontFace::Ccw,
+						cull_mode: None,
+						polygon_mode: wgpu::PolygonMode::Fill,
+						unclipped_depth: false,
+						conservative: false,
+					},
+					depth_stencil: Some(wgpu::DepthStencilState {
+						format: crate::DEPTH_FORMAT,
+						depth_write_enabled: true,
+						depth_compare: wgpu::CompareFunction::Less,
+						stencil: wgpu::StencilState::default(),
+						bias: wgpu::DepthBiasState::default(),
+					}),
+					multisample: wgpu::MultisampleState::default(),
+					multiview: None,
+				});
+
+		Self { render_pipeline }
+	}
+}
+
+impl RenderPassTrait for GBufferPass {
+	fn execute<'manager>(
+		&mut self,
+		command_encoder: &'manager CommandEncoder,
+		global_resources: &mut crate::ResourceManagerHandle<'manager>,
+	) -> Option<wgpu::CommandBuffer> {
+		let resolution = command_encoder.get_camera().unwrap().get_resolution();
+		let current = global_resources
+			.get_resource::<GBufferResource>()
+			.map(|gbuffer| gbuffer.resolution);
+		if current != Some(resolution) {
+			global_resources.insert_resource(GBufferResource::new(command_encoder.device(), resolution));
+		}
+
+		let gbuffer = global_resources.get_resource::<GBufferResource>().unwrap();
+		let meshes = command_encoder.get_meshes();
+		let materials = command_encoder.get_materials();
+
+		let mut encoder = command_encoder
+			.device()
+			.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+				label: Some("G-Buffer Encoder"),
+			});
+
+		let clear = |view| {
+			Some(wgpu::RenderPassColorAttachment {
+				view,
+				resolve_target: None,
+				ops: wgpu::Operations {
+					load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+					store: wgpu::StoreOp::Store,
+				},
+			})
+		};
+
+		{
+			let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+				label: Some("G-Buffer Pass"),
+				color_attachments: &[
+					clear(&gbuffer.albedo.view),
+					clear(&gbuffer.normal.view),
+					clear(&gbuffer.material.view),
+				],
+				depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+					view: &gbuffer.depth.view,
+					depth_ops: Some(wgpu::Operations {
+						load: wgpu::LoadOp::Clear(1.0),
+						store: wgpu::StoreOp::Store,
+					}),
+					stencil_ops: None,
+				}),
+				occlusion_query_set: None,
+				timestamp_writes: None,
+			});
+
+			render_pass.set_pipeline(&self.render_pipeline);
+			render_pass.set_bind_group(
+				0,
+				unsafe {
+					command_encoder
+						.get_camera_bind_group()
+						.unwrap()
+						.as_untyped()
+				},
+				&[],
+			);
+
+			draw_meshes(&mut render_pass, &meshes, &materials);
+		}
+
+		Some(encoder.finish())
+	}
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct DeferredLightingUniform {
+	inverse_view_proj: [[f32; 4]; 4],
+}
+
+// Cone traces once per pixel of the GBufferResource, from the same voxel structures as the ForwardRenderingPass
+pub struct DeferredLightingPass {
+	// One per VoxelBackend
+	render_pipelines: [wgpu::RenderPipeline; 3],
+	voxel_read_layouts: VoxelReadLayouts,
+	voxel_backend: VoxelBackend,
+	gbuffer_bind_group_layout: wgpu::BindGroupLayout,
+	lighting_bind_group_layout: wgpu::BindGroupLayout,
+	lighting_buffer: wgpu::Buffer,
+	cone_tracing: ConeTracingBuffer,
+	lights: crate::lights::LightsBuffer,
+}
+
+impl DeferredLightingPass {
+	pub fn new(renderer: &crate::Renderer) -> Self {
+		let gbuffer_entry = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+			binding,
+			visibility: wgpu::ShaderStages::FRAGMENT,
+			ty: wgpu::BindingType::Texture {
+				sample_type,
+				view_dimension: wgpu::TextureViewDimension::D2,
+				multisampled: false,
+			},
+			count: None,
+		};
+		let color_sample_type = wgpu::TextureSampleType::Float { filterable: false };
+
+		let gbuffer_bind_group_layout =
+			renderer
+				.device()
+				.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+					label: Some("G-Buffer Bind group layout"),
+					entries: &[
+						gbuffer_entry(0, color_sample_type),
+						gbuffer_entry(1, color_sample_type),
+						gbuffer_entry(2, color_sample_type),
+						gbuffer_entry(3, color_sample_type),
+					],
+				});
+
+		let lighting_bind_group_layout =
+			renderer
+				.device()
+				.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+					label: Some("Deferred Lighting Bind group layout"),
+					entries: &[wgpu::BindGroupLayoutEntry {
+						binding: 0,
+						visibility: wgpu::ShaderStages::FRAGMENT,
+						ty: wgpu::BindingType::Buffer {
+							ty: wgpu::BufferBindingType::Uniform,
+							has_dynamic_offset: false,
+							min_binding_size: None,
+						},
+						count: None,
+					}],
+				});
+
+		let voxel_read_layouts = VoxelReadLayouts::new(renderer.device());
+		let render_pipelines = VoxelBackend::ALL.map(|backend| {
+			new_lighting_pipeline(
+				renderer,
+				backend,
+				&[
+					crate::camera::CameraBindGroup::get_bind_group_layout(renderer.device()),
+					&gbuffer_bind_group_layout,
+					&lighting_bind_group_layout,
+					voxel_read_layouts.layout(backend),
+				],
+			)
+		});
+
+		let lighting_buffer = renderer.device().create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Deferred Lighting Buffer"),
+			size: std::mem::size_of::<DeferredLightingUniform>() as u64,
+			usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+
+		Self {
+			render_pipelines,
+			voxel_read_layouts,
+			voxel_backend: VoxelBackend::default(),
+			gbuffer_bind_group_layout,
+			lighting_bind_group_layout,
+			lighting_buffer,
+			cone_tracing: ConeTracingBuffer::new(renderer.device()),
+			lights: crate::lights::LightsBuffer::new(renderer.device()),
+		}
+	}
+
+	pub fn voxel_backend(&self) -> VoxelBackend {
+		self.voxel_backend
+	}
+
+	pub fn set_voxel_backend(&mut self, voxel_backend: VoxelBackend) {
+		self.voxel_backend = voxel_backend;
+	}
+
+	pub fn cone_tracing_settings(&self) -> ConeTracingSettings {
+		self.cone_tracing.settings()
+	}
+
+	pub fn set_cone_tracing_settings(&mut self, settings: ConeTracingSettings) {
+		self.cone_tracing.set_settings(settings);
+	}
+}
+
+// The full screen lighting shader with the voxel sampling functions of one backend appended
+fn new_lighting_pipeline(
+	renderer: &crate::Renderer,
+	voxel_backend: VoxelBackend,
+	bind_group_layouts: &[&wgpu::BindGroupLayout],
+) -> wgpu::RenderPipeline {
+	let shader = renderer
+		.device()
+		.create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("Deferred Lighting Shader"),
+			source: wgpu::ShaderSource::Wgsl(
+				format!(
+					"{}\n{}\n{}\n{}",
+					include_str!("./shaders/_lights.wgsl"),
+					include_str!("./shaders/_cone_tracing.wgsl"),
+					include_str!("./shaders/deferred_lighting.wgsl"),
+					voxel_backend.shader()
+				)
+				.into(),
+			),
+		});
+
+	let pipeline_layout =
+		renderer
+			.device()
+			.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+				label: Some("Deferred Lighting Pipeline layout"),
+				bind_group_layouts,
+				push_constant_ranges: &[],
+			});
+
+	renderer
+		.device()
+		.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+			label: Some("Deferred Lighting Pipeline"),
+			layout: Some(&pipeline_layout),
+			vertex: wgpu::VertexState {
+				module: &shader,
+				entry_point: "vs_fullscreen",
+				buffers: &[],
+			},
+			fragment: Some(wgpu::FragmentState {
+				module: &shader,
+				entry_point: "fs_lighting",
+				targets: &[Some(wgpu::ColorTargetState {
+					format: renderer.renderer.config.lock().unwrap().format,
+					blend: Some(wgpu::BlendState::REPLACE),
+					write_mask: wgpu::ColorWrites::ALL,
+				})],
+			}),
+			primitive: wgpu::PrimitiveState::default(),
+			depth_stencil: None,
+			multisample: wgpu::MultisampleState::default(),
+			multiview: None,
+		})
+}
+
+impl RenderPassTrait for DeferredLightingPass {
+	fn execute<'manager>(
+		&mut self,
+		command_encoder: &'manager CommandEncoder,
+		global_resources: &mut crate::ResourceManagerHandle<'manager>,
+	) -> Option<wgpu::CommandBuffer> {
+		// Nothing to shade before a GBufferPass ran
+		let gbuffer = global_resources.get_resource::<GBufferResource>()?;
+
+		let camera = command_encoder.get_camera().unwrap();
+		command_encoder.queue().write_buffer(
+			&self.lighting_buffer,
+			0,
+			bytemuck::cast_slice(&[DeferredLightingUniform {
+				inverse_view_proj: glm::inverse(&camera.view_proj()).into(),
+			}]),
+		);
+
+		let gbuffer_bind_group = command_encoder
+			.device()
+			.create_bind_group(&wgpu::BindGroupDescriptor {
+				label: Some("G-Buffer Bindgroup"),
+				layout: &self.gbuffer_bind_group_layout,
+				entries: &[
+					wgpu::BindGroupEntry {
+						binding: 0,
+						resource: wgpu::BindingResource::TextureView(&gbuffer.albedo.view),
+					},
+					wgpu::BindGroupEntry {
+						binding: 1,
+						resource: wgpu::BindingResource::TextureView(&gbuffer.normal.view),
+					},
+					wgpu::BindGroupEntry {
+						binding: 2,
+						resource: wgpu::BindingResource::TextureView(&gbuffer.material.view),
+					},
+					wgpu::BindGroupEntry {
+						binding: 3,
+						resource: wgpu::BindingResource::TextureView(&gbuffer.depth.view),
+					},
+				],
+			});
+		drop(gbuffer);
+
+		let lighting_bind_group = command_encoder
+			.device()
+			.create_bind_group(&wgpu::BindGroupDescriptor {
+				label: Some("Deferred Lighting Bindgroup"),
+				layout: &self.lighting_bind_group_layout,
+				entries: &[wgpu::BindGroupEntry {
+					binding: 0,
+					resource: self.lighting_buffer.as_entire_binding(),
+				}],
+			});
+
+		let voxels_bind_group = self.voxel_read_layouts.bind_group(
+			command_encoder,
+			global_resources,
+			self.voxel_backend,
+			self.cone_tracing.update(command_encoder.queue()),
+			self.lights.update(command_encoder.device(), command_encoder.queue(), &command_encoder.get_lights()),
+		);
+
+		let view = command_encoder.get_surface_texture_view();
+		let mut encoder = command_encoder
+			.device()
+			.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+				label: Some("Deferred Lighting Encoder"),
+			});
+
+		{
+			let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+				label: Some("Deferred Lighting Pass"),
+				color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+					view: &view,
+					resolve_target: None,
+					ops: wgpu::Operations {
+						load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+						store: wgpu::StoreOp::Store,
+					},
+				})],
+				depth_stencil_attachment: None,
+				occlusion_query_set: None,
+				timestamp_writes: None,
+			});
+
+			render_pass.set_pipeline(&self.render_pipelines[self.voxel_backend as usize]);
+			render_pass.set_bind_group(
+				0,
+				unsafe {
+					command_encoder
+						.get_camera_bind_group()
+						.unwrap()
+						.as_untyped()
+				},
+				&[],
+			);
+			render_pass.set_bind_group(1, &gbuffer_bind_group, &[]);
+			render_pass.set_bind_group(2, &lighting_bind_group, &[]);
+			render_pass.set_bind_group(3, &voxels_bind_group, &[]);
+			render_pass.draw(0..3, 0..1);
+		}
+
+		Some(encoder.finish())
+	}
+}
@@ -1,109 +1,270 @@
 use crate::command_encoder::*;
+use wgpu::util::DeviceExt;
 use wgpu_helper::types;
 use wgpu_helper::bind_group::{BindGroup, BindGroupType};
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConeTracingSettings {
+	pub diffuse_cone_count: u32,
+	// Tangent of the cone half angle
+	pub diffuse_cone_aperture: f32,
+	pub diffuse_max_distance: f32,
+	// The specular cone aperture follows the material roughness
+	pub specular_max_distance: f32,
+}
+
+impl Default for ConeTracingSettings {
+	fn default() -> Self {
+		Self {
+			diffuse_cone_count: 6,
+			diffuse_cone_aperture: 0.577,
+			diffuse_max_distance: 30.0,
+			specular_max_distance: 30.0,
+		}
+	}
+}
+
+impl ConeTracingSettings {
+	pub fn to_uniform(self) -> ConeTracingUniform {
+		ConeTracingUniform {
+			diffuse_cone_count: self.diffuse_cone_count.max(1),
+			diffuse_cone_aperture: self.diffuse_cone_aperture,
+			diffuse_max_distance: self.diffuse_max_distance,
+			specular_max_distance: self.specular_max_distance,
+			debug_view: ForwardDebugView::default() as u32,
+			_padding: [0; 3],
+		}
+	}
+}
+
+// What fs_main outputs in place of the shaded colour. Vectors are mapped from [-1, 1] to [0, 1]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ForwardDebugView {
+	#[default]
+	Shaded = 0,
+	// The interpolated vertex normal
+	WorldNormal = 1,
+	// The normal after the normal map was applied through the TBN basis
+	MappedNormal = 2,
+	// Wrapped to [0, 1] in red and green
+	Uv0 = 3,
+	VertexColor = 4,
+	// Roughness in green and metalness in blue, as glTF packs them
+	MetallicRoughness = 5,
+	// How much the voxels block the light, white is fully shadowed
+	ShadowOcclusion = 6,
+	// The cone traced indirect diffuse light on the albedo
+	DiffuseGi = 7,
+	// The specular cone weighted by the fresnel term
+	Specular = 8,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ConeTracingUniform {
+	diffuse_cone_count: u32,
+	diffuse_cone_aperture: f32,
+	diffuse_max_distance: f32,
+	specular_max_distance: f32,
+	// The ForwardDebugView shares the buffer of the cone tracing settings
+	debug_view: u32,
+	_padding: [u32; 3],
+}
+
+// The cone tracing settings a pass traces with, written to their uniform buffer once they changed
+pub(crate) struct ConeTracingBuffer {
+	settings: ConeTracingSettings,
+	debug_view: ForwardDebugView,
+	buffer: wgpu::Buffer,
+	dirty: bool,
+}
+
+impl ConeTracingBuffer {
+	pub(crate) fn new(device: &wgpu::Device) -> Self {
+		let settings = ConeTracingSettings::default();
+		let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+			label: Some("Cone Tracing Settings Buffer"),
+			contents: bytemuck::cast_slice(&[settings.to_uniform()]),
+			usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+		});
+
+		Self {
+			settings,
+			debug_view: ForwardDebugView::default(),
+			buffer,
+			dirty: false,
+		}
+	}
+
+	pub(crate) fn settings(&self) -> ConeTracingSettings {
+		self.settings
+	}
+
+	pub(crate) fn set_settings(&mut self, settings: ConeTracingSettings) {
+		self.settings = settings;
+		self.dirty = true;
+	}
+
+	pub(crate) fn debug_view(&self) -> ForwardDebugView {
+		self.debug_view
+	}
+
+	pub(crate) fn set_debug_view(&mut self, debug_view: ForwardDebugView) {
+		self.debug_view = debug_view;
+		self.dirty = true;
+	}
+
+	// Writes the uniform if anything changed since the last call
+	pub(crate) fn update(&mut self, queue: &wgpu::Queue) -> &wgpu::Buffer {
+		if self.dirty {
+			queue.write_buffer(
+				&self.buffer,
+				0,
+				bytemuck::cast_slice(&[ConeTracingUniform {
+					debug_view: self.debug_view as u32,
+					..self.settings.to_uniform()
+				}]),
+			);
+			self.dirty = false;
+		}
+
+		&self.buffer
+	}
+}
+
+// Cone traces the voxel structure of its VoxelBackend, the dense VoxelsResource by default
 pub struct ForwardRenderingPass {
-	render_pipeline: wgpu::RenderPipeline,
-	voxels_read_bind_group_layout: wgpu::BindGroupLayout,
+	// One per VoxelBackend
+	render_pipelines: [wgpu::RenderPipeline; 3],
+	voxel_read_layouts: VoxelReadLayouts,
+	voxel_backend: VoxelBackend,
+	cone_tracing: ConeTracingBuffer,
+	lights: crate::lights::LightsBuffer,
 }
 
 impl ForwardRenderingPass {
 	pub fn new(renderer: &crate::Renderer) -> Self {
-		let shader = renderer
-			.device()
-			.create_shader_module(wgpu::ShaderModuleDescriptor {
-				label: Some("Shader"),
-				source: wgpu::ShaderSource::Wgsl(
-					include_str!("./shaders/forward.wgsl").into(),
-				),
-			});
+		let voxel_read_layouts = VoxelReadLayouts::new(renderer.device());
+		let render_pipelines =
+			VoxelBackend::ALL.map(|backend| new_render_pipeline(renderer, backend, voxel_read_layouts.layout(backend)));
 
-		let voxels_read_bind_group_layout = renderer.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-			label: Some("Voxels Bind group layout"),
-			entries: &[
-				wgpu::BindGroupLayoutEntry {
-					binding: 0,
-					visibility: wgpu::ShaderStages::FRAGMENT,
-					ty: wgpu::BindingType::Texture {
-						sample_type: wgpu::TextureSampleType::Float { filterable: false },
-						view_dimension: wgpu::TextureViewDimension::D3,
-						multisampled: false,
-					},
-					count: None,
-				},
-				wgpu::BindGroupLayoutEntry {
-					binding: 1,
-					visibility: wgpu::ShaderStages::FRAGMENT,
-					ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
-					count: None,
-				},
-			],
-		});
+		Self {
+			render_pipelines,
+			voxel_read_layouts,
+			voxel_backend: VoxelBackend::default(),
+			cone_tracing: ConeTracingBuffer::new(renderer.device()),
+			lights: crate::lights::LightsBuffer::new(renderer.device()),
+		}
+	}
 
-		let render_pipeline_layout =
-			renderer
-				.device()
-				.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-					label: Some("Render Pipeline layout"),
-					bind_group_layouts: &[
-						&crate::camera::CameraBindGroup::get_bind_group_layout(&renderer.device()),
-						&crate::ModelBindGroup::get_bind_group_layout(&renderer.device()),
-						&crate::MaterialBindGroup::get_bind_group_layout(&renderer.device()),
-						&voxels_read_bind_group_layout,
-					],
-					push_constant_ranges: &[],
-				});
-
-		let render_pipeline =
-			renderer
-				.device()
-				.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-					label: Some("Render Pipeline"),
-					layout: Some(&render_pipeline_layout),
-					vertex: wgpu::VertexState {
-						module: &shader,
-						entry_point: "vs_main",
-						buffers: &[
-							crate::mesh::VertexPosition::desc(),
-							crate::mesh::VertexNormals::desc(),
-							crate::mesh::VertexColors::desc(),
-						],
-					},
-					fragment: Some(wgpu::FragmentState {
-						module: &shader,
-						entry_point: "fs_main",
-						targets: &[Some(wgpu::ColorTargetState {
-							format: renderer.renderer.config.lock().unwrap().format, // TODO: Change for better
-							blend: Some(wgpu::BlendState::REPLACE),
-							write_mask: wgpu::ColorWrites::ALL,
-						})],
-					}),
-					primitive: wgpu::PrimitiveState {
-						topology: wgpu::PrimitiveTopology::TriangleList,
-						strip_index_format: None,
-						front_face: wgpu::FrontFace::Ccw,
-						cull_mode: None,
-						polygon_mode: wgpu::PolygonMode::Fill,
-						unclipped_depth: false,
-						conservative: false,
-					},
-					depth_stencil: Some(wgpu::DepthStencilState {
-						format: crate::DEPTH_FORMAT,
-						depth_write_enabled: true,
-						depth_compare: wgpu::CompareFunction::Less,
-						stencil: wgpu::StencilState::default(),
-						bias: wgpu::DepthBiasState::default(),
-					}),
-					multisample: wgpu::MultisampleState {
-						count: 1,
-						mask: !0,
-						alpha_to_coverage_enabled: false,
-					},
-					multiview: None,
-				});
+	pub fn voxel_backend(&self) -> VoxelBackend {
+		self.voxel_backend
+	}
+
+	pub fn set_voxel_backend(&mut self, voxel_backend: VoxelBackend) {
+		self.voxel_backend = voxel_backend;
+	}
+
+	pub fn cone_tracing_settings(&self) -> ConeTracingSettings {
+		self.cone_tracing.settings()
+	}
+
+	pub fn set_cone_tracing_settings(&mut self, settings: ConeTracingSettings) {
+		self.cone_tracing.set_settings(settings);
+	}
 
-		Self { render_pipeline, voxels_read_bind_group_layout }
+	pub fn debug_view(&self) -> ForwardDebugView {
+		self.cone_tracing.debug_view()
 	}
+
+	pub fn set_debug_view(&mut self, debug_view: ForwardDebugView) {
+		self.cone_tracing.set_debug_view(debug_view);
+	}
+}
+
+// The forward shader with the voxel sampling functions of one backend appended
+fn new_render_pipeline(
+	renderer: &crate::Renderer,
+	voxel_backend: VoxelBackend,
+	voxels_read_bind_group_layout: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+	let shader = renderer
+		.device()
+		.create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("Shader"),
+			source: wgpu::ShaderSource::Wgsl(
+				format!(
+					"{}\n{}\n{}\n{}\n{}",
+					include_str!("./shaders/_mesh_vertex.wgsl"),
+					include_str!("./shaders/_lights.wgsl"),
+					include_str!("./shaders/_cone_tracing.wgsl"),
+					include_str!("./shaders/forward.wgsl"),
+					voxel_backend.shader()
+				)
+				.into(),
+			),
+		});
+
+	let render_pipeline_layout =
+		renderer
+			.device()
+			.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+				label: Some("Render Pipeline layout"),
+				bind_group_layouts: &[
+					crate::camera::CameraBindGroup::get_bind_group_layout(renderer.device()),
+					crate::ModelBindGroup::get_bind_group_layout(renderer.device()),
+					crate::MaterialBindGroup::get_bind_group_layout(renderer.device()),
+					voxels_read_bind_group_layout,
+				],
+				push_constant_ranges: &[],
+			});
+
+	renderer
+		.device()
+		.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+			label: Some("Render Pipeline"),
+			layout: Some(&render_pipeline_layout),
+			vertex: wgpu::VertexState {
+				module: &shader,
+				entry_point: "vs_main",
+				buffers: &[
+					crate::mesh::VertexPosition::desc(),
+					crate::mesh::VertexNormals::desc(),
+					crate::mesh::VertexColors::desc(),
+				],
+			},
+			fragment: Some(wgpu::FragmentState {
+				module: &shader,
+				entry_point: "fs_main",
+				targets: &[Some(wgpu::ColorTargetState {
+					format: renderer.renderer.config.lock().unwrap().format, // TODO: Change for better
+					blend: Some(wgpu::BlendState::REPLACE),
+					write_mask: wgpu::ColorWrites::ALL,
+				})],
+			}),
+			primitive: wgpu::PrimitiveState {
+				topology: wgpu::PrimitiveTopology::TriangleList,
+				strip_index_format: None,
+				front_face: wgpu::FrontFace::Ccw,
+				cull_mode: None,
+				polygon_mode: wgpu::PolygonMode::Fill,
+				unclipped_depth: false,
+				conservative: false,
+			},
+			depth_stencil: Some(wgpu::DepthStencilState {
+				format: crate::DEPTH_FORMAT,
+				depth_write_enabled: true,
+				depth_compare: wgpu::CompareFunction::Less,
+				stencil: wgpu::StencilState::default(),
+				bias: wgpu::DepthBiasState::default(),
+			}),
+			multisample: wgpu::MultisampleState {
+				count: 1,
+				mask: !0,
+				alpha_to_coverage_enabled: false,
+			},
+			multiview: None,
+		})
 }
 
 impl RenderPassTrait for ForwardRenderingPass {
@@ -130,22 +291,15 @@ impl RenderPassTrait for ForwardRenderingPass {
 		let meshes = command_encoder.get_meshes();
 		let materials = command_encoder.get_materials();
 
-		let voxels_resource = global_resources.get_resource::<super::voxelization::VoxelsResource>().unwrap();
-		let voxels_bind_group = command_encoder.device().create_bind_group(&wgpu::BindGroupDescriptor {
-			label: None,
-			layout: &self.voxels_read_bind_group_layout,
-			entries: &[
-				wgpu::BindGroupEntry {
-					binding: 0,
-					resource: wgpu::BindingResource::TextureView(&voxels_resource.color.view),
-				},
-				wgpu::BindGroupEntry {
-					binding: 1,
-					resource: wgpu::BindingResource::Sampler(&voxels_resource.color.sampler),
-				},
-			]
-		});
-		
+		let voxels_bind_group = self.voxel_read_layouts.bind_group(
+			command_encoder,
+			global_resources,
+			self.voxel_backend,
+			self.cone_tracing.update(command_encoder.queue()),
+			self.lights.update(command_encoder.device(), command_encoder.queue(), &command_encoder.get_lights()),
+		);
+		let render_pipeline = &self.render_pipelines[self.voxel_backend as usize];
+
 		{
 			let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
 				label: Some("Render Pass"),
@@ -174,27 +328,11 @@ impl RenderPassTrait for ForwardRenderingPass {
 				timestamp_writes: None,
 			});
 
-			render_pass.set_pipeline(&self.render_pipeline);
+			render_pass.set_pipeline(render_pipeline);
 			render_pass.set_bind_group(0, unsafe { &command_encoder.get_camera_bind_group().unwrap().as_untyped() }, &[]);
 			render_pass.set_bind_group(3, &voxels_bind_group, &[]);
 
-			for mesh in meshes.meshes.iter() {
-				render_pass.set_bind_group(1, &mesh.model_bind_group, &[]);
-				render_pass
-					.set_vertex_buffer(0, mesh.vertex_buffer.slice(mesh.positions.to_owned()));
-				render_pass.set_vertex_buffer(1, mesh.vertex_buffer.slice(mesh.normals.to_owned()));
-				render_pass.set_vertex_buffer(2, mesh.vertex_buffer.slice(mesh.colors.to_owned()));
-				render_pass
-					.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-				for primitive in mesh.primitives.iter() {
-					render_pass.set_bind_group(
-						2,
-						&materials.materials.get(&primitive.material).unwrap().bind_group,
-						&[],
-					);
-					render_pass.draw_indexed(primitive.index.to_owned(), 0, 0..1);
-				}
-			}
+			draw_meshes(&mut render_pass, &meshes, &materials);
 		}
 
 		
@@ -202,3 +340,343 @@ impl RenderPassTrait for ForwardRenderingPass {
 		Some(encoder.finish())
 	}
 }
+
+// Every primitive with its model and material, the camera and any other bind groups are set by the caller
+pub(crate) fn draw_meshes<'pass>(
+	render_pass: &mut wgpu::RenderPass<'pass>,
+	meshes: &'pass MeshData,
+	materials: &'pass MaterialData,
+) {
+	for mesh in meshes.meshes.iter() {
+		render_pass.set_bind_group(1, &mesh.model_bind_group, &[]);
+		render_pass
+			.set_vertex_buffer(0, mesh.vertex_buffer.slice(mesh.positions.to_owned()));
+		render_pass.set_vertex_buffer(1, mesh.vertex_buffer.slice(mesh.normals.to_owned()));
+		render_pass.set_vertex_buffer(2, mesh.vertex_buffer.slice(mesh.colors.to_owned()));
+		render_pass
+			.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+		for primitive in mesh.primitives.iter() {
+			render_pass.set_bind_group(
+				2,
+				&materials.materials.get(&primitive.material).unwrap().bind_group,
+				&[],
+			);
+			render_pass.draw_indexed(primitive.index.to_owned(), 0, 0..1);
+		}
+	}
+}
+
+// Which voxel structure the cone tracing samples, each has its own vol_sample functions and bind group layout.
+// The resource of the chosen backend has to exist once the pass runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VoxelBackend {
+	// The VoxelsResource of a VoxelizationPass
+	#[default]
+	Dense = 0,
+	// The VoxelClipmapResource of a ClipmapVoxelizationPass
+	Clipmap = 1,
+	// The SparseVoxelOctreeResource of a SparseVoxelOctreePass
+	Octree = 2,
+}
+
+impl VoxelBackend {
+	pub(crate) const ALL: [Self; 3] = [Self::Dense, Self::Clipmap, Self::Octree];
+
+	pub(crate) fn shader(self) -> &'static str {
+		match self {
+			Self::Dense => include_str!("./shaders/forward_dense.wgsl"),
+			Self::Clipmap => include_str!("./shaders/forward_clipmap.wgsl"),
+			Self::Octree => include_str!("./shaders/forward_svo.wgsl"),
+		}
+	}
+}
+
+// Group 3 of the forward and the deferred lighting shaders, binding 3 is always the cone tracing uniform and binding 10 the
+// scene's lights
+pub(crate) struct VoxelReadLayouts {
+	dense: wgpu::BindGroupLayout,
+	clipmap: wgpu::BindGroupLayout,
+	octree: wgpu::BindGroupLayout,
+}
+
+// The bindings every backend's group 3 has besides its volumes
+struct SharedVoxelReadBuffers<'a> {
+	cone_tracing: &'a wgpu::Buffer,
+	lights: &'a wgpu::Buffer,
+}
+
+impl VoxelReadLayouts {
+	pub(crate) fn new(device: &wgpu::Device) -> Self {
+		// The six directional volumes of VoxelMipMode::Anisotropic, +X to -Z
+		let anisotropic_entry = |binding| wgpu::BindGroupLayoutEntry {
+			binding,
+			visibility: wgpu::ShaderStages::FRAGMENT,
+			ty: wgpu::BindingType::Texture {
+				sample_type: wgpu::TextureSampleType::Float { filterable: false },
+				view_dimension: wgpu::TextureViewDimension::D3,
+				multisampled: false,
+			},
+			count: None,
+		};
+
+		let voxels_read_bind_group_layout_entries = [
+				wgpu::BindGroupLayoutEntry {
+					binding: 0,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Texture {
+						sample_type: wgpu::TextureSampleType::Float { filterable: false },
+						view_dimension: wgpu::TextureViewDimension::D3,
+						multisampled: false,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 1,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 2,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: false,
+						min_binding_size: None,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 3,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: false,
+						min_binding_size: None,
+					},
+					count: None,
+				},
+				anisotropic_entry(4),
+				anisotropic_entry(5),
+				anisotropic_entry(6),
+				anisotropic_entry(7),
+				anisotropic_entry(8),
+				anisotropic_entry(9),
+				wgpu::BindGroupLayoutEntry {
+					binding: 10,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Storage { read_only: true },
+						has_dynamic_offset: false,
+						min_binding_size: None,
+					},
+					count: None,
+				},
+		];
+
+		let voxels_read_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+			label: Some("Voxels Bind group layout"),
+			entries: &voxels_read_bind_group_layout_entries,
+		});
+
+		let clipmap_read_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+			label: Some("Voxel Clipmap Bind group layout"),
+			entries: &[
+				voxels_read_bind_group_layout_entries[0],
+				voxels_read_bind_group_layout_entries[1],
+				voxels_read_bind_group_layout_entries[2],
+				voxels_read_bind_group_layout_entries[3],
+				voxels_read_bind_group_layout_entries[10],
+			],
+		});
+
+		let octree_pool_entry = |binding| wgpu::BindGroupLayoutEntry {
+			binding,
+			visibility: wgpu::ShaderStages::FRAGMENT,
+			ty: wgpu::BindingType::Buffer {
+				ty: wgpu::BufferBindingType::Storage { read_only: true },
+				has_dynamic_offset: false,
+				min_binding_size: None,
+			},
+			count: None,
+		};
+
+		let octree_read_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+			label: Some("Voxel Octree Bind group layout"),
+			entries: &[
+				octree_pool_entry(0),
+				octree_pool_entry(1),
+				voxels_read_bind_group_layout_entries[2],
+				voxels_read_bind_group_layout_entries[3],
+				voxels_read_bind_group_layout_entries[10],
+			],
+		});
+
+		Self {
+			dense: voxels_read_bind_group_layout,
+			clipmap: clipmap_read_bind_group_layout,
+			octree: octree_read_bind_group_layout,
+		}
+	}
+
+	pub(crate) fn layout(&self, backend: VoxelBackend) -> &wgpu::BindGroupLayout {
+		match backend {
+			VoxelBackend::Dense => &self.dense,
+			VoxelBackend::Clipmap => &self.clipmap,
+			VoxelBackend::Octree => &self.octree,
+		}
+	}
+
+	pub(crate) fn bind_group(
+		&self,
+		command_encoder: &CommandEncoder,
+		global_resources: &crate::ResourceManagerHandle,
+		backend: VoxelBackend,
+		cone_tracing_buffer: &wgpu::Buffer,
+		lights_buffer: &wgpu::Buffer,
+	) -> wgpu::BindGroup {
+		let shared = SharedVoxelReadBuffers {
+			cone_tracing: cone_tracing_buffer,
+			lights: lights_buffer,
+		};
+
+		match backend {
+			VoxelBackend::Dense => {
+				let voxels_resource = global_resources
+					.get_resource::<super::voxelization::VoxelsResource>()
+					.expect("VoxelBackend::Dense needs a VoxelsResource");
+				self.new_dense_bind_group(command_encoder, &shared, &voxels_resource)
+			}
+			VoxelBackend::Clipmap => {
+				let clipmap_resource = global_resources
+					.get_resource::<super::voxelization::VoxelClipmapResource>()
+					.expect("VoxelBackend::Clipmap needs a VoxelClipmapResource");
+				self.new_clipmap_bind_group(command_encoder, &shared, &clipmap_resource)
+			}
+			VoxelBackend::Octree => {
+				let octree_resource = global_resources
+					.get_resource::<super::voxelization::SparseVoxelOctreeResource>()
+					.expect("VoxelBackend::Octree needs a SparseVoxelOctreeResource");
+				self.new_octree_bind_group(command_encoder, &shared, &octree_resource)
+			}
+		}
+	}
+
+	fn new_dense_bind_group(
+		&self,
+		command_encoder: &CommandEncoder,
+		shared: &SharedVoxelReadBuffers,
+		voxels_resource: &super::voxelization::VoxelsResource,
+	) -> wgpu::BindGroup {
+		// Isotropic volumes never sample the directional bindings, so they just get the radiance
+		let anisotropic_views: Vec<&wgpu::TextureView> = match &voxels_resource.anisotropic_radiance {
+			Some(textures) => textures.iter().map(|texture| &texture.view).collect(),
+			None => vec![&voxels_resource.radiance.view; 6],
+		};
+		let anisotropic_entries = anisotropic_views
+			.iter()
+			.enumerate()
+			.map(|(i, view)| wgpu::BindGroupEntry {
+				binding: 4 + i as u32,
+				resource: wgpu::BindingResource::TextureView(view),
+			});
+
+		command_encoder.device().create_bind_group(&wgpu::BindGroupDescriptor {
+			label: None,
+			layout: &self.dense,
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: wgpu::BindingResource::TextureView(&voxels_resource.radiance.view),
+				},
+				wgpu::BindGroupEntry {
+					binding: 1,
+					resource: wgpu::BindingResource::Sampler(&voxels_resource.radiance.sampler),
+				},
+				wgpu::BindGroupEntry {
+					binding: 2,
+					resource: voxels_resource.volume_buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 3,
+					resource: shared.cone_tracing.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 10,
+					resource: shared.lights.as_entire_binding(),
+				},
+			]
+			.into_iter()
+			.chain(anisotropic_entries)
+			.collect::<Vec<_>>(),
+		})
+	}
+
+	fn new_clipmap_bind_group(
+		&self,
+		command_encoder: &CommandEncoder,
+		shared: &SharedVoxelReadBuffers,
+		clipmap_resource: &super::voxelization::VoxelClipmapResource,
+	) -> wgpu::BindGroup {
+		command_encoder.device().create_bind_group(&wgpu::BindGroupDescriptor {
+			label: None,
+			layout: &self.clipmap,
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: wgpu::BindingResource::TextureView(&clipmap_resource.radiance.view),
+				},
+				wgpu::BindGroupEntry {
+					binding: 1,
+					resource: wgpu::BindingResource::Sampler(&clipmap_resource.radiance.sampler),
+				},
+				wgpu::BindGroupEntry {
+					binding: 2,
+					resource: clipmap_resource.clipmap_buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 3,
+					resource: shared.cone_tracing.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 10,
+					resource: shared.lights.as_entire_binding(),
+				},
+			],
+		})
+	}
+
+	fn new_octree_bind_group(
+		&self,
+		command_encoder: &CommandEncoder,
+		shared: &SharedVoxelReadBuffers,
+		octree_resource: &super::voxelization::SparseVoxelOctreeResource,
+	) -> wgpu::BindGroup {
+		command_encoder.device().create_bind_group(&wgpu::BindGroupDescriptor {
+			label: None,
+			layout: &self.octree,
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: octree_resource.nodes.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 1,
+					resource: octree_resource.values.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 2,
+					resource: octree_resource.octree_buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 3,
+					resource: shared.cone_tracing.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 10,
+					resource: shared.lights.as_entire_binding(),
+				},
+			],
+		})
+	}
+}
@@ -0,0 +1,150 @@
+// Lighting shared by the forward and the deferred path.
+// Bindings 0 to 2 and the vol_sample functions come from the voxel backend, forward_dense.wgsl, forward_clipmap.wgsl or forward_svo.wgsl.
+// PointLight and point_light_irradiance come from _lights.wgsl
+
+struct ConeTracingSettings {
+	diffuse_cone_count: u32,
+	diffuse_cone_aperture: f32,
+	diffuse_max_distance: f32,
+	specular_max_distance: f32,
+	// ForwardDebugView, 0 shades normally
+	debug_view: u32,
+};
+
+@group(3) @binding(3)
+var<uniform> cone_tracing: ConeTracingSettings;
+@group(3) @binding(10)
+var<storage, read> lights: Lights;
+
+struct SurfaceLight {
+    // The shaded colour, the other fields are the terms it was made of
+    color: vec3<f32>,
+    shadow: f32,
+    indirect: vec4<f32>,
+    specular: vec4<f32>,
+    fresnel: vec3<f32>,
+};
+
+fn light_surface(position: vec3<f32>, pixel_normal: vec3<f32>, albedo: vec3<f32>, roughness: f32, metallic: f32, view_dir: vec3<f32>) -> SurfaceLight {
+    var out: SurfaceLight;
+    // The shadow debug view shows the first light
+    out.shadow = 0.0;
+    var direct = vec3(0.0);
+    for (var i: u32 = 0u; i < lights.count; i++) {
+        let light = lights.lights[i];
+        let shadow = shadow_trace(position, pixel_normal, light.position);
+        if i == 0u {
+            out.shadow = shadow;
+        }
+        direct += point_light_irradiance(light, position, pixel_normal) * (1.0 - shadow);
+    }
+    out.indirect = diffuse_trace(position, pixel_normal);
+    out.specular = specular_trace(position, pixel_normal, view_dir, roughness);
+
+    let f0 = mix(vec3(0.04), albedo, metallic);
+    out.fresnel = fresnel_schlick(max(dot(pixel_normal, view_dir), 0.0), f0);
+
+    let diffuse = albedo * (1.0 - metallic) * (vec3(0.01) + direct + out.indirect.rgb);
+    out.color = ((1.0 - out.fresnel) * diffuse) + (out.fresnel * out.specular.rgb);
+
+    return out;
+}
+
+fn shadow_trace(position: vec3<f32>, nrm: vec3<f32>, light_position: vec3<f32>) -> f32 {
+    let origin = position + (nrm * 0.1);
+    let dir = normalize(light_position - origin);
+
+    let max_dist = distance(origin, light_position);
+    var dist = 0.0;
+    var occlusion = 0.0;
+
+    while (dist < max_dist && occlusion < 1.0) {
+		var c = origin + (dir * dist);
+        dist += finest_voxel_size();
+		
+        let voxel = vol_sample(finest_voxel_size(), c);
+        let v_occlusion = voxel.a;
+        occlusion = occlusion + (1.0 - occlusion) * v_occlusion;
+	}
+
+    return occlusion;
+}
+
+const PI: f32 = 3.14159265359;
+
+// Side cones are spread evenly around the normal, tilted 60 degrees away from it
+const SIDE_CONE_COS: f32 = 0.5;
+const SIDE_CONE_SIN: f32 = 0.8660254;
+
+// Returns the cosine weighted indirect radiance in rgb and the ambient occlusion in alpha
+fn diffuse_trace(position: vec3<f32>, nrm: vec3<f32>) -> vec4<f32> {
+    let origin = position + (nrm * finest_voxel_size());
+
+    var helper = vec3(1.0, 0.0, 0.0);
+    if abs(nrm.x) > 0.99 {
+        helper = vec3(0.0, 0.0, 1.0);
+    }
+    let x = normalize(cross(nrm, helper));
+    let z = cross(nrm, x);
+
+    let cone_count = max(cone_tracing.diffuse_cone_count, 1u);
+    let side_cone_count = f32(cone_count - 1u);
+
+    var result = vec4(0.0);
+    var total_weight = 0.0;
+
+    for (var i: u32 = 0u; i < cone_count; i++) {
+        var dir = nrm;
+        var weight = 1.0;
+
+        if i > 0u {
+            let phi = (f32(i - 1u) / side_cone_count) * 2.0 * PI;
+            dir = normalize((nrm * SIDE_CONE_COS) + (((x * cos(phi)) + (z * sin(phi))) * SIDE_CONE_SIN));
+            weight = SIDE_CONE_COS;
+        }
+
+        result += weight * trace_diffuse_cone(origin, dir);
+        total_weight += weight;
+    }
+
+    return result / total_weight;
+}
+
+// Traces a single cone along the reflected view vector, rougher surfaces get a wider cone
+fn specular_trace(position: vec3<f32>, nrm: vec3<f32>, view_dir: vec3<f32>, roughness: f32) -> vec4<f32> {
+    let origin = position + (nrm * finest_voxel_size());
+    let dir = normalize(reflect(-view_dir, nrm));
+    let aperture = tan(clamp(roughness, 0.0, 1.0) * PI * 0.25);
+
+    return trace_cone(origin, dir, aperture, cone_tracing.specular_max_distance);
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
+    return f0 + ((1.0 - f0) * pow(1.0 - cos_theta, 5.0));
+}
+
+fn trace_diffuse_cone(start: vec3<f32>, dir: vec3<f32>) -> vec4<f32> {
+    return trace_cone(start, dir, cone_tracing.diffuse_cone_aperture, cone_tracing.diffuse_max_distance);
+}
+
+// Accumulates radiance and occlusion front to back, aperture is the tangent of the cone half angle
+fn trace_cone(start: vec3<f32>, dir: vec3<f32>, aperture: f32, max_distance: f32) -> vec4<f32> {
+    var dist = finest_voxel_size();
+
+    var color = vec3(0.0);
+    var occlusion = 0.0;
+    while (dist < max_distance && occlusion < 1.0) {
+        let c = start + (dir * dist);
+        let diameter = max(finest_voxel_size(), 2.0 * aperture * dist);
+
+        let voxel = vol_sample_directional(diameter, c, dir);
+
+        // Mip-mapped voxels are averaged with empty neighbours, so their color is already premultiplied
+        color += (1.0 - occlusion) * voxel.rgb;
+        occlusion += (1.0 - occlusion) * voxel.a;
+
+        dist += diameter * 0.5;
+    }
+
+    return vec4(color, occlusion);
+}
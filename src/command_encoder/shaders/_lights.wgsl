@@ -0,0 +1,27 @@
+// The scene's point lights, as lights::LightsBuffer lays them out
+
+struct PointLight {
+	position: vec3<f32>,
+	color: vec3<f32>,
+	intensity: f32,
+};
+
+struct Lights {
+	count: u32,
+	lights: array<PointLight>,
+};
+
+// Light arriving at a surface before shadowing, falling off with the inverse square of the distance as
+// KHR_lights_punctual defines it. A zero normal, which thin two-sided voxels average to, faces every light
+fn point_light_irradiance(light: PointLight, position: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+	let to_light = light.position - position;
+	let distance_squared = max(dot(to_light, to_light), 1e-4);
+	let light_dir = to_light * inverseSqrt(distance_squared);
+
+	var n_dot_l = 1.0;
+	if dot(normal, normal) > 0.0 {
+		n_dot_l = max(dot(normal, light_dir), 0.0);
+	}
+
+	return light.color * light.intensity * n_dot_l / distance_squared;
+}
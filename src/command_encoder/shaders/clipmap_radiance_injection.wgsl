@@ -0,0 +1,96 @@
+struct ClipmapLevel {
+	min: vec3<f32>,
+	voxel_size: f32,
+};
+
+struct VoxelClipmap {
+	resolution: u32,
+	level_count: u32,
+	levels: array<ClipmapLevel, 8>,
+};
+
+@group(0) @binding(0)
+var<uniform> clipmap: VoxelClipmap;
+@group(0) @binding(1)
+var voxels_color: texture_3d<f32>;
+@group(0) @binding(2)
+var voxels_radiance: texture_storage_3d<rgba8unorm, write>;
+@group(0) @binding(3)
+var voxels_emissive: texture_3d<f32>;
+@group(0) @binding(4)
+var voxels_normal: texture_3d<f32>;
+
+@group(1) @binding(0)
+var<storage, read> lights: Lights;
+
+@compute @workgroup_size(4, 4, 4)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+	if any(id >= vec3(clipmap.resolution, clipmap.resolution, clipmap.resolution * clipmap.level_count)) {
+		return;
+	}
+
+	let albedo = textureLoad(voxels_color, id, 0);
+
+	if albedo.a == 0.0 {
+		textureStore(voxels_radiance, id, vec4(0.0));
+		return;
+	}
+
+	let level = id.z / clipmap.resolution;
+	let local = vec3(id.xy, id.z % clipmap.resolution);
+	let position = clipmap.levels[level].min + ((vec3<f32>(local) + 0.5) * clipmap.levels[level].voxel_size);
+
+	let normal = textureLoad(voxels_normal, id, 0).xyz;
+
+	var radiance = vec3(0.0);
+	for (var i: u32 = 0u; i < lights.count; i++) {
+		let light = lights.lights[i];
+		let visibility = 1.0 - shadow_trace(position, light.position, level);
+
+		radiance += point_light_irradiance(light, position, normal) * visibility;
+	}
+
+	let emissive = textureLoad(voxels_emissive, id, 0);
+
+	textureStore(voxels_radiance, id, vec4((albedo.rgb * radiance) + emissive.rgb, albedo.a));
+}
+
+// Finest level from min_level up that contains the position, level_count if there is none
+fn find_level(position: vec3<f32>, min_level: u32) -> u32 {
+	for (var level = min_level; level < clipmap.level_count; level++) {
+		let local = (position - clipmap.levels[level].min) / clipmap.levels[level].voxel_size;
+		if all(local >= vec3(0.0)) && all(local < vec3(f32(clipmap.resolution))) {
+			return level;
+		}
+	}
+
+	return clipmap.level_count;
+}
+
+fn shadow_trace(origin: vec3<f32>, light_position: vec3<f32>, start_level: u32) -> f32 {
+	let dir = normalize(light_position - origin);
+	let max_dist = distance(origin, light_position);
+
+	// Start outside the voxel's own surface
+	var dist = clipmap.levels[start_level].voxel_size * 2.0;
+	var occlusion = 0.0;
+	var level = start_level;
+
+	while (dist < max_dist && occlusion < 1.0) {
+		let position = origin + (dir * dist);
+
+		// Rays only ever leave a level, so the search can start at the current one
+		level = find_level(position, level);
+		if level >= clipmap.level_count {
+			break;
+		}
+
+		let local = vec3<u32>((position - clipmap.levels[level].min) / clipmap.levels[level].voxel_size);
+		let voxel = textureLoad(voxels_color, local + vec3(0u, 0u, level * clipmap.resolution), 0);
+		occlusion += (1.0 - occlusion) * voxel.a;
+
+		dist += clipmap.levels[level].voxel_size;
+	}
+
+	return occlusion;
+}
@@ -0,0 +1,55 @@
+// Full screen pass of the DeferredLightingPass, appended to _cone_tracing.wgsl
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    position: vec4<f32>,
+};
+
+struct DeferredLighting {
+    inverse_view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0)
+var g_albedo: texture_2d<f32>;
+@group(1) @binding(1)
+var g_normal: texture_2d<f32>;
+@group(1) @binding(2)
+var g_material: texture_2d<f32>;
+@group(1) @binding(3)
+// Bound as a float texture, GLSL has no texel fetch from depth textures
+var g_depth: texture_2d<f32>;
+
+@group(2) @binding(0)
+var<uniform> deferred: DeferredLighting;
+
+// A single triangle covering the screen
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    return vec4(f32(vertex_index & 1u) * 4.0 - 1.0, f32(vertex_index >> 1u) * 4.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_lighting(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(position.xy);
+    let albedo = textureLoad(g_albedo, pixel, 0);
+    if albedo.a == 0.0 {
+        return vec4(0.0, 0.0, 0.0, 1.0);
+    }
+
+    let normal = normalize(textureLoad(g_normal, pixel, 0).xyz);
+    let material = textureLoad(g_material, pixel, 0);
+    let depth = textureLoad(g_depth, pixel, 0).r;
+
+    // The world position is rebuilt from the depth, y points down in pixels but up in clip space
+    let uv = position.xy / vec2<f32>(textureDimensions(g_albedo));
+    let world = deferred.inverse_view_proj * vec4(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let world_position = world.xyz / world.w;
+
+    let view_dir = normalize(camera.position.xyz - world_position);
+    let light = light_surface(world_position, normal, albedo.rgb, material.g, material.b, view_dir);
+
+    return vec4(light.color, 1.0);
+}
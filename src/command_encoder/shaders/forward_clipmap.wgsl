@@ -0,0 +1,45 @@
+// VoxelClipmapResource backend of forward.wgsl
+
+struct ClipmapLevel {
+	min: vec3<f32>,
+	voxel_size: f32,
+};
+
+struct VoxelClipmap {
+	resolution: u32,
+	level_count: u32,
+	levels: array<ClipmapLevel, 8>,
+};
+
+@group(3) @binding(0)
+var voxels_radiance: texture_3d<f32>;
+@group(3) @binding(1)
+var voxels_radiance_s: sampler;
+@group(3) @binding(2)
+var<uniform> clipmap: VoxelClipmap;
+
+fn finest_voxel_size() -> f32 {
+    return clipmap.levels[0].voxel_size;
+}
+
+// Levels stand in for mips, falling back to coarser levels once the position leaves a finer one
+fn vol_sample(diameter: f32, position: vec3<f32>) -> vec4<f32> {
+    let last_level = f32(clipmap.level_count - 1u);
+    let wanted_level = u32(clamp(floor(log2(diameter / finest_voxel_size())), 0.0, last_level));
+
+    for (var level = wanted_level; level < clipmap.level_count; level++) {
+        let extent = clipmap.levels[level].voxel_size * f32(clipmap.resolution);
+        let local = (position - clipmap.levels[level].min) / extent;
+
+        if all(local >= vec3(0.0)) && all(local < vec3(1.0)) {
+            let uvw = vec3(local.xy, (f32(level) + local.z) / f32(clipmap.level_count));
+            return textureSampleLevel(voxels_radiance, voxels_radiance_s, uvw, 0.0);
+        }
+    }
+
+    return vec4(0.0);
+}
+
+fn vol_sample_directional(diameter: f32, position: vec3<f32>, dir: vec3<f32>) -> vec4<f32> {
+    return vol_sample(diameter, position);
+}
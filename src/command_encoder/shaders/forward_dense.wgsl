@@ -0,0 +1,78 @@
+// Dense VoxelsResource backend of forward.wgsl
+
+struct VoxelVolume {
+	min: vec3<f32>,
+	resolution: u32,
+	size: vec3<f32>,
+	voxel_size: f32,
+	mip_level_count: u32,
+	anisotropic: u32,
+};
+
+@group(3) @binding(0)
+var voxels_radiance: texture_3d<f32>;
+@group(3) @binding(1)
+var voxels_radiance_s: sampler;
+@group(3) @binding(2)
+var<uniform> volume: VoxelVolume;
+@group(3) @binding(4)
+var voxels_pos_x: texture_3d<f32>;
+@group(3) @binding(5)
+var voxels_neg_x: texture_3d<f32>;
+@group(3) @binding(6)
+var voxels_pos_y: texture_3d<f32>;
+@group(3) @binding(7)
+var voxels_neg_y: texture_3d<f32>;
+@group(3) @binding(8)
+var voxels_pos_z: texture_3d<f32>;
+@group(3) @binding(9)
+var voxels_neg_z: texture_3d<f32>;
+
+fn finest_voxel_size() -> f32 {
+    return volume.voxel_size;
+}
+
+fn vol_sample(diameter: f32, position: vec3<f32>) -> vec4<f32> {
+    let uvw = (position - volume.min) / volume.size;
+
+    var vlevel = log2(diameter / volume.voxel_size);
+    vlevel = min(f32(volume.mip_level_count - 1u), vlevel);
+
+    return textureSampleLevel(voxels_radiance, voxels_radiance_s, uvw, vlevel);
+}
+
+// Anisotropic volumes start at the first mip, level 0 is always the isotropic radiance
+fn vol_sample_directional(diameter: f32, position: vec3<f32>, dir: vec3<f32>) -> vec4<f32> {
+    let vlevel = log2(diameter / volume.voxel_size);
+
+    if volume.anisotropic == 0u || vlevel < 1.0 {
+        return vol_sample(diameter, position);
+    }
+
+    let uvw = (position - volume.min) / volume.size;
+    let level = min(f32(volume.mip_level_count - 2u), vlevel - 1.0);
+    let weights = dir * dir;
+
+    var x: vec4<f32>;
+    if dir.x >= 0.0 {
+        x = textureSampleLevel(voxels_pos_x, voxels_radiance_s, uvw, level);
+    } else {
+        x = textureSampleLevel(voxels_neg_x, voxels_radiance_s, uvw, level);
+    }
+
+    var y: vec4<f32>;
+    if dir.y >= 0.0 {
+        y = textureSampleLevel(voxels_pos_y, voxels_radiance_s, uvw, level);
+    } else {
+        y = textureSampleLevel(voxels_neg_y, voxels_radiance_s, uvw, level);
+    }
+
+    var z: vec4<f32>;
+    if dir.z >= 0.0 {
+        z = textureSampleLevel(voxels_pos_z, voxels_radiance_s, uvw, level);
+    } else {
+        z = textureSampleLevel(voxels_neg_z, voxels_radiance_s, uvw, level);
+    }
+
+    return (weights.x * x) + (weights.y * y) + (weights.z * z);
+}
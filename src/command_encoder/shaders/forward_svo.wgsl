@@ -0,0 +1,53 @@
+// SparseVoxelOctreeResource backend of forward.wgsl
+
+struct SparseVoxelOctree {
+	min: vec3<f32>,
+	depth: u32,
+	size: vec3<f32>,
+	voxel_size: f32,
+	tile_capacity: u32,
+};
+
+@group(3) @binding(0)
+var<storage, read> svo_nodes: array<u32>;
+@group(3) @binding(1)
+var<storage, read> svo_values: array<u32>;
+@group(3) @binding(2)
+var<uniform> octree: SparseVoxelOctree;
+
+fn finest_voxel_size() -> f32 {
+    return octree.voxel_size;
+}
+
+// Octree levels stand in for mips, the walk stops at the level whose cells match the diameter
+fn vol_sample(diameter: f32, position: vec3<f32>) -> vec4<f32> {
+    let uvw = (position - octree.min) / octree.size;
+    if any(uvw < vec3(0.0)) || any(uvw >= vec3(1.0)) {
+        return vec4(0.0);
+    }
+
+    let level = u32(clamp(round(log2(diameter / octree.voxel_size)), 0.0, f32(octree.depth - 1u)));
+    let target_depth = octree.depth - level;
+    let cell = vec3<u32>(uvw * f32(1u << octree.depth));
+
+    var tile = 0u;
+    for (var i = 0u; i < target_depth; i++) {
+        let octant = (cell >> vec3(octree.depth - 1u - i)) & vec3(1u);
+        let slot = (tile * 8u) + octant.x + (octant.y * 2u) + (octant.z * 4u);
+
+        if i + 1u == target_depth {
+            return unpack4x8unorm(svo_values[slot]);
+        }
+
+        tile = svo_nodes[slot];
+        if tile == 0u {
+            break;
+        }
+    }
+
+    return vec4(0.0);
+}
+
+fn vol_sample_directional(diameter: f32, position: vec3<f32>, dir: vec3<f32>) -> vec4<f32> {
+    return vol_sample(diameter, position);
+}
@@ -0,0 +1,25 @@
+// Fragment shader of the GBufferPass, appended to _mesh_vertex.wgsl
+
+struct GBufferOutput {
+    @location(0) albedo: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+    @location(2) material: vec4<f32>,
+};
+
+@fragment
+fn fs_gbuffer(in: VertexOutput) -> GBufferOutput {
+    let dif = textureSample(t_diffuse, s_diffuse, in.uv0);
+    let metal_roughness = textureSample(t_metal, s_metal, in.uv0);
+    let pixel_normal = mapped_normal(in);
+
+    if dif.a == 0.0 {
+        discard;
+    }
+
+    // Alpha marks the pixels a mesh covers
+    var out: GBufferOutput;
+    out.albedo = vec4(dif.rgb, 1.0);
+    out.normal = vec4(pixel_normal, 0.0);
+    out.material = vec4(0.0, metal_roughness.g, metal_roughness.b, 0.0);
+    return out;
+}
@@ -0,0 +1,21 @@
+// Turns the count of the face pass into the arguments of an indexed indirect draw, clamped to the buffer capacity
+
+@group(0) @binding(0)
+var<storage, read_write> quad_count: atomic<i32>;
+
+@group(0) @binding(1)
+var<storage, read_write> draw: DrawCmd;
+
+@group(0) @binding(2)
+var<storage, read> indices: array<u32>;
+
+@compute @workgroup_size(1)
+fn main() {
+    let count = min(u32(max(atomicLoad(&quad_count), 0)), arrayLength(&indices) / 6u);
+
+    draw.index_count = count * 6u;
+    draw.instance_count = 1u;
+    draw.first_index = 0u;
+    draw.base_vertex = 0u;
+    draw.first_instance = 0u;
+}
@@ -0,0 +1,117 @@
+struct VoxelVolume {
+	min: vec3<f32>,
+	resolution: u32,
+	size: vec3<f32>,
+	voxel_size: f32,
+	mip_level_count: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> volume: VoxelVolume;
+@group(0) @binding(1)
+var voxels_color: texture_3d<f32>;
+@group(0) @binding(2)
+var voxels_normal: texture_3d<f32>;
+@group(0) @binding(3)
+var voxels_direct: texture_3d<f32>;
+@group(0) @binding(4)
+var voxels_radiance: texture_3d<f32>;
+@group(0) @binding(5)
+var voxels_radiance_s: sampler;
+@group(0) @binding(6)
+var output: texture_storage_3d<rgba8unorm, write>;
+
+struct RadianceBounce {
+	cone_count: u32,
+	cone_aperture: f32,
+};
+
+@group(0) @binding(7)
+var<uniform> bounce: RadianceBounce;
+
+const PI: f32 = 3.14159265359;
+
+// Side cones are spread evenly around the normal, tilted 60 degrees away from it
+const SIDE_CONE_COS: f32 = 0.5;
+const SIDE_CONE_SIN: f32 = 0.8660254;
+
+@compute @workgroup_size(4, 4, 4)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+	if any(id >= vec3(volume.resolution)) {
+		return;
+	}
+
+	let albedo = textureLoad(voxels_color, id, 0);
+	let direct = textureLoad(voxels_direct, id, 0);
+
+	if albedo.a == 0.0 {
+		textureStore(output, id, direct);
+		return;
+	}
+
+	let position = volume.min + ((vec3<f32>(id) + 0.5) * volume.voxel_size);
+	let nrm = normalize(textureLoad(voxels_normal, id, 0).xyz);
+
+	let indirect = diffuse_trace(position, nrm);
+
+	textureStore(output, id, vec4(direct.rgb + (albedo.rgb * indirect.rgb), direct.a));
+}
+
+fn diffuse_trace(position: vec3<f32>, nrm: vec3<f32>) -> vec4<f32> {
+	// Start outside the voxel's own surface
+	let origin = position + (nrm * volume.voxel_size * 2.0);
+
+	var helper = vec3(1.0, 0.0, 0.0);
+	if abs(nrm.x) > 0.99 {
+		helper = vec3(0.0, 0.0, 1.0);
+	}
+	let x = normalize(cross(nrm, helper));
+	let z = cross(nrm, x);
+
+	let side_cone_count = f32(max(bounce.cone_count, 2u) - 1u);
+
+	var result = vec4(0.0);
+	var total_weight = 0.0;
+
+	for (var i: u32 = 0u; i < bounce.cone_count; i++) {
+		var dir = nrm;
+		var weight = 1.0;
+
+		if i > 0u {
+			let phi = (f32(i - 1u) / side_cone_count) * 2.0 * PI;
+			dir = normalize((nrm * SIDE_CONE_COS) + (((x * cos(phi)) + (z * sin(phi))) * SIDE_CONE_SIN));
+			weight = SIDE_CONE_COS;
+		}
+
+		result += weight * trace_cone(origin, dir, bounce.cone_aperture);
+		total_weight += weight;
+	}
+
+	return result / total_weight;
+}
+
+fn trace_cone(start: vec3<f32>, dir: vec3<f32>, aperture: f32) -> vec4<f32> {
+	let max_distance = length(volume.size);
+	var dist = volume.voxel_size;
+
+	var color = vec3(0.0);
+	var occlusion = 0.0;
+	while (dist < max_distance && occlusion < 1.0) {
+		let c = start + (dir * dist);
+		let uvw = (c - volume.min) / volume.size;
+		if any(uvw < vec3(0.0)) || any(uvw > vec3(1.0)) {
+			break;
+		}
+
+		let diameter = max(volume.voxel_size, 2.0 * aperture * dist);
+		let level = min(f32(volume.mip_level_count - 1u), log2(diameter / volume.voxel_size));
+		let voxel = textureSampleLevel(voxels_radiance, voxels_radiance_s, uvw, level);
+
+		color += (1.0 - occlusion) * voxel.rgb;
+		occlusion += (1.0 - occlusion) * voxel.a;
+
+		dist += diameter * 0.5;
+	}
+
+	return vec4(color, occlusion);
+}
@@ -0,0 +1,74 @@
+struct VoxelVolume {
+	min: vec3<f32>,
+	resolution: u32,
+	size: vec3<f32>,
+	voxel_size: f32,
+	mip_level_count: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> volume: VoxelVolume;
+@group(0) @binding(1)
+var voxels_color: texture_3d<f32>;
+@group(0) @binding(2)
+var voxels_radiance: texture_storage_3d<rgba8unorm, write>;
+@group(0) @binding(3)
+var voxels_emissive: texture_3d<f32>;
+@group(0) @binding(4)
+var voxels_normal: texture_3d<f32>;
+
+@group(1) @binding(0)
+var<storage, read> lights: Lights;
+
+@compute @workgroup_size(4, 4, 4)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+	if any(id >= vec3(volume.resolution)) {
+		return;
+	}
+
+	let albedo = textureLoad(voxels_color, id, 0);
+
+	if albedo.a == 0.0 {
+		textureStore(voxels_radiance, id, vec4(0.0));
+		return;
+	}
+
+	let position = volume.min + ((vec3<f32>(id) + 0.5) * volume.voxel_size);
+
+	let normal = textureLoad(voxels_normal, id, 0).xyz;
+
+	var radiance = vec3(0.0);
+	for (var i: u32 = 0u; i < lights.count; i++) {
+		let light = lights.lights[i];
+		let visibility = 1.0 - shadow_trace(position, light.position);
+
+		radiance += point_light_irradiance(light, position, normal) * visibility;
+	}
+
+	let emissive = textureLoad(voxels_emissive, id, 0);
+
+	textureStore(voxels_radiance, id, vec4((albedo.rgb * radiance) + emissive.rgb, albedo.a));
+}
+
+fn shadow_trace(origin: vec3<f32>, light_position: vec3<f32>) -> f32 {
+	let dir = normalize(light_position - origin);
+	let max_dist = distance(origin, light_position);
+
+	// Start outside the voxel's own surface
+	var dist = volume.voxel_size * 2.0;
+	var occlusion = 0.0;
+
+	while (dist < max_dist && occlusion < 1.0) {
+		let grid_position = (origin + (dir * dist) - volume.min) / volume.voxel_size;
+		if any(grid_position < vec3(0.0)) || any(grid_position >= vec3(f32(volume.resolution))) {
+			break;
+		}
+
+		let voxel = textureLoad(voxels_color, vec3<u32>(grid_position), 0);
+		occlusion += (1.0 - occlusion) * voxel.a;
+
+		dist += volume.voxel_size;
+	}
+
+	return occlusion;
+}
@@ -0,0 +1,223 @@
+// voxel_fragments.wgsl and _lights.wgsl are prepended
+
+struct VoxelVolume {
+	min: vec3<f32>,
+	resolution: u32,
+	size: vec3<f32>,
+	voxel_size: f32,
+};
+
+struct SparseVoxelOctree {
+	min: vec3<f32>,
+	depth: u32,
+	size: vec3<f32>,
+	voxel_size: f32,
+	tile_capacity: u32,
+};
+
+struct OctreeLevel {
+	level: u32,
+};
+
+// Marks a child pointer while its tile is being allocated
+const LOCKED: u32 = 0xffffffffu;
+
+// Every tile holds the child pointers (nodes) and the packed radiance (values) of its eight octants.
+// Tile 0 is the root, so a child pointer of 0 means the octant is empty. Octants of the deepest level are single voxels
+@group(0) @binding(0)
+var<uniform> octree: SparseVoxelOctree;
+@group(0) @binding(1)
+var<storage, read_write> nodes: array<atomic<u32>>;
+@group(0) @binding(2)
+var<storage, read_write> values: array<u32>;
+@group(0) @binding(3)
+var<storage, read_write> tile_count: atomic<u32>;
+@group(0) @binding(4)
+var<uniform> octree_level: OctreeLevel;
+
+@group(1) @binding(0)
+var<storage, read_write> fragments: array<VoxelFragment>;
+@group(1) @binding(1)
+var<storage, read_write> fragment_counter: VoxelFragmentCounter;
+
+@group(2) @binding(0)
+var<uniform> volume: VoxelVolume;
+@group(2) @binding(1)
+var<storage, read> lights: Lights;
+
+// Octree cell of a fragment at the deepest level
+fn fragment_cell(index: u32) -> vec3<u32> {
+	let position = unpack_position(fragments[index].position);
+	return (position << vec3(octree.depth)) / volume.resolution;
+}
+
+fn octant_slot(tile: u32, cell: vec3<u32>, level: u32) -> u32 {
+	let octant = (cell >> vec3(octree.depth - 1u - level)) & vec3(1u);
+	return (tile * 8u) + octant.x + (octant.y * 2u) + (octant.z * 4u);
+}
+
+// Walks down to the tile at the given level, returns LOCKED when the branch is missing
+fn find_tile(cell: vec3<u32>, level: u32) -> u32 {
+	var tile = 0u;
+	for (var i = 0u; i < level; i++) {
+		tile = atomicLoad(&nodes[octant_slot(tile, cell, i)]);
+		if tile == 0u || tile == LOCKED {
+			return LOCKED;
+		}
+	}
+
+	return tile;
+}
+
+fn fragment_index(id: vec3<u32>) -> u32 {
+	let index = fragment_invocation_index(id);
+	if index >= min(atomicLoad(&fragment_counter.count), arrayLength(&fragments)) {
+		return LOCKED;
+	}
+
+	return index;
+}
+
+// Allocates the child tiles of one level, the first fragment reaching an empty octant claims it
+@compute @workgroup_size(64)
+fn subdivide(@builtin(global_invocation_id) id: vec3<u32>) {
+	let index = fragment_index(id);
+	if index == LOCKED {
+		return;
+	}
+
+	let cell = fragment_cell(index);
+	let tile = find_tile(cell, octree_level.level);
+	if tile == LOCKED {
+		return;
+	}
+
+	let slot = octant_slot(tile, cell, octree_level.level);
+
+	// Only the invocation that swaps out the empty pointer allocates, anyone swapping out
+	// an allocated pointer puts it back, and swapping out LOCKED leaves it to its holder
+	let old_value = atomicExchange(&nodes[slot], LOCKED);
+	if old_value == 0u {
+		let child = atomicAdd(&tile_count, 1u);
+		if child < octree.tile_capacity {
+			atomicStore(&nodes[slot], child);
+		} else {
+			atomicStore(&nodes[slot], 0u);
+		}
+	} else if old_value != LOCKED {
+		atomicStore(&nodes[slot], old_value);
+	}
+}
+
+// Value slot of a cell at the deepest level, LOCKED when its leaf tile doesn't exist
+fn leaf_slot(cell: vec3<u32>) -> u32 {
+	let leaf_level = octree.depth - 1u;
+	let tile = find_tile(cell, leaf_level);
+	if tile == LOCKED {
+		return LOCKED;
+	}
+
+	return octant_slot(tile, cell, leaf_level);
+}
+
+// Stores the colour of every fragment in the octant of its deepest tile, its alpha is the occupancy shadows are traced through
+@compute @workgroup_size(64)
+fn write_leaves(@builtin(global_invocation_id) id: vec3<u32>) {
+	let index = fragment_index(id);
+	if index == LOCKED {
+		return;
+	}
+
+	let slot = leaf_slot(fragment_cell(index));
+	if slot == LOCKED {
+		return;
+	}
+
+	values[slot] = fragments[index].color;
+}
+
+// Replaces the colour of every leaf with its lit colour, keeping the alpha
+@compute @workgroup_size(64)
+fn light_leaves(@builtin(global_invocation_id) id: vec3<u32>) {
+	let index = fragment_index(id);
+	if index == LOCKED {
+		return;
+	}
+
+	let cell = fragment_cell(index);
+	let slot = leaf_slot(cell);
+	if slot == LOCKED {
+		return;
+	}
+
+	let fragment = fragments[index];
+	let albedo = unpack4x8unorm(fragment.color);
+	let normal = unpack4x8snorm(fragment.normal).xyz;
+	let position = octree.min + ((vec3<f32>(cell) + 0.5) * octree.voxel_size);
+
+	var radiance = vec3(0.0);
+	for (var i: u32 = 0u; i < lights.count; i++) {
+		let light = lights.lights[i];
+		let visibility = 1.0 - shadow_trace(position, light.position);
+
+		radiance += point_light_irradiance(light, position, normal) * visibility;
+	}
+
+	let emissive = unpack4x8unorm(fragment.emissive).rgb;
+	values[slot] = pack4x8unorm(vec4((albedo.rgb * radiance) + emissive, albedo.a));
+}
+
+// Marches the deepest level of the octree towards the light, like the dense radiance injection
+fn shadow_trace(origin: vec3<f32>, light_position: vec3<f32>) -> f32 {
+	let dir = normalize(light_position - origin);
+	let max_dist = distance(origin, light_position);
+	let resolution = f32(1u << octree.depth);
+
+	// Start outside the voxel's own surface
+	var dist = octree.voxel_size * 2.0;
+	var occlusion = 0.0;
+
+	while (dist < max_dist && occlusion < 1.0) {
+		let grid_position = (origin + (dir * dist) - octree.min) / octree.voxel_size;
+		if any(grid_position < vec3(0.0)) || any(grid_position >= vec3(resolution)) {
+			break;
+		}
+
+		let slot = leaf_slot(vec3<u32>(grid_position));
+		if slot != LOCKED {
+			occlusion += (1.0 - occlusion) * unpack4x8unorm(values[slot]).a;
+		}
+
+		dist += octree.voxel_size;
+	}
+
+	return occlusion;
+}
+
+// Averages the eight octants of a child tile into its parent's value, empty octants count as zero like the dense mips
+@compute @workgroup_size(64)
+fn mip(@builtin(global_invocation_id) id: vec3<u32>) {
+	let index = fragment_index(id);
+	if index == LOCKED {
+		return;
+	}
+
+	let cell = fragment_cell(index);
+	let tile = find_tile(cell, octree_level.level);
+	if tile == LOCKED {
+		return;
+	}
+
+	let slot = octant_slot(tile, cell, octree_level.level);
+	let child = atomicLoad(&nodes[slot]);
+	if child == 0u {
+		return;
+	}
+
+	var sum = vec4(0.0);
+	for (var i = 0u; i < 8u; i++) {
+		sum += unpack4x8unorm(values[(child * 8u) + i]);
+	}
+
+	values[slot] = pack4x8unorm(sum / 8.0);
+}
@@ -0,0 +1,65 @@
+@group(0) @binding(0)
+var source: texture_3d<f32>;
+
+@group(0) @binding(1)
+var output: texture_storage_3d<rgba8unorm, write>;
+
+// Every direction integrates the 2x2x2 block front to back along its axis, then averages the four columns
+fn downsample(id: vec3<u32>, axis: u32, positive: bool) -> vec4<f32> {
+	let u_axis = (axis + 1u) % 3u;
+	let v_axis = (axis + 2u) % 3u;
+
+	var value = vec4(0.0);
+	for (var u: u32 = 0u; u < 2u; u++) {
+		for (var v: u32 = 0u; v < 2u; v++) {
+			var near = vec3(0u);
+			near[u_axis] = u;
+			near[v_axis] = v;
+			var far = near;
+
+			if positive {
+				far[axis] = 1u;
+			} else {
+				near[axis] = 1u;
+			}
+
+			let near_value = textureLoad(source, 2u * id + near, 0);
+			let far_value = textureLoad(source, 2u * id + far, 0);
+
+			// Voxels are premultiplied, so the far voxel only shows through what the near one lets pass
+			value += near_value + ((1.0 - near_value.a) * far_value);
+		}
+	}
+
+	return value * 0.25;
+}
+
+@compute @workgroup_size(1)
+fn main_pos_x(@builtin(global_invocation_id) id : vec3<u32>,) {
+	textureStore(output, id, downsample(id, 0u, true));
+}
+
+@compute @workgroup_size(1)
+fn main_neg_x(@builtin(global_invocation_id) id : vec3<u32>,) {
+	textureStore(output, id, downsample(id, 0u, false));
+}
+
+@compute @workgroup_size(1)
+fn main_pos_y(@builtin(global_invocation_id) id : vec3<u32>,) {
+	textureStore(output, id, downsample(id, 1u, true));
+}
+
+@compute @workgroup_size(1)
+fn main_neg_y(@builtin(global_invocation_id) id : vec3<u32>,) {
+	textureStore(output, id, downsample(id, 1u, false));
+}
+
+@compute @workgroup_size(1)
+fn main_pos_z(@builtin(global_invocation_id) id : vec3<u32>,) {
+	textureStore(output, id, downsample(id, 2u, true));
+}
+
+@compute @workgroup_size(1)
+fn main_neg_z(@builtin(global_invocation_id) id : vec3<u32>,) {
+	textureStore(output, id, downsample(id, 2u, false));
+}
@@ -0,0 +1,148 @@
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+
+struct VoxelVolume {
+    min: vec3<f32>,
+    resolution: u32,
+    size: vec3<f32>,
+    voxel_size: f32,
+    mip_level_count: u32,
+};
+
+struct VoxelDebug {
+    inverse_view_proj: mat4x4<f32>,
+    mip_level: u32,
+    channel: u32,
+    slice_axis: u32,
+    // Negative when the whole volume is shown
+    slice_index: i32,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0)
+var voxels_color: texture_3d<f32>;
+@group(1) @binding(1)
+var voxels_channel: texture_3d<f32>;
+@group(1) @binding(2)
+var<uniform> volume: VoxelVolume;
+@group(1) @binding(3)
+var<uniform> debug: VoxelDebug;
+
+struct DebugVertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+    @location(1) ndc: vec2<f32>,
+};
+
+fn debug_size() -> vec3<i32> {
+    return vec3<i32>(textureDimensions(voxels_color, debug.mip_level));
+}
+
+fn is_visible(voxel: vec3<i32>) -> bool {
+    if any(voxel < vec3(0)) || any(voxel >= debug_size()) {
+        return false;
+    }
+    if debug.slice_index >= 0 && voxel[debug.slice_axis] != debug.slice_index {
+        return false;
+    }
+
+    return textureLoad(voxels_color, voxel, i32(debug.mip_level)).a > 0.0;
+}
+
+fn voxel_debug_color(voxel: vec3<i32>) -> vec3<f32> {
+    return debug_channel_color(
+        debug.channel,
+        textureLoad(voxels_color, voxel, i32(debug.mip_level)),
+        textureLoad(voxels_channel, voxel, i32(debug.mip_level)),
+    );
+}
+
+// One point per voxel of the mip level, empty ones are moved outside the clip volume
+@vertex
+fn vs_points(@builtin(vertex_index) vertex_index: u32) -> DebugVertexOutput {
+    let size = vec3<u32>(debug_size());
+    let voxel = vec3<i32>(vec3(vertex_index % size.x, vertex_index / size.x % size.y, vertex_index / (size.x * size.y)));
+
+    var out: DebugVertexOutput;
+    if !is_visible(voxel) {
+        out.clip_position = vec4(2.0, 2.0, 2.0, 1.0);
+        return out;
+    }
+
+    let center = volume.min + (vec3<f32>(voxel) + 0.5) * volume.size / vec3<f32>(size);
+    out.clip_position = camera.view_proj * vec4(center, 1.0);
+    out.color = voxel_debug_color(voxel);
+    return out;
+}
+
+@fragment
+fn fs_points(in: DebugVertexOutput) -> @location(0) vec4<f32> {
+    return vec4(in.color, 1.0);
+}
+
+// A single triangle covering the screen
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vertex_index: u32) -> DebugVertexOutput {
+    let ndc = vec2(f32(vertex_index & 1u) * 4.0 - 1.0, f32(vertex_index >> 1u) * 4.0 - 1.0);
+
+    var out: DebugVertexOutput;
+    out.clip_position = vec4(ndc, 0.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+// Amanatides and Woo traversal of the mip level's grid, from where the camera ray enters the volume
+@fragment
+fn fs_ray_march(in: DebugVertexOutput) -> @location(0) vec4<f32> {
+    let near = debug.inverse_view_proj * vec4(in.ndc, 0.0, 1.0);
+    let far = debug.inverse_view_proj * vec4(in.ndc, 1.0, 1.0);
+    let origin = near.xyz / near.w;
+
+    let size = debug_size();
+    let voxel_size = volume.size / vec3<f32>(size);
+    let grid_origin = (origin - volume.min) / voxel_size;
+    var direction = (far.xyz / far.w - origin) / voxel_size;
+    direction = select(direction, vec3(1e-6), abs(direction) < vec3(1e-6));
+
+    let inverse_direction = 1.0 / direction;
+    let t0 = -grid_origin * inverse_direction;
+    let t1 = (vec3<f32>(size) - grid_origin) * inverse_direction;
+    let t_min = min(t0, t1);
+    let t_max = max(t0, t1);
+    let t_enter = max(max(t_min.x, t_min.y), max(t_min.z, 0.0));
+    let t_exit = min(t_max.x, min(t_max.y, t_max.z));
+    if t_enter > t_exit {
+        return vec4(0.0, 0.0, 0.0, 1.0);
+    }
+
+    let entry = grid_origin + direction * t_enter;
+    var voxel = clamp(vec3<i32>(floor(entry)), vec3(0), size - 1);
+    let step = vec3<i32>(sign(direction));
+    let delta = abs(inverse_direction);
+    var t_next = (vec3<f32>(voxel) + max(vec3<f32>(step), vec3(0.0)) - grid_origin) * inverse_direction;
+
+    for (var i = 0; i < size.x + size.y + size.z; i++) {
+        if any(voxel < vec3(0)) || any(voxel >= size) {
+            break;
+        }
+        if is_visible(voxel) {
+            return vec4(voxel_debug_color(voxel), 1.0);
+        }
+
+        if t_next.x < t_next.y && t_next.x < t_next.z {
+            voxel.x += step.x;
+            t_next.x += delta.x;
+        } else if t_next.y < t_next.z {
+            voxel.y += step.y;
+            t_next.y += delta.y;
+        } else {
+            voxel.z += step.z;
+            t_next.z += delta.z;
+        }
+    }
+
+    return vec4(0.0, 0.0, 0.0, 1.0);
+}
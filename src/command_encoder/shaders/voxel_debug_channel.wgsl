@@ -0,0 +1,15 @@
+// Albedo, opacity, normal, emissive and radiance as 0 to 4, see VoxelChannel.
+// Color is the voxel of the colour volume, value the one of the displayed channel
+fn debug_channel_color(channel: u32, color: vec4<f32>, value: vec4<f32>) -> vec3<f32> {
+    switch channel {
+        case 1u: {
+            return vec3(color.a);
+        }
+        case 2u: {
+            return value.xyz * 0.5 + 0.5;
+        }
+        default: {
+            return value.rgb;
+        }
+    }
+}
@@ -0,0 +1,189 @@
+struct VoxelVolume {
+    min: vec3<f32>,
+    resolution: u32,
+    size: vec3<f32>,
+    voxel_size: f32,
+    mip_level_count: u32,
+};
+
+struct Vertex {
+    position: vec3<f32>,
+    color: vec3<f32>,
+    normal: vec3<f32>,
+};
+
+@group(0) @binding(0)
+var voxels_color: texture_3d<f32>;
+
+// Four vertices and six indices per quad
+@group(0) @binding(1)
+var<storage, read_write> vertices: array<Vertex>;
+@group(0) @binding(2)
+var<storage, read_write> indices: array<u32>;
+
+@group(0) @binding(3)
+var<storage, read_write> quad_count: atomic<i32>;
+
+@group(0) @binding(4)
+var<uniform> volume: VoxelVolume;
+
+struct MeshifySettings {
+    channel: u32,
+};
+
+// The same mip level of the channel that colours the quads
+@group(0) @binding(5)
+var voxels_channel: texture_3d<f32>;
+
+@group(0) @binding(6)
+var<uniform> settings: MeshifySettings;
+
+fn is_filled(voxel: vec3<i32>) -> bool {
+    let size = vec3<i32>(textureDimensions(voxels_color));
+    if any(voxel < vec3(0)) || any(voxel >= size) {
+        return false;
+    }
+
+    return textureLoad(voxels_color, voxel, 0).a > 0.0;
+}
+
+fn axis_vector(axis: u32) -> vec3<i32> {
+    var vector = vec3(0);
+    vector[axis] = 1;
+    return vector;
+}
+
+fn voxel_color(voxel: vec3<i32>) -> vec3<f32> {
+    return debug_channel_color(settings.channel, textureLoad(voxels_color, voxel, 0), textureLoad(voxels_channel, voxel, 0));
+}
+
+// The colour of the voxel's face looking along the axis, zero alpha when it is hidden or there is no voxel
+fn face_color(voxel: vec3<i32>, axis: u32, sign: i32) -> vec4<f32> {
+    if !is_filled(voxel) || is_filled(voxel + axis_vector(axis) * sign) {
+        return vec4(0.0);
+    }
+
+    return vec4(voxel_color(voxel), 1.0);
+}
+
+fn has_face(voxel: vec3<i32>, axis: u32, sign: i32, color: vec4<f32>) -> bool {
+    return color.a > 0.0 && all(face_color(voxel, axis, sign) == color);
+}
+
+// Faces are visited along u = axis + 1 and v = axis + 2, so u x v points along the axis
+fn emit_quad(voxel: vec3<i32>, axis: u32, sign: i32, width: i32, height: i32, color: vec3<f32>) {
+    // Quads past the capacity are still counted, but not written
+    let quad = atomicAdd(&quad_count, 1);
+    if u32(quad) >= arrayLength(&indices) / 6u {
+        return;
+    }
+
+    let u = axis_vector((axis + 1u) % 3u) * width;
+    let v = axis_vector((axis + 2u) % 3u) * height;
+    var origin = voxel;
+    origin[axis] += max(sign, 0);
+
+    var corners = array<vec3<i32>, 4>(origin, origin + u, origin + u + v, origin + v);
+    if sign < 0 {
+        corners = array<vec3<i32>, 4>(origin, origin + v, origin + u + v, origin + u);
+    }
+
+    let voxel_size = volume.size / vec3<f32>(textureDimensions(voxels_color));
+    let normal = vec3<f32>(axis_vector(axis) * sign);
+    let base = u32(quad) * 4u;
+    for (var i = 0u; i < 4u; i++) {
+        vertices[base + i] = Vertex(volume.min + vec3<f32>(corners[i]) * voxel_size, color, normal);
+    }
+
+    let index = u32(quad) * 6u;
+    indices[index + 0u] = base + 0u;
+    indices[index + 1u] = base + 1u;
+    indices[index + 2u] = base + 2u;
+    indices[index + 3u] = base + 0u;
+    indices[index + 4u] = base + 2u;
+    indices[index + 5u] = base + 3u;
+}
+
+// All six faces of every voxel with at least one empty neighbour
+@compute @workgroup_size(1)
+fn cubes(@builtin(workgroup_id) workgroup_id: vec3<u32>) {
+    let voxel = vec3<i32>(workgroup_id);
+    if !is_filled(voxel) {
+        return;
+    }
+
+    var exposed = false;
+    for (var axis = 0u; axis < 3u; axis++) {
+        exposed = exposed || !is_filled(voxel + axis_vector(axis)) || !is_filled(voxel - axis_vector(axis));
+    }
+    if !exposed {
+        return;
+    }
+
+    let color = voxel_color(voxel);
+    for (var axis = 0u; axis < 3u; axis++) {
+        emit_quad(voxel, axis, 1, 1, 1, color);
+        emit_quad(voxel, axis, -1, 1, 1, color);
+    }
+}
+
+@compute @workgroup_size(1)
+fn exposed_faces(@builtin(workgroup_id) workgroup_id: vec3<u32>) {
+    let voxel = vec3<i32>(workgroup_id);
+    for (var axis = 0u; axis < 3u; axis++) {
+        for (var sign = -1; sign <= 1; sign += 2) {
+            let color = face_color(voxel, axis, sign);
+            if color.a > 0.0 {
+                emit_quad(voxel, axis, sign, 1, 1, color.rgb);
+            }
+        }
+    }
+}
+
+fn run_length(voxel: vec3<i32>, axis: u32, sign: i32, color: vec4<f32>) -> i32 {
+    let u = axis_vector((axis + 1u) % 3u);
+    var run = 1;
+    while has_face(voxel + u * run, axis, sign, color) {
+        run++;
+    }
+
+    return run;
+}
+
+// Whether a row of faces of the colour starts at the voxel and spans exactly width faces
+fn is_run(voxel: vec3<i32>, axis: u32, sign: i32, color: vec4<f32>, width: i32) -> bool {
+    let u = axis_vector((axis + 1u) % 3u);
+    return has_face(voxel, axis, sign, color)
+        && !has_face(voxel - u, axis, sign, color)
+        && run_length(voxel, axis, sign, color) == width;
+}
+
+// Same coloured faces are merged into rows along u, then identical rows are stacked along v.
+// Only the voxel where a stack starts emits its quad
+@compute @workgroup_size(1)
+fn greedy_faces(@builtin(workgroup_id) workgroup_id: vec3<u32>) {
+    let voxel = vec3<i32>(workgroup_id);
+    for (var axis = 0u; axis < 3u; axis++) {
+        let u = axis_vector((axis + 1u) % 3u);
+        let v = axis_vector((axis + 2u) % 3u);
+
+        for (var sign = -1; sign <= 1; sign += 2) {
+            let color = face_color(voxel, axis, sign);
+            if color.a == 0.0 || has_face(voxel - u, axis, sign, color) {
+                continue;
+            }
+
+            let width = run_length(voxel, axis, sign, color);
+            if is_run(voxel - v, axis, sign, color, width) {
+                continue;
+            }
+
+            var height = 1;
+            while is_run(voxel + v * height, axis, sign, color, width) {
+                height++;
+            }
+
+            emit_quad(voxel, axis, sign, width, height, color.rgb);
+        }
+    }
+}
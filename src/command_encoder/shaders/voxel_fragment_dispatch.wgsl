@@ -0,0 +1,15 @@
+@group(0) @binding(0)
+var<storage, read_write> fragments: array<VoxelFragment>;
+@group(0) @binding(1)
+var<storage, read_write> fragment_counter: VoxelFragmentCounter;
+
+// Fragments past the capacity were dropped, so they don't get an invocation
+@compute @workgroup_size(1)
+fn prepare_dispatch() {
+	let count = min(atomicLoad(&fragment_counter.count), arrayLength(&fragments));
+
+	let workgroups = (count + FRAGMENT_WORKGROUP_SIZE - 1u) / FRAGMENT_WORKGROUP_SIZE;
+	fragment_counter.workgroups_x = min(workgroups, FRAGMENT_WORKGROUPS_PER_ROW);
+	fragment_counter.workgroups_y = (workgroups + FRAGMENT_WORKGROUPS_PER_ROW - 1u) / FRAGMENT_WORKGROUPS_PER_ROW;
+	fragment_counter.workgroups_z = 1u;
+}
@@ -0,0 +1,34 @@
+// Shared by every shader reading or writing a VoxelFragmentList, prepended to their source
+
+// Grid position packed 10 bits per axis, colours packed with pack4x8unorm, the normal with pack4x8snorm
+struct VoxelFragment {
+	position: u32,
+	color: u32,
+	normal: u32,
+	emissive: u32,
+};
+
+// The first three words double as the indirect dispatch arguments over the fragments
+struct VoxelFragmentCounter {
+	workgroups_x: u32,
+	workgroups_y: u32,
+	workgroups_z: u32,
+	count: atomic<u32>,
+};
+
+const FRAGMENT_WORKGROUP_SIZE: u32 = 64u;
+// Indirect arguments aren't validated against the dispatch limit, so rows of workgroups are stacked along y below
+// the smallest max_compute_workgroups_per_dimension
+const FRAGMENT_WORKGROUPS_PER_ROW: u32 = 65535u;
+
+fn fragment_invocation_index(id: vec3<u32>) -> u32 {
+	return id.x + (id.y * FRAGMENT_WORKGROUPS_PER_ROW * FRAGMENT_WORKGROUP_SIZE);
+}
+
+fn pack_position(position: vec3<u32>) -> u32 {
+	return position.x | (position.y << 10u) | (position.z << 20u);
+}
+
+fn unpack_position(position: u32) -> vec3<u32> {
+	return vec3(position & 0x3ffu, (position >> 10u) & 0x3ffu, (position >> 20u) & 0x3ffu);
+}
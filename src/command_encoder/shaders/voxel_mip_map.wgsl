@@ -1,14 +1,18 @@
-const WIDTH = 50.0;
-
 @group(0) @binding(0)
 var source: texture_3d<f32>;
 
+// VoxelOutput is declared by VoxelMipMapper for the format of the volume
 @group(0) @binding(1)
-var output: texture_storage_3d<rgba8unorm, write>;
+var output: VoxelOutput;
+
+// First output voxel of the dispatch
+@group(0) @binding(2)
+var<uniform> origin: vec3<u32>;
 
 
 @compute @workgroup_size(1)
-fn main(@builtin(global_invocation_id) id : vec3<u32>,) {
+fn main(@builtin(global_invocation_id) global_id : vec3<u32>,) {
+	let id = origin + global_id;
 	let offset = vec2<u32>(0u, 1u);
 	
 	let value = (
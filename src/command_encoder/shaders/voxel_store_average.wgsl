@@ -0,0 +1,52 @@
+// Appended to voxelization.wgsl for VoxelWriteMode::Average.
+// Every voxel of the region keeps a running average in one word, red, green and blue as unorm8
+// with the sample count in the upper 8 bits. Words are indexed from the region's min corner
+@group(0) @binding(8)
+var<storage, read_write> color_accumulator: array<atomic<u32>>;
+
+fn accumulator_index(in_v: vec3<i32>) -> u32 {
+    let v = vec3<u32>(in_v - region.min);
+    let size = vec3<u32>(region.max - region.min);
+    return v.x + ((v.y + (v.z * size.y)) * size.x);
+}
+
+// Retries until no other invocation changed the word between reading and writing it
+fn accumulate(index: u32, value: vec3<f32>) {
+    // Most voxels are empty when first hit
+    var expected = 0u;
+
+    loop {
+        let count = expected >> 24u;
+        let average = ((unpack4x8unorm(expected).rgb * f32(count)) + value) / f32(count + 1u);
+        let desired = (pack4x8unorm(vec4(average, 0.0)) & 0xffffffu) | (min(count + 1u, 0xffu) << 24u);
+
+        let result = atomicCompareExchangeWeak(&color_accumulator[index], expected, desired);
+        if result.exchanged {
+            break;
+        }
+
+        expected = result.old_value;
+    }
+}
+
+fn store_color(address: vec3<i32>, color: vec4<f32>) {
+    let in_v = address - vec3(0, 0, i32(region.z_offset));
+    accumulate(accumulator_index(in_v), color.rgb);
+}
+
+// Writes the averages of the region to voxels_color as opaque voxels, voxels without samples keep what they held
+@compute @workgroup_size(4, 4, 4)
+fn resolve_average(@builtin(global_invocation_id) id: vec3<u32>) {
+    let in_v = region.min + vec3<i32>(id);
+    if !in_region(in_v) {
+        return;
+    }
+
+    let word = atomicLoad(&color_accumulator[accumulator_index(in_v)]);
+    if (word >> 24u) == 0u {
+        return;
+    }
+
+    let address = in_v + vec3(0, 0, i32(region.z_offset));
+    textureStore(voxels_color, address, vec4(unpack4x8unorm(word).rgb, 1.0));
+}
@@ -0,0 +1,4 @@
+// Appended to voxelization.wgsl for VoxelWriteMode::LastWriter, whichever point is stored last wins
+fn store_color(address: vec3<i32>, color: vec4<f32>) {
+    textureStore(voxels_color, address, color);
+}
@@ -1,9 +1,26 @@
-const WIDTH = 50.0;
+// voxel_fragments.wgsl is prepended, then voxelization_raster.wgsl and store_color from voxel_store_last_writer.wgsl or voxel_store_average.wgsl are appended
+
+struct VoxelVolume {
+	min: vec3<f32>,
+	resolution: u32,
+	size: vec3<f32>,
+	voxel_size: f32,
+	mip_level_count: u32,
+};
 
 struct Voxel {
 	center: vec3<f32>,
 };
 
+// Box of the grid that may be written, stores are shifted z_offset texels along z
+struct VoxelizationRegion {
+	min: vec3<i32>,
+	z_offset: u32,
+	max: vec3<i32>,
+	append_fragments: u32,
+	write_volume: u32,
+};
+
 struct Vertex {
 	position: vec3<f32>,
 	grid_position: vec3<f32>,
@@ -18,13 +35,19 @@ struct Triangle {
     dom_axis: i32,
 };
 
+// Offset of the primitive in the mesh's index buffer
+struct MeshPrimitive {
+	index_start: u32,
+	triangle_count: u32,
+};
+
 struct ModelUniform {
 	matrix: mat4x4<f32>,
 };
 
 struct VertexNormals {
-	normals: u32,
-	tangents: u32,
+	normals: vec3<f32>,
+	tangents: vec4<f32>,
 };
 
 struct VertexColors {
@@ -44,8 +67,10 @@ fn read_vertex(index: u32) -> vec3<f32> {
 fn read_normal(index: u32) -> VertexNormals {
     var normals: VertexNormals;
 
-    normals.normals = v_normals[index * 2u];
-    normals.tangents = v_normals[index * 2u + 1u];
+    let new_index = index * 7u;
+
+    normals.normals = vec3(v_normals[new_index], v_normals[new_index + 1u], v_normals[new_index + 2u]);
+    normals.tangents = vec4(v_normals[new_index + 3u], v_normals[new_index + 4u], v_normals[new_index + 5u], v_normals[new_index + 6u]);
 
     return normals;
 }
@@ -63,23 +88,37 @@ fn read_color(index: u32) -> VertexColors {
 }
 
 @group(0) @binding(0)
+var<uniform> volume: VoxelVolume;
+@group(0) @binding(1)
 var voxels_color: texture_storage_3d<rgba8unorm, write>;
-// @group(0) @binding(1)
-// var voxels_normal: texture_storage_3d<rgba8snorm, write>;
-// @group(0) @binding(2)
-// var voxels_emissive: texture_storage_3d<rgba8unorm, write>;
+@group(0) @binding(2)
+var voxels_normal: texture_storage_3d<rgba8snorm, write>;
+@group(0) @binding(3)
+var voxels_emissive: texture_storage_3d<rgba8unorm, write>;
+@group(0) @binding(4)
+var voxels_material: texture_storage_3d<rgba8unorm, write>;
+@group(0) @binding(5)
+var<uniform> region: VoxelizationRegion;
+@group(0) @binding(6)
+var<storage, read_write> fragments: array<VoxelFragment>;
+@group(0) @binding(7)
+var<storage, read_write> fragment_counter: VoxelFragmentCounter;
 
 @group(1) @binding(0)
 var<storage, read> v_indices: array<u32>;
 @group(1) @binding(1)
 var<storage, read> v_positions: array<f32>;
 @group(1) @binding(2)
-var<storage, read> v_normals: array<u32>;
+var<storage, read> v_normals: array<f32>;
 @group(1) @binding(3)
 var<storage, read> v_colors: array<u32>;
+@group(1) @binding(4)
+var<uniform> primitive: MeshPrimitive;
 
 @group(2) @binding(0)
 var<uniform> model_transform: ModelUniform;
+@group(2) @binding(1)
+var<uniform> normal_matrix: mat3x3<f32>;
 
 @group(3) @binding(0)
 var t_diffuse: texture_2d<f32>;
@@ -93,23 +132,41 @@ var s_metal: sampler;
 var t_normal: texture_2d<f32>;
 @group(3) @binding(5)
 var s_normal: sampler;
+@group(3) @binding(6)
+var t_emissive: texture_2d<f32>;
+@group(3) @binding(7)
+var s_emissive: sampler;
 
-// Data per side
+// Data per voxel
 // Color RGBA8Unorm - alpha translucency
-// Normal RGBA8Snorm - alpha metallic (reinterperet as unorm)
-// Emissive RGBA8Unorm - alpha roughness
-
-
+// Normal RGBA8Snorm - alpha occupancy
+// Emissive RGBA8Unorm - alpha occupancy
+// Material RGBA8Unorm - red metallic, green roughness, alpha occupancy
+
+
+// One invocation per triangle, rows of workgroups are stacked along y past the dispatch limit
+@compute @workgroup_size(64)
+fn main(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(num_workgroups) workgroup_count: vec3<u32>,
+) {
+    let triangle_index = global_id.y * workgroup_count.x * 64u + global_id.x;
+    if triangle_index >= primitive.triangle_count {
+        return;
+    }
 
-@compute @workgroup_size(1)
-fn main(@builtin(workgroup_id) workgroup_id: vec3<u32>) {
-    var indices_index = (workgroup_id.x * 3u);
+    var indices_index = primitive.index_start + triangle_index * 3u;
 
-    var voxel_grid_dimension = f32(textureDimensions(voxels_color).x);
-    var voxel_size = WIDTH / voxel_grid_dimension;
+    var voxel_size = volume.size / f32(volume.resolution);
 
     var triangle = get_triangle(indices_index, voxel_size);
 
+    let grid_min = (triangle.bounds_min - volume.min) / voxel_size;
+    let grid_max = (triangle.bounds_max - volume.min) / voxel_size;
+    if any(grid_max < vec3<f32>(region.min)) || any(grid_min >= vec3<f32>(region.max)) {
+        return;
+    }
+
     voxelize_line(triangle, triangle.vertices[0].grid_position, triangle.vertices[1].grid_position);
     voxelize_line(triangle, triangle.vertices[1].grid_position, triangle.vertices[2].grid_position);
     voxelize_line(triangle, triangle.vertices[0].grid_position, triangle.vertices[2].grid_position);
@@ -117,14 +174,14 @@ fn main(@builtin(workgroup_id) workgroup_id: vec3<u32>) {
     voxelize_interior(triangle);
 }
 
-fn get_triangle(indices_index: u32, voxel_size: f32) -> Triangle {
+fn get_triangle(indices_index: u32, voxel_size: vec3<f32>) -> Triangle {
     var triangle: Triangle;
 
     for (var i: u32 = 0u; i < 3u; i++) {
         var vertex: Vertex;
         let index: u32 = v_indices[indices_index + i];
         vertex.position = (model_transform.matrix * vec4<f32>(read_vertex(index), 1.0)).xyz;
-        vertex.grid_position = (vertex.position + (WIDTH / 2.0)) / voxel_size;
+        vertex.grid_position = (vertex.position - volume.min) / voxel_size;
         vertex.normal = read_normal(index);
         vertex.color = read_color(index);
         triangle.vertices[i] = vertex;
@@ -174,6 +231,10 @@ fn get_triangle(indices_index: u32, voxel_size: f32) -> Triangle {
 }
 
 fn voxelize_point(triangle: Triangle, in_v: vec3<i32>, f_v: vec3<f32>) {
+    if !in_region(in_v) {
+        return;
+    }
+
     let v0 = triangle.vertices[1].grid_position - triangle.vertices[0].grid_position;
     let v1 = triangle.vertices[2].grid_position - triangle.vertices[0].grid_position;
     let v2 = f_v - triangle.vertices[0].grid_position;
@@ -189,22 +250,86 @@ fn voxelize_point(triangle: Triangle, in_v: vec3<i32>, f_v: vec3<f32>) {
     let u = 1.0 - v - w;
 
     let uv0 = (triangle.vertices[0].color.uv0 * u) + (triangle.vertices[1].color.uv0 * v) + (triangle.vertices[2].color.uv0 * w);
+    let normal = (triangle.vertices[0].normal.normals * u) + (triangle.vertices[1].normal.normals * v) + (triangle.vertices[2].normal.normals * w);
+    let tangent = (triangle.vertices[0].normal.tangents * u) + (triangle.vertices[1].normal.tangents * v) + (triangle.vertices[2].normal.tangents * w);
+
+    store_surface(in_v, shade_surface(uv0, normal, tangent));
+}
+
+struct VoxelSurface {
+    color: vec4<f32>,
+    normal: vec3<f32>,
+    emissive: vec3<f32>,
+    // glTF packs roughness in green and metalness in blue
+    metal_roughness: vec4<f32>,
+};
+
+// Normal and tangent are interpolated in model space
+fn shade_surface(uv0: vec2<f32>, model_normal: vec3<f32>, tangent: vec4<f32>) -> VoxelSurface {
+    var surface: VoxelSurface;
 
     let color_r = textureGather(0, t_diffuse, s_diffuse, uv0);
     let color_g = textureGather(1, t_diffuse, s_diffuse, uv0);
     let color_b = textureGather(2, t_diffuse, s_diffuse, uv0);
     let color_a = textureGather(3, t_diffuse, s_diffuse, uv0);
 
-    let color = vec4(
+    surface.color = vec4(
         (color_r.x + color_r.y + color_r.z + color_r.w) / 4.0,
         (color_g.x + color_g.y + color_g.z + color_g.w) / 4.0,
         (color_b.x + color_b.y + color_b.z + color_b.w) / 4.0,
         (color_a.x + color_a.y + color_a.z + color_a.w) / 4.0,
     );
 
-    let texture_dim = textureDimensions(t_diffuse);
+    let normal = normalize(normal_matrix * model_normal);
+
+    surface.normal = normal;
 
-    textureStore(voxels_color, in_v, color);
+	// Meshes without tangents can't use their normal map
+    if dot(tangent.xyz, tangent.xyz) > 0.0 {
+        let t = normalize(normal_matrix * tangent.xyz);
+        let b = cross(normal, t) * sign(tangent.w);
+        let tbn = mat3x3<f32>(t, b, normal);
+
+        let nrm = textureSampleLevel(t_normal, s_normal, uv0, 0.0).xyz * 2.0 - 1.0;
+        surface.normal = normalize(tbn * nrm);
+    }
+
+    surface.emissive = textureSampleLevel(t_emissive, s_emissive, uv0, 0.0).rgb;
+    surface.metal_roughness = textureSampleLevel(t_metal, s_metal, uv0, 0.0);
+
+    return surface;
+}
+
+fn store_surface(in_v: vec3<i32>, surface: VoxelSurface) {
+    if !in_region(in_v) {
+        return;
+    }
+
+    let address = in_v + vec3(0, 0, i32(region.z_offset));
+
+    if region.write_volume != 0u {
+        store_color(address, surface.color);
+        textureStore(voxels_normal, address, vec4(surface.normal, 1.0));
+        textureStore(voxels_emissive, address, vec4(surface.emissive, 1.0));
+        textureStore(voxels_material, address, vec4(surface.metal_roughness.b, surface.metal_roughness.g, 0.0, 1.0));
+    }
+
+    if region.append_fragments != 0u {
+        append_fragment(VoxelFragment(
+            pack_position(vec3<u32>(address)),
+            pack4x8unorm(surface.color),
+            pack4x8snorm(vec4(surface.normal, 1.0)),
+            pack4x8unorm(vec4(surface.emissive, 1.0)),
+        ));
+    }
+}
+
+// Fragments past the end of the list are counted but dropped
+fn append_fragment(fragment: VoxelFragment) {
+    let index = atomicAdd(&fragment_counter.count, 1u);
+    if index < arrayLength(&fragments) {
+        fragments[index] = fragment;
+    }
 }
 
 fn voxelize_line(triangle: Triangle, v1: vec3<f32>, v2: vec3<f32>) {
@@ -289,6 +414,25 @@ fn voxelize_interior(triangle: Triangle) {
     }
 }
 
+fn in_region(v: vec3<i32>) -> bool {
+    return all(v >= region.min) && all(v < region.max);
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn clear(@builtin(global_invocation_id) id: vec3<u32>) {
+    let in_v = region.min + vec3<i32>(id);
+    if !in_region(in_v) {
+        return;
+    }
+
+    let address = in_v + vec3(0, 0, i32(region.z_offset));
+
+    textureStore(voxels_color, address, vec4(0.0));
+    textureStore(voxels_normal, address, vec4(0.0));
+    textureStore(voxels_emissive, address, vec4(0.0));
+    textureStore(voxels_material, address, vec4(0.0));
+}
+
 fn compare_vec(v1: vec3<i32>, v2: vec3<i32>) -> bool {
     if v1.x == v2.x && v1.y == v2.y && v1.z == v2.z {
         return true;
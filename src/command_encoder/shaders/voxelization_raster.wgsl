@@ -0,0 +1,74 @@
+// Rasterization path of the voxelizer, every triangle is drawn on the plane facing its dominant axis.
+// The viewport spans the region's largest side at one pixel per voxel
+
+struct RasterVertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) grid_position: vec3<f32>,
+    @location(1) uv0: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec4<f32>,
+    @location(4) @interpolate(flat) dom_axis: u32,
+};
+
+// No vertex buffers, vertex_index walks the indices of the bound primitive
+@vertex
+fn raster_vertex(@builtin(vertex_index) vertex_index: u32) -> RasterVertexOutput {
+    let voxel_size = volume.size / f32(volume.resolution);
+    let triangle_start = primitive.index_start + (vertex_index / 3u) * 3u;
+
+    var grid_positions: array<vec3<f32>, 3>;
+    for (var i: u32 = 0u; i < 3u; i++) {
+        let position = (model_transform.matrix * vec4<f32>(read_vertex(v_indices[triangle_start + i]), 1.0)).xyz;
+        grid_positions[i] = (position - volume.min) / voxel_size;
+    }
+
+    let face_normal = abs(cross(grid_positions[1] - grid_positions[0], grid_positions[2] - grid_positions[0]));
+    var dom_axis = 0u;
+    if face_normal.y > face_normal.x && face_normal.y >= face_normal.z {
+        dom_axis = 1u;
+    } else if face_normal.z > face_normal.x && face_normal.z > face_normal.y {
+        dom_axis = 2u;
+    }
+
+    let index = v_indices[primitive.index_start + vertex_index];
+    let grid_position = grid_positions[vertex_index % 3u];
+    let region_size = vec3<f32>(region.max - region.min);
+    let extent = max(region_size.x, max(region_size.y, region_size.z));
+    let local = (grid_position - vec3<f32>(region.min)) / extent;
+
+    var projected: vec3<f32>;
+    switch dom_axis {
+        case 0u: { projected = local.yzx; }
+        case 1u: { projected = local.zxy; }
+        default: { projected = local.xyz; }
+    }
+
+    var out: RasterVertexOutput;
+    out.clip_position = vec4(projected.xy * 2.0 - 1.0, projected.z, 1.0);
+    out.grid_position = grid_position;
+    out.uv0 = read_color(index).uv0;
+    out.normal = read_normal(index).normals;
+    out.tangent = read_normal(index).tangents;
+    out.dom_axis = dom_axis;
+
+    return out;
+}
+
+// Coverage is widened by MSAA, and every voxel the triangle passes along the dominant axis within the pixel is written
+@fragment
+fn raster_fragment(in: RasterVertexOutput) -> @location(0) vec4<f32> {
+    let depth = in.grid_position[in.dom_axis];
+    let slope = fwidth(depth) * 0.5;
+
+    let surface = shade_surface(in.uv0, in.normal, in.tangent);
+
+    var voxel = vec3<i32>(floor(in.grid_position));
+    let first = i32(floor(depth - slope));
+    let last = i32(floor(depth + slope));
+    for (var d = first; d <= last; d++) {
+        voxel[in.dom_axis] = d;
+        store_surface(voxel, surface);
+    }
+
+    return vec4(0.0);
+}
@@ -0,0 +1,210 @@
+use std::{
+	io::{self, Read, Write},
+	path::Path,
+};
+
+use super::{VoxelVolumeDescriptor, VoxelsResource};
+use crate::{readback::VolumeReadback, Renderer};
+
+const CACHE_MAGIC: &[u8; 4] = b"VCTV";
+const CACHE_VERSION: u32 = 1;
+
+// Voxelized colour, normal, emissive and material volumes with every mip level, saved to skip voxelizing static scenes.
+// The volume and the hash of the source glTF files decide whether a cache still matches
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoxelCache {
+	pub volume: VoxelVolumeDescriptor,
+	pub source_hash: u64,
+	// Channel, then mip level, then 4 byte voxels with x varying fastest
+	pub channels: [Vec<Vec<[u8; 4]>>; 4],
+}
+
+impl VoxelCache {
+	// FNV-1a over every glTF file of the scene in the given order and every external buffer and image they reference,
+	// stable across builds
+	pub fn source_hash<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> io::Result<u64> {
+		paths
+			.into_iter()
+			.try_fold(0xcbf29ce484222325, |hash, path| hash_gltf(hash, path.as_ref()))
+	}
+
+	// Blocks until every mip level of the voxelized volumes was copied back
+	pub fn capture(renderer: &Renderer, voxels: &VoxelsResource, source_hash: u64) -> Result<Self, wgpu::BufferAsyncError> {
+		let mut encoder = renderer
+			.device()
+			.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+				label: Some("Voxel Cache Encoder"),
+			});
+
+		let readbacks = channel_textures(voxels).map(|texture| {
+			(0..texture.mip_level_count())
+				.map(|mip_level| VolumeReadback::new(renderer.device(), &mut encoder, texture, mip_level))
+				.collect::<Vec<_>>()
+		});
+		renderer.queue().submit(Some(encoder.finish()));
+
+		let mut channels: [Vec<Vec<[u8; 4]>>; 4] = Default::default();
+		for (channel, levels) in channels.iter_mut().zip(readbacks) {
+			for readback in levels {
+				channel.push(readback.read(renderer.device())?.1);
+			}
+		}
+
+		Ok(Self {
+			volume: voxels.volume,
+			source_hash,
+			channels,
+		})
+	}
+
+	pub fn matches(&self, volume: &VoxelVolumeDescriptor, source_hash: u64) -> bool {
+		self.volume == *volume && self.source_hash == source_hash
+	}
+
+	// Writes every mip level into the resource's volumes, which have to share the cache's volume
+	pub fn upload(&self, renderer: &Renderer, voxels: &VoxelsResource) {
+		assert_eq!(self.volume, voxels.volume);
+
+		for (levels, texture) in self.channels.iter().zip(channel_textures(voxels)) {
+			for (mip_level, level) in levels.iter().enumerate() {
+				write_mip_level(renderer.queue(), texture, mip_level as u32, level);
+			}
+		}
+	}
+
+	pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+		writer.write_all(CACHE_MAGIC)?;
+		writer.write_all(&CACHE_VERSION.to_le_bytes())?;
+		writer.write_all(&self.source_hash.to_le_bytes())?;
+		for value in self.volume.min.iter().chain(self.volume.max.iter()) {
+			writer.write_all(&value.to_le_bytes())?;
+		}
+		writer.write_all(&self.volume.resolution.to_le_bytes())?;
+
+		for levels in self.channels.iter() {
+			for level in levels {
+				writer.write_all(bytemuck::cast_slice(level))?;
+			}
+		}
+
+		Ok(())
+	}
+
+	pub fn read(reader: &mut impl Read) -> io::Result<Self> {
+		let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+
+		let mut magic = [0; 4];
+		reader.read_exact(&mut magic)?;
+		if &magic != CACHE_MAGIC {
+			return Err(invalid("not a voxel cache"));
+		}
+		if read_u32(reader)? != CACHE_VERSION {
+			return Err(invalid("unsupported voxel cache version"));
+		}
+
+		let mut source_hash = [0; 8];
+		reader.read_exact(&mut source_hash)?;
+
+		let mut bounds = [0.0; 6];
+		for value in bounds.iter_mut() {
+			*value = f32::from_bits(read_u32(reader)?);
+		}
+		let resolution = read_u32(reader)?;
+		if resolution == 0 {
+			return Err(invalid("voxel cache without voxels"));
+		}
+		// Renderer requests the default limits, so none of its devices takes a larger volume
+		if resolution > wgpu::Limits::default().max_texture_dimension_3d {
+			return Err(invalid("voxel cache resolution exceeds the 3D texture limit"));
+		}
+
+		let volume = VoxelVolumeDescriptor {
+			min: glm::vec3(bounds[0], bounds[1], bounds[2]),
+			max: glm::vec3(bounds[3], bounds[4], bounds[5]),
+			resolution,
+		};
+
+		let mut channels: [Vec<Vec<[u8; 4]>>; 4] = Default::default();
+		for levels in channels.iter_mut() {
+			for mip_level in 0..volume.extent().max_mips(wgpu::TextureDimension::D3) {
+				// Read as it arrives, so a truncated file fails before its whole size is allocated
+				let size = (resolution >> mip_level).max(1) as u64;
+				let length = size * size * size * 4;
+				let mut level = Vec::new();
+				reader.take(length).read_to_end(&mut level)?;
+				if level.len() as u64 != length {
+					return Err(io::ErrorKind::UnexpectedEof.into());
+				}
+				levels.push(bytemuck::pod_collect_to_vec(&level));
+			}
+		}
+
+		Ok(Self {
+			volume,
+			source_hash: u64::from_le_bytes(source_hash),
+			channels,
+		})
+	}
+}
+
+fn channel_textures(voxels: &VoxelsResource) -> [&wgpu::Texture; 4] {
+	[
+		&voxels.color.texture,
+		&voxels.normal.texture,
+		&voxels.emissive.texture,
+		&voxels.material.texture,
+	]
+}
+
+pub(crate) fn write_mip_level(queue: &wgpu::Queue, texture: &wgpu::Texture, mip_level: u32, voxels: &[[u8; 4]]) {
+	let size = texture.size().mip_level_size(mip_level, wgpu::TextureDimension::D3);
+
+	queue.write_texture(
+		wgpu::ImageCopyTexture {
+			texture,
+			mip_level,
+			origin: wgpu::Origin3d::ZERO,
+			aspect: wgpu::TextureAspect::All,
+		},
+		bytemuck::cast_slice(voxels),
+		wgpu::ImageDataLayout {
+			offset: 0,
+			bytes_per_row: Some(size.width * 4),
+			rows_per_image: Some(size.height),
+		},
+		size,
+	);
+}
+
+fn hash_gltf(hash: u64, path: &Path) -> io::Result<u64> {
+	let mut hash = fnv1a(hash, &std::fs::read(path)?);
+
+	let gltf = gltf::Gltf::open(path).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+	let buffer_uris = gltf.buffers().filter_map(|buffer| match buffer.source() {
+		gltf::buffer::Source::Uri(uri) => Some(uri),
+		gltf::buffer::Source::Bin => None,
+	});
+	let image_uris = gltf.images().filter_map(|image| match image.source() {
+		gltf::image::Source::Uri { uri, .. } => Some(uri),
+		gltf::image::Source::View { .. } => None,
+	});
+
+	let directory = path.parent().unwrap_or(Path::new(""));
+	for uri in buffer_uris.chain(image_uris).filter(|uri| !uri.starts_with("data:")) {
+		hash = fnv1a(hash, &std::fs::read(directory.join(uri))?);
+	}
+
+	Ok(hash)
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+	let mut bytes = [0; 4];
+	reader.read_exact(&mut bytes)?;
+	Ok(u32::from_le_bytes(bytes))
+}
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+	bytes
+		.iter()
+		.fold(hash, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
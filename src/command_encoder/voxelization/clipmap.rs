@@ -0,0 +1,431 @@
+use crate::command_encoder::*;
+use crate::Renderer;
+
+use super::{
+	MeshSelection, VoxelVolumeDescriptor, VoxelVolumeUniform, VoxelWriteMode, VoxelizationRegionUniform, Voxelizer,
+};
+
+pub const MAX_CLIPMAP_LEVELS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VoxelClipmapDescriptor {
+	pub level_count: u32,
+	// Voxels along every side of a level, has to be even
+	pub resolution: u32,
+	// Voxel size of the finest level, every further level doubles it
+	pub voxel_size: f32,
+}
+
+impl Default for VoxelClipmapDescriptor {
+	fn default() -> Self {
+		Self {
+			level_count: 5,
+			resolution: 128,
+			voxel_size: 0.1,
+		}
+	}
+}
+
+impl VoxelClipmapDescriptor {
+	pub fn level_voxel_size(&self, level: u32) -> f32 {
+		self.voxel_size * (1u32 << level) as f32
+	}
+
+	pub fn level_extent(&self, level: u32) -> f32 {
+		self.level_voxel_size(level) * self.resolution as f32
+	}
+
+	// Levels are stacked along z in every clipmap texture
+	pub fn extent(&self) -> wgpu::Extent3d {
+		wgpu::Extent3d {
+			width: self.resolution,
+			height: self.resolution,
+			depth_or_array_layers: self.resolution * self.level_count,
+		}
+	}
+
+	// Grid coordinates of the level's min corner, snapped to its voxels so scrolling moves whole voxels
+	pub fn level_origin(&self, level: u32, center: &glm::Vec3) -> [i32; 3] {
+		let voxel_size = self.level_voxel_size(level);
+		[0, 1, 2].map(|i| (center[i] / voxel_size).floor() as i32 - (self.resolution / 2) as i32)
+	}
+
+	fn level_volume(&self, level: u32, origin: [i32; 3]) -> VoxelVolumeDescriptor {
+		let voxel_size = self.level_voxel_size(level);
+		let min = glm::vec3(origin[0] as f32, origin[1] as f32, origin[2] as f32) * voxel_size;
+
+		VoxelVolumeDescriptor {
+			min,
+			max: min + glm::vec3(1.0, 1.0, 1.0) * self.level_extent(level),
+			resolution: self.resolution,
+		}
+	}
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ClipmapLevelUniform {
+	min: [f32; 3],
+	voxel_size: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct VoxelClipmapUniform {
+	resolution: u32,
+	level_count: u32,
+	_padding: [u32; 2],
+	levels: [ClipmapLevelUniform; MAX_CLIPMAP_LEVELS],
+}
+
+pub struct VoxelClipmapResource {
+	pub color: crate::mesh::Texture,
+	pub normal: crate::mesh::Texture,
+	pub emissive: crate::mesh::Texture,
+	pub material: crate::mesh::Texture,
+	// Lit voxels written by the RadianceInjectionPass, alpha holds the opacity
+	pub radiance: crate::mesh::Texture,
+	pub descriptor: VoxelClipmapDescriptor,
+	pub clipmap_buffer: wgpu::Buffer,
+	// Grid origin every level currently holds, None until it is first voxelized
+	pub origins: Vec<Option<[i32; 3]>>,
+}
+
+impl crate::Resource for VoxelClipmapResource {
+	fn updated_settings(&mut self, _renderer: &Renderer) {}
+}
+
+fn new_clipmap_texture(
+	device: &wgpu::Device,
+	size: wgpu::Extent3d,
+	label: &str,
+	format: wgpu::TextureFormat,
+) -> crate::mesh::Texture {
+	let texture = device.create_texture(&wgpu::TextureDescriptor {
+		label: Some(&format!("{label} Texture")),
+		size,
+		mip_level_count: 1,
+		sample_count: 1,
+		dimension: wgpu::TextureDimension::D3,
+		format,
+		usage: wgpu::TextureUsages::STORAGE_BINDING
+			| wgpu::TextureUsages::TEXTURE_BINDING
+			| wgpu::TextureUsages::COPY_SRC
+			| wgpu::TextureUsages::COPY_DST,
+		view_formats: &[],
+	});
+
+	let view = texture.create_view(&Default::default());
+	let sampler = device.create_sampler(&super::voxel_sampler_descriptor());
+
+	crate::mesh::Texture {
+		texture,
+		view,
+		sampler,
+	}
+}
+
+// A level re-voxelizes at most one slab per axis, each through its own slot
+const REGIONS_PER_LEVEL: usize = 3;
+
+struct ClipmapRegionSlot {
+	volume_buffer: wgpu::Buffer,
+	region_buffer: wgpu::Buffer,
+	bind_group: wgpu::BindGroup,
+}
+
+// Keeps a VoxelClipmapResource centred on the camera, only voxelizing the slabs that scrolled into view
+pub struct ClipmapVoxelizationPass {
+	voxelizer: Voxelizer,
+	region_slots: Vec<ClipmapRegionSlot>,
+	// Copies can't overlap inside a texture, so shifted contents go through these
+	scratch: wgpu::Texture,
+	scratch_normal: wgpu::Texture,
+}
+
+impl ClipmapVoxelizationPass {
+	// Panics when the descriptor has no levels or more than MAX_CLIPMAP_LEVELS, or an odd or zero resolution
+	pub fn new(renderer: &Renderer, descriptor: &VoxelClipmapDescriptor) -> Self {
+		assert!(
+			(1..=MAX_CLIPMAP_LEVELS).contains(&(descriptor.level_count as usize)),
+			"A clipmap needs between 1 and {MAX_CLIPMAP_LEVELS} levels, got {}",
+			descriptor.level_count
+		);
+		assert!(
+			descriptor.resolution > 0 && descriptor.resolution.is_multiple_of(2),
+			"A clipmap's resolution has to be even, got {}",
+			descriptor.resolution
+		);
+
+		let size = descriptor.extent();
+
+		let clipmap_buffer = renderer.device().create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Voxel Clipmap Buffer"),
+			size: std::mem::size_of::<VoxelClipmapUniform>() as u64,
+			usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+
+		let clipmap_resource = VoxelClipmapResource {
+			color: new_clipmap_texture(renderer.device(), size, "Clipmap Color", wgpu::TextureFormat::Rgba8Unorm),
+			normal: new_clipmap_texture(renderer.device(), size, "Clipmap Normal", wgpu::TextureFormat::Rgba8Snorm),
+			emissive: new_clipmap_texture(renderer.device(), size, "Clipmap Emissive", wgpu::TextureFormat::Rgba8Unorm),
+			material: new_clipmap_texture(renderer.device(), size, "Clipmap Material", wgpu::TextureFormat::Rgba8Unorm),
+			radiance: new_clipmap_texture(renderer.device(), size, "Clipmap Radiance", wgpu::TextureFormat::Rgba8Unorm),
+			descriptor: *descriptor,
+			clipmap_buffer,
+			origins: vec![None; descriptor.level_count as usize],
+		};
+
+		let voxelizer = Voxelizer::new(renderer);
+
+		let region_slots = (0..descriptor.level_count as usize * REGIONS_PER_LEVEL)
+			.map(|_| {
+				let new_uniform_buffer = |label, size| {
+					renderer.device().create_buffer(&wgpu::BufferDescriptor {
+						label: Some(label),
+						size: size as u64,
+						usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+						mapped_at_creation: false,
+					})
+				};
+
+				let volume_buffer = new_uniform_buffer(
+					"Clipmap Level Volume Buffer",
+					std::mem::size_of::<VoxelVolumeUniform>(),
+				);
+				let region_buffer = new_uniform_buffer(
+					"Clipmap Region Buffer",
+					std::mem::size_of::<VoxelizationRegionUniform>(),
+				);
+
+				let bind_group = voxelizer.new_bind_group(
+					renderer.device(),
+					&volume_buffer,
+					&region_buffer,
+					[
+						&clipmap_resource.color.view,
+						&clipmap_resource.normal.view,
+						&clipmap_resource.emissive.view,
+						&clipmap_resource.material.view,
+					],
+					None,
+					None,
+				);
+
+				ClipmapRegionSlot {
+					volume_buffer,
+					region_buffer,
+					bind_group,
+				}
+			})
+			.collect();
+
+		renderer.insert_resource(clipmap_resource);
+
+		let level_size = wgpu::Extent3d {
+			depth_or_array_layers: descriptor.resolution,
+			..size
+		};
+
+		let new_scratch = |format| {
+			renderer.device().create_texture(&wgpu::TextureDescriptor {
+				label: Some("Clipmap Scratch Texture"),
+				size: level_size,
+				mip_level_count: 1,
+				sample_count: 1,
+				dimension: wgpu::TextureDimension::D3,
+				format,
+				usage: wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST,
+				view_formats: &[],
+			})
+		};
+
+		Self {
+			voxelizer,
+			region_slots,
+			scratch: new_scratch(wgpu::TextureFormat::Rgba8Unorm),
+			scratch_normal: new_scratch(wgpu::TextureFormat::Rgba8Snorm),
+		}
+	}
+
+	// Moves what is still inside the level by -delta voxels
+	fn encode_shift(
+		&self,
+		encoder: &mut wgpu::CommandEncoder,
+		clipmap: &VoxelClipmapResource,
+		level: u32,
+		delta: [i32; 3],
+	) {
+		let resolution = clipmap.descriptor.resolution as i32;
+		let z_offset = level * clipmap.descriptor.resolution;
+
+		let source_min = delta.map(|d| d.max(0));
+		let source_max = delta.map(|d| resolution.min(resolution + d));
+		let size = wgpu::Extent3d {
+			width: (source_max[0] - source_min[0]) as u32,
+			height: (source_max[1] - source_min[1]) as u32,
+			depth_or_array_layers: (source_max[2] - source_min[2]) as u32,
+		};
+
+		let origin = |position: [i32; 3], z_offset: u32| wgpu::Origin3d {
+			x: position[0] as u32,
+			y: position[1] as u32,
+			z: position[2] as u32 + z_offset,
+		};
+
+		for (texture, scratch) in [
+			(&clipmap.color.texture, &self.scratch),
+			(&clipmap.normal.texture, &self.scratch_normal),
+			(&clipmap.emissive.texture, &self.scratch),
+			(&clipmap.material.texture, &self.scratch),
+		] {
+			encoder.copy_texture_to_texture(
+				wgpu::ImageCopyTexture {
+					origin: origin(source_min, z_offset),
+					..texture.as_image_copy()
+				},
+				wgpu::ImageCopyTexture {
+					origin: origin(source_min, 0),
+					..scratch.as_image_copy()
+				},
+				size,
+			);
+
+			encoder.copy_texture_to_texture(
+				wgpu::ImageCopyTexture {
+					origin: origin(source_min, 0),
+					..scratch.as_image_copy()
+				},
+				wgpu::ImageCopyTexture {
+					origin: origin([0, 1, 2].map(|i| source_min[i] - delta[i]), z_offset),
+					..texture.as_image_copy()
+				},
+				size,
+			);
+		}
+	}
+}
+
+impl RenderPassTrait for ClipmapVoxelizationPass {
+	fn execute<'manager>(
+		&mut self,
+		command_encoder: &'manager CommandEncoder,
+		global_resources: &mut crate::ResourceManagerHandle<'manager>,
+	) -> Option<wgpu::CommandBuffer> {
+		// The camera position translates the world, so the eye sits at its inverse
+		let center = -command_encoder.get_camera().unwrap().position();
+
+		let mut shifts = Vec::new();
+		let mut regions = Vec::new();
+		let mut levels = Vec::new();
+		{
+			let mut clipmap = global_resources.get_mut_resource::<VoxelClipmapResource>().unwrap();
+			let descriptor = clipmap.descriptor;
+			let resolution = descriptor.resolution as i32;
+
+			for level in 0..descriptor.level_count {
+				let origin = descriptor.level_origin(level, &center);
+				let z_offset = level * descriptor.resolution;
+
+				match clipmap.origins[level as usize] {
+					Some(old_origin) if old_origin == origin => {}
+					Some(old_origin) if (0..3).all(|i| (origin[i] - old_origin[i]).abs() < resolution) => {
+						let delta = [0, 1, 2].map(|i| origin[i] - old_origin[i]);
+						shifts.push((level, delta));
+
+						for axis in 0..3 {
+							let mut min = [0; 3];
+							let mut max = [resolution; 3];
+
+							if delta[axis] > 0 {
+								min[axis] = resolution - delta[axis];
+							} else if delta[axis] < 0 {
+								max[axis] = -delta[axis];
+							} else {
+								continue;
+							}
+
+							regions.push((level, origin, VoxelizationRegionUniform::new(min, max, z_offset)));
+						}
+					}
+					_ => {
+						regions.push((level, origin, VoxelizationRegionUniform::new([0; 3], [resolution; 3], z_offset)));
+					}
+				}
+
+				clipmap.origins[level as usize] = Some(origin);
+
+				let volume = descriptor.level_volume(level, origin);
+				levels.push(ClipmapLevelUniform {
+					min: volume.min.into(),
+					voxel_size: volume.voxel_size(),
+				});
+			}
+		}
+
+		let clipmap = global_resources.get_resource::<VoxelClipmapResource>().unwrap();
+
+		levels.resize(MAX_CLIPMAP_LEVELS, bytemuck::Zeroable::zeroed());
+		let clipmap_uniform = VoxelClipmapUniform {
+			resolution: clipmap.descriptor.resolution,
+			level_count: clipmap.descriptor.level_count,
+			_padding: [0; 2],
+			levels: levels.try_into().unwrap(),
+		};
+		command_encoder.queue().write_buffer(
+			&clipmap.clipmap_buffer,
+			0,
+			bytemuck::cast_slice(&[clipmap_uniform]),
+		);
+
+		let mut encoder = command_encoder
+			.device()
+			.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+				label: Some("Clipmap Voxelization Encoder"),
+			});
+
+		for (level, delta) in shifts {
+			self.encode_shift(&mut encoder, &clipmap, level, delta);
+		}
+
+		let mut slot_counts = vec![0; clipmap.descriptor.level_count as usize];
+		let bind_groups: Vec<_> = regions
+			.iter()
+			.map(|(level, origin, region)| {
+				let slot_index = *level as usize * REGIONS_PER_LEVEL + slot_counts[*level as usize];
+				slot_counts[*level as usize] += 1;
+				let slot = &self.region_slots[slot_index];
+
+				let volume = clipmap.descriptor.level_volume(*level, *origin);
+				command_encoder.queue().write_buffer(
+					&slot.volume_buffer,
+					0,
+					bytemuck::cast_slice(&[volume.to_uniform()]),
+				);
+				command_encoder
+					.queue()
+					.write_buffer(&slot.region_buffer, 0, bytemuck::cast_slice(&[*region]));
+
+				&slot.bind_group
+			})
+			.collect();
+
+		// Regions may overlap, so everything is cleared before anything is voxelized
+		for (bind_group, (_, _, region)) in bind_groups.iter().zip(regions.iter()) {
+			self.voxelizer.encode_clear(&mut encoder, bind_group, region);
+		}
+
+		if !bind_groups.is_empty() {
+			self.voxelizer.encode(
+				command_encoder,
+				&mut encoder,
+				&bind_groups,
+				VoxelWriteMode::LastWriter,
+				MeshSelection::ALL,
+			);
+		}
+
+		Some(encoder.finish())
+	}
+}
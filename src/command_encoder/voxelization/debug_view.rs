@@ -0,0 +1,363 @@
+use crate::command_encoder::*;
+use wgpu_helper::bind_group::{BindGroup, BindGroupType};
+
+use crate::Renderer;
+
+use super::{MeshifyMode, MeshifyPass, MeshifySettings, RenderMeshifyPass, VoxelsResource};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VoxelChannel {
+	#[default]
+	Albedo = 0,
+	// The alpha of the colour volume as grey
+	Opacity = 1,
+	// Mapped from [-1, 1] to [0, 1]
+	Normal = 2,
+	Emissive = 3,
+	// Lit voxels of the RadianceInjectionPass
+	Radiance = 4,
+}
+
+impl VoxelChannel {
+	pub(crate) fn texture(self, voxels: &VoxelsResource) -> &crate::mesh::Texture {
+		match self {
+			Self::Albedo | Self::Opacity => &voxels.color,
+			Self::Normal => &voxels.normal,
+			Self::Emissive => &voxels.emissive,
+			Self::Radiance => &voxels.radiance,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VoxelDebugStyle {
+	// Meshified cubes, see MeshifyMode::Cubes
+	#[default]
+	Cubes,
+	// One pixel per occupied voxel
+	Points,
+	// Every pixel marches through the volume until it hits a voxel, nothing is meshified
+	RayMarch,
+}
+
+// A single layer of voxels at the debugged mip level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VoxelSlice {
+	// 0 to 2 for X to Z
+	pub axis: u32,
+	pub index: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VoxelDebugSettings {
+	// Clamped to the coarsest level of the volume
+	pub mip_level: u32,
+	pub channel: VoxelChannel,
+	pub style: VoxelDebugStyle,
+	// Only voxels of the slice are drawn as points or ray marched, cubes always show the whole volume
+	pub slice: Option<VoxelSlice>,
+}
+
+impl Default for VoxelDebugSettings {
+	fn default() -> Self {
+		Self {
+			mip_level: 1,
+			channel: VoxelChannel::default(),
+			style: VoxelDebugStyle::default(),
+			slice: None,
+		}
+	}
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct VoxelDebugUniform {
+	inverse_view_proj: [[f32; 4]; 4],
+	mip_level: u32,
+	channel: u32,
+	slice_axis: u32,
+	slice_index: i32,
+}
+
+// Draws one mip level and channel of the VoxelsResource over the whole frame, in place of the forward pass
+pub struct VoxelDebugPass {
+	settings: VoxelDebugSettings,
+	settings_buffer: wgpu::Buffer,
+	bind_group_layout: wgpu::BindGroupLayout,
+	points_pipeline: wgpu::RenderPipeline,
+	ray_march_pipeline: wgpu::RenderPipeline,
+	meshify_pass: MeshifyPass,
+	render_meshify_pass: RenderMeshifyPass,
+}
+
+impl VoxelDebugPass {
+	pub fn new(renderer: &Renderer) -> Self {
+		let shader = renderer
+			.device()
+			.create_shader_module(wgpu::ShaderModuleDescriptor {
+				label: Some("Voxel Debug Shader"),
+				source: wgpu::ShaderSource::Wgsl(
+					format!(
+						"{}\n{}",
+						include_str!("../shaders/voxel_debug_channel.wgsl"),
+						include_str!("../shaders/voxel_debug.wgsl")
+					)
+					.into(),
+				),
+			});
+
+		let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+			binding,
+			visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+			ty: wgpu::BindingType::Texture {
+				sample_type: wgpu::TextureSampleType::Float { filterable: false },
+				view_dimension: wgpu::TextureViewDimension::D3,
+				multisampled: false,
+			},
+			count: None,
+		};
+		let uniform_entry = |binding| wgpu::BindGroupLayoutEntry {
+			binding,
+			visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+			ty: wgpu::BindingType::Buffer {
+				ty: wgpu::BufferBindingType::Uniform,
+				has_dynamic_offset: false,
+				min_binding_size: None,
+			},
+			count: None,
+		};
+
+		let bind_group_layout =
+			renderer
+				.device()
+				.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+					label: Some("Voxel Debug Bind Group Layout"),
+					entries: &[texture_entry(0), texture_entry(1), uniform_entry(2), uniform_entry(3)],
+				});
+
+		let pipeline_layout =
+			renderer
+				.device()
+				.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+					label: Some("Voxel Debug Pipeline layout"),
+					bind_group_layouts: &[
+						crate::camera::CameraBindGroup::get_bind_group_layout(renderer.device()),
+						&bind_group_layout,
+					],
+					push_constant_ranges: &[],
+				});
+
+		let format = renderer.renderer.config.lock().unwrap().format;
+		let new_pipeline = |vertex_entry_point, fragment_entry_point, topology, depth_stencil| {
+			renderer
+				.device()
+				.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+					label: Some("Voxel Debug Pipeline"),
+					layout: Some(&pipeline_layout),
+					vertex: wgpu::VertexState {
+						module: &shader,
+						entry_point: vertex_entry_point,
+						buffers: &[],
+					},
+					fragment: Some(wgpu::FragmentState {
+						module: &shader,
+						entry_point: fragment_entry_point,
+						targets: &[Some(wgpu::ColorTargetState {
+							format,
+							blend: Some(wgpu::BlendState::REPLACE),
+							write_mask: wgpu::ColorWrites::ALL,
+						})],
+					}),
+					primitive: wgpu::PrimitiveState {
+						topology,
+						..Default::default()
+					},
+					depth_stencil,
+					multisample: wgpu::MultisampleState::default(),
+					multiview: None,
+				})
+		};
+
+		let points_pipeline = new_pipeline(
+			"vs_points",
+			"fs_points",
+			wgpu::PrimitiveTopology::PointList,
+			Some(wgpu::DepthStencilState {
+				format: crate::DEPTH_FORMAT,
+				depth_write_enabled: true,
+				depth_compare: wgpu::CompareFunction::Less,
+				stencil: wgpu::StencilState::default(),
+				bias: wgpu::DepthBiasState::default(),
+			}),
+		);
+		let ray_march_pipeline = new_pipeline(
+			"vs_fullscreen",
+			"fs_ray_march",
+			wgpu::PrimitiveTopology::TriangleList,
+			None,
+		);
+
+		let settings_buffer = renderer.device().create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Voxel Debug Settings Buffer"),
+			size: std::mem::size_of::<VoxelDebugUniform>() as u64,
+			usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+
+		Self {
+			settings: VoxelDebugSettings::default(),
+			settings_buffer,
+			bind_group_layout,
+			points_pipeline,
+			ray_march_pipeline,
+			meshify_pass: MeshifyPass::new(renderer),
+			render_meshify_pass: RenderMeshifyPass::new(renderer),
+		}
+	}
+
+	pub fn settings(&self) -> VoxelDebugSettings {
+		self.settings
+	}
+
+	pub fn set_settings(&mut self, settings: VoxelDebugSettings) {
+		self.settings = settings;
+	}
+}
+
+impl RenderPassTrait for VoxelDebugPass {
+	fn execute<'manager>(
+		&mut self,
+		command_encoder: &'manager CommandEncoder,
+		global_resources: &mut crate::ResourceManagerHandle<'manager>,
+	) -> Option<wgpu::CommandBuffer> {
+		let mut encoder =
+			command_encoder
+				.device()
+				.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+					label: Some("Voxel Debug Encoder"),
+				});
+
+		if self.settings.style == VoxelDebugStyle::Cubes {
+			self.meshify_pass.set_settings(MeshifySettings {
+				mode: MeshifyMode::Cubes,
+				mip_level: self.settings.mip_level,
+				channel: self.settings.channel,
+			});
+			self.meshify_pass.encode(command_encoder, global_resources, &mut encoder);
+			self.render_meshify_pass.encode(command_encoder, global_resources, &mut encoder);
+
+			return Some(encoder.finish());
+		}
+
+		let camera = command_encoder.get_camera().unwrap();
+		if global_resources
+			.get_resource::<crate::resources::DepthBufferResource>()
+			.is_none()
+		{
+			let depth_buffer = camera.new_depth_buffer();
+			global_resources.insert_resource(crate::resources::DepthBufferResource { depth_buffer });
+		}
+
+		let voxels_resource = global_resources.get_resource::<VoxelsResource>().unwrap();
+		let depth_buffer = global_resources
+			.get_resource::<crate::resources::DepthBufferResource>()
+			.unwrap();
+
+		let mip_level = self.settings.mip_level.min(voxels_resource.color.texture.mip_level_count() - 1);
+		let (slice_axis, slice_index) = match self.settings.slice {
+			Some(slice) => (slice.axis.min(2), slice.index as i32),
+			None => (0, -1),
+		};
+		command_encoder.queue().write_buffer(
+			&self.settings_buffer,
+			0,
+			bytemuck::cast_slice(&[VoxelDebugUniform {
+				inverse_view_proj: glm::inverse(&camera.view_proj()).into(),
+				mip_level,
+				channel: self.settings.channel as u32,
+				slice_axis,
+				slice_index,
+			}]),
+		);
+
+		let bind_group = command_encoder
+			.device()
+			.create_bind_group(&wgpu::BindGroupDescriptor {
+				label: Some("Voxel Debug Bindgroup"),
+				layout: &self.bind_group_layout,
+				entries: &[
+					wgpu::BindGroupEntry {
+						binding: 0,
+						resource: wgpu::BindingResource::TextureView(&voxels_resource.color.view),
+					},
+					wgpu::BindGroupEntry {
+						binding: 1,
+						resource: wgpu::BindingResource::TextureView(
+							&self.settings.channel.texture(&voxels_resource).view,
+						),
+					},
+					wgpu::BindGroupEntry {
+						binding: 2,
+						resource: voxels_resource.volume_buffer.as_entire_binding(),
+					},
+					wgpu::BindGroupEntry {
+						binding: 3,
+						resource: self.settings_buffer.as_entire_binding(),
+					},
+				],
+			});
+
+		let view = command_encoder.get_surface_texture_view();
+		let points = self.settings.style == VoxelDebugStyle::Points;
+		{
+			let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+				label: Some("Voxel Debug Pass"),
+				color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+					view: &view,
+					resolve_target: None,
+					ops: wgpu::Operations {
+						load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+						store: wgpu::StoreOp::Store,
+					},
+				})],
+				depth_stencil_attachment: points.then(|| wgpu::RenderPassDepthStencilAttachment {
+					view: &depth_buffer.depth_buffer.view,
+					depth_ops: Some(wgpu::Operations {
+						load: wgpu::LoadOp::Clear(1.0),
+						store: wgpu::StoreOp::Store,
+					}),
+					stencil_ops: None,
+				}),
+				occlusion_query_set: None,
+				timestamp_writes: None,
+			});
+
+			render_pass.set_bind_group(
+				0,
+				unsafe {
+					command_encoder
+						.get_camera_bind_group()
+						.unwrap()
+						.as_untyped()
+				},
+				&[],
+			);
+			render_pass.set_bind_group(1, &bind_group, &[]);
+
+			if points {
+				let size = voxels_resource
+					.color
+					.texture
+					.size()
+					.mip_level_size(mip_level, wgpu::TextureDimension::D3);
+				render_pass.set_pipeline(&self.points_pipeline);
+				render_pass.draw(0..size.width * size.height * size.depth_or_array_layers, 0..1);
+			} else {
+				render_pass.set_pipeline(&self.ray_march_pipeline);
+				render_pass.draw(0..3, 0..1);
+			}
+		}
+
+		Some(encoder.finish())
+	}
+}
@@ -0,0 +1,207 @@
+use std::io::{self, Write};
+
+use super::{VoxelVolumeDescriptor, VoxelsResource};
+use crate::{readback::VolumeReadback, Renderer};
+
+// MagicaVoxel models can't be larger than this along any axis
+const VOX_MAX_SIZE: u32 = 256;
+// Index 0 of a .vox palette means empty
+const VOX_PALETTE_SIZE: usize = 255;
+
+// One mip level of a voxel volume copied back to the CPU, x varies fastest, then y, then z
+pub struct VoxelVolumeData {
+	pub size: [u32; 3],
+	pub voxels: Vec<[u8; 4]>,
+	pub volume: VoxelVolumeDescriptor,
+	pub mip_level: u32,
+}
+
+impl VoxelVolumeData {
+	// Blocks until the mip level was copied, the texture has to hold 4 bytes per voxel
+	pub fn read(
+		renderer: &Renderer,
+		texture: &wgpu::Texture,
+		volume: VoxelVolumeDescriptor,
+		mip_level: u32,
+	) -> Result<Self, wgpu::BufferAsyncError> {
+		assert!(mip_level < texture.mip_level_count());
+
+		let mut encoder = renderer
+			.device()
+			.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+				label: Some("Voxel Export Encoder"),
+			});
+		let readback = VolumeReadback::new(renderer.device(), &mut encoder, texture, mip_level);
+		renderer.queue().submit(Some(encoder.finish()));
+
+		let (size, voxels) = readback.read(renderer.device())?;
+
+		Ok(Self {
+			size: [size.width, size.height, size.depth_or_array_layers],
+			voxels,
+			volume,
+			mip_level,
+		})
+	}
+
+	// Volumes don't have to be cubic, so voxels can be longer along some axes
+	pub fn voxel_size(&self) -> glm::Vec3 {
+		self.volume.size().component_div(&glm::Vec3::from(self.size.map(|size| size as f32)))
+	}
+
+	pub fn voxel(&self, position: [u32; 3]) -> [u8; 4] {
+		let [x, y, z] = position;
+		self.voxels[((z * self.size[1] + y) * self.size[0] + x) as usize]
+	}
+
+	// Every voxel with a non zero alpha, in storage order
+	pub fn occupied(&self) -> impl Iterator<Item = ([u32; 3], [u8; 4])> + '_ {
+		let [width, height, _] = self.size;
+		self.voxels
+			.iter()
+			.enumerate()
+			.filter(|(_, voxel)| voxel[3] > 0)
+			.map(move |(i, voxel)| {
+				let i = i as u32;
+				([i % width, i / width % height, i / (width * height)], *voxel)
+			})
+	}
+
+	// MagicaVoxel is Z up, so the volume is rotated about X with +Y becoming +Z.
+	// Colours are quantised to a 255 entry palette, alpha is dropped
+	pub fn write_vox(&self, writer: &mut impl Write) -> io::Result<()> {
+		if self.size.iter().any(|size| *size > VOX_MAX_SIZE) {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				format!(
+					"{:?} voxels don't fit a .vox model of at most {} per axis, export a coarser mip level",
+					self.size, VOX_MAX_SIZE
+				),
+			));
+		}
+
+		let occupied: Vec<_> = self.occupied().collect();
+		let colors: Vec<_> = occupied.iter().map(|(_, color)| *color).collect();
+		let (palette, indices) = quantize_palette(&colors);
+
+		let [width, height, depth] = self.size;
+		let size_chunk = [width, depth, height]
+			.iter()
+			.flat_map(|size| size.to_le_bytes())
+			.collect::<Vec<_>>();
+
+		let mut xyzi_chunk = Vec::with_capacity(4 + occupied.len() * 4);
+		xyzi_chunk.extend((occupied.len() as u32).to_le_bytes());
+		for (([x, y, z], _), index) in occupied.iter().zip(indices) {
+			xyzi_chunk.extend([*x as u8, (depth - 1 - z) as u8, *y as u8, index]);
+		}
+
+		let mut rgba_chunk = Vec::with_capacity(256 * 4);
+		for i in 0..256 {
+			let color = palette.get(i).copied().unwrap_or([0, 0, 0, 0]);
+			rgba_chunk.extend([color[0], color[1], color[2], 255]);
+		}
+
+		let children = [(b"SIZE", size_chunk), (b"XYZI", xyzi_chunk), (b"RGBA", rgba_chunk)];
+		let children_size: usize = children.iter().map(|(_, content)| 12 + content.len()).sum();
+
+		writer.write_all(b"VOX ")?;
+		writer.write_all(&150u32.to_le_bytes())?;
+		write_vox_chunk_header(writer, b"MAIN", 0, children_size)?;
+		for (id, content) in children.iter() {
+			write_vox_chunk_header(writer, id, content.len(), 0)?;
+			writer.write_all(content)?;
+		}
+
+		Ok(())
+	}
+
+	// The voxels as tightly packed RGBA8 in storage order, described by a JSON header
+	pub fn write_raw(&self, data: &mut impl Write, header: &mut impl Write) -> io::Result<()> {
+		data.write_all(bytemuck::cast_slice(&self.voxels))?;
+
+		let (min, max, voxel_size) = (self.volume.min, self.volume.max, self.voxel_size());
+		writeln!(header, "{{")?;
+		writeln!(header, "\t\"size\": [{}, {}, {}],", self.size[0], self.size[1], self.size[2])?;
+		writeln!(header, "\t\"format\": \"rgba8unorm\",")?;
+		writeln!(header, "\t\"order\": \"xyz\",")?;
+		writeln!(header, "\t\"min\": [{:?}, {:?}, {:?}],", min.x, min.y, min.z)?;
+		writeln!(header, "\t\"max\": [{:?}, {:?}, {:?}],", max.x, max.y, max.z)?;
+		writeln!(header, "\t\"voxel_size\": [{:?}, {:?}, {:?}],", voxel_size.x, voxel_size.y, voxel_size.z)?;
+		writeln!(header, "\t\"mip_level\": {}", self.mip_level)?;
+		writeln!(header, "}}")
+	}
+
+	// One "x y z r g b a" line per occupied voxel
+	pub fn write_sparse(&self, writer: &mut impl Write) -> io::Result<()> {
+		for ([x, y, z], [r, g, b, a]) in self.occupied() {
+			writeln!(writer, "{x} {y} {z} {r} {g} {b} {a}")?;
+		}
+
+		Ok(())
+	}
+}
+
+impl VoxelsResource {
+	pub fn read_color(&self, renderer: &Renderer, mip_level: u32) -> Result<VoxelVolumeData, wgpu::BufferAsyncError> {
+		VoxelVolumeData::read(renderer, &self.color.texture, self.volume, mip_level)
+	}
+}
+
+fn write_vox_chunk_header(writer: &mut impl Write, id: &[u8; 4], content_size: usize, children_size: usize) -> io::Result<()> {
+	writer.write_all(id)?;
+	writer.write_all(&(content_size as u32).to_le_bytes())?;
+	writer.write_all(&(children_size as u32).to_le_bytes())
+}
+
+// Median cut, the widest channel of the widest bucket is split until the palette is full.
+// Returns the palette and the 1 based palette index of every colour
+fn quantize_palette(colors: &[[u8; 4]]) -> (Vec<[u8; 4]>, Vec<u8>) {
+	let channel_range = |bucket: &[usize], channel: usize| {
+		let values = bucket.iter().map(|i| colors[*i][channel]);
+		values.clone().max().unwrap_or(0) - values.min().unwrap_or(0)
+	};
+	let widest_channel = |bucket: &[usize]| (0..3).max_by_key(|channel| channel_range(bucket, *channel)).unwrap();
+
+	let mut buckets = vec![(0..colors.len()).collect::<Vec<_>>()];
+	while buckets.len() < VOX_PALETTE_SIZE {
+		let Some((widest, range)) = buckets
+			.iter()
+			.enumerate()
+			.map(|(i, bucket)| (i, channel_range(bucket, widest_channel(bucket))))
+			.max_by_key(|(_, range)| *range)
+		else {
+			break;
+		};
+		if range == 0 {
+			break;
+		}
+
+		let mut bucket = buckets.swap_remove(widest);
+		let channel = widest_channel(&bucket);
+		bucket.sort_unstable_by_key(|i| colors[*i][channel]);
+		let upper = bucket.split_off(bucket.len() / 2);
+		buckets.push(bucket);
+		buckets.push(upper);
+	}
+
+	let mut indices = vec![0; colors.len()];
+	let palette = buckets
+		.iter()
+		.filter(|bucket| !bucket.is_empty())
+		.enumerate()
+		.map(|(palette_index, bucket)| {
+			let mut sum = [0u64; 4];
+			for i in bucket {
+				indices[*i] = palette_index as u8 + 1;
+				for (channel, sum) in sum.iter_mut().enumerate() {
+					*sum += colors[*i][channel] as u64;
+				}
+			}
+
+			sum.map(|sum| (sum / bucket.len() as u64) as u8)
+		})
+		.collect();
+
+	(palette, indices)
+}
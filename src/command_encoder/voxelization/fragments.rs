@@ -0,0 +1,220 @@
+use crate::{readback::BufferReadback, Renderer};
+
+// Structs and packing helpers prepended to every shader using a fragment list
+pub(crate) const FRAGMENT_SHADER_PRELUDE: &str = include_str!("../shaders/voxel_fragments.wgsl");
+
+// Grid positions are packed 10 bits per axis, so fragment lists can't address finer volumes
+pub const MAX_FRAGMENT_RESOLUTION: u32 = 1 << 10;
+
+// One voxel of a fragment list, the grid position is packed 10 bits per axis
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct VoxelFragment {
+	pub position: u32,
+	pub color: u32,
+	pub normal: u32,
+	pub emissive: u32,
+}
+
+impl VoxelFragment {
+	pub fn position(&self) -> [u32; 3] {
+		[0, 10, 20].map(|shift| (self.position >> shift) & 0x3ff)
+	}
+}
+
+// Dispatch arguments over the fragments, followed by the fragment count
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct VoxelFragmentCounter {
+	pub workgroups: [u32; 3],
+	pub count: u32,
+}
+
+// Fragments past the capacity are dropped, but still counted
+pub struct VoxelFragmentList {
+	pub fragments: wgpu::Buffer,
+	pub counter: wgpu::Buffer,
+	// Indirect buffers can't be bound for storage in the same dispatch, so the arguments are copied here
+	pub indirect: wgpu::Buffer,
+	pub capacity: u32,
+}
+
+impl VoxelFragmentList {
+	pub fn new(device: &wgpu::Device, capacity: u32) -> Self {
+		let fragments = device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Voxel Fragment Buffer"),
+			size: capacity.max(1) as u64 * std::mem::size_of::<VoxelFragment>() as u64,
+			usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+
+		let counter = device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Voxel Fragment Counter Buffer"),
+			size: std::mem::size_of::<VoxelFragmentCounter>() as u64,
+			usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+
+		let indirect = device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Voxel Fragment Indirect Buffer"),
+			size: std::mem::size_of::<wgpu::util::DispatchIndirectArgs>() as u64,
+			usage: wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+
+		Self {
+			fragments,
+			counter,
+			indirect,
+			capacity,
+		}
+	}
+
+	pub fn encode_clear(&self, encoder: &mut wgpu::CommandEncoder) {
+		encoder.clear_buffer(&self.counter, 0, None);
+	}
+
+	// Replaces the fragments and count with those of a list of the same capacity
+	pub fn encode_copy_from(&self, encoder: &mut wgpu::CommandEncoder, source: &VoxelFragmentList) {
+		assert_eq!(self.capacity, source.capacity);
+
+		encoder.copy_buffer_to_buffer(&source.counter, 0, &self.counter, 0, self.counter.size());
+		encoder.copy_buffer_to_buffer(&source.fragments, 0, &self.fragments, 0, self.fragments.size());
+	}
+
+	// Blocks until every stored fragment was copied back, including what the queue is still writing
+	pub fn read(&self, renderer: &Renderer) -> Result<Vec<VoxelFragment>, wgpu::BufferAsyncError> {
+		let mut encoder = renderer
+			.device()
+			.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+				label: Some("Voxel Fragment Readback Encoder"),
+			});
+
+		let fragment_size = std::mem::size_of::<VoxelFragment>() as u64;
+		let counter = BufferReadback::new(
+			renderer.device(),
+			&mut encoder,
+			&self.counter,
+			std::mem::size_of::<VoxelFragmentCounter>() as u64,
+		);
+		let fragments = BufferReadback::new(
+			renderer.device(),
+			&mut encoder,
+			&self.fragments,
+			self.capacity.max(1) as u64 * fragment_size,
+		);
+		renderer.queue().submit(Some(encoder.finish()));
+
+		let counter: VoxelFragmentCounter = bytemuck::pod_read_unaligned(&counter.read(renderer.device())?);
+		let mut fragments: Vec<VoxelFragment> = bytemuck::pod_collect_to_vec(&fragments.read(renderer.device())?);
+		fragments.truncate(counter.count.min(self.capacity) as usize);
+
+		Ok(fragments)
+	}
+
+	// Once the dispatch arguments were written to the counter
+	pub fn encode_copy_indirect(&self, encoder: &mut wgpu::CommandEncoder) {
+		encoder.copy_buffer_to_buffer(
+			&self.counter,
+			0,
+			&self.indirect,
+			0,
+			std::mem::size_of::<wgpu::util::DispatchIndirectArgs>() as u64,
+		);
+	}
+}
+
+// Turns the fragment count into the arguments of VoxelFragmentList::indirect
+pub(crate) struct FragmentDispatchPreparer {
+	pipeline: wgpu::ComputePipeline,
+	bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl FragmentDispatchPreparer {
+	pub fn new(renderer: &Renderer) -> Self {
+		let shader = renderer
+			.device()
+			.create_shader_module(wgpu::ShaderModuleDescriptor {
+				label: Some("Voxel Fragment Dispatch shader"),
+				source: wgpu::ShaderSource::Wgsl(
+					format!(
+						"{}\n{}",
+						FRAGMENT_SHADER_PRELUDE,
+						include_str!("../shaders/voxel_fragment_dispatch.wgsl")
+					)
+					.into(),
+				),
+			});
+
+		let storage_entry = |binding| wgpu::BindGroupLayoutEntry {
+			binding,
+			visibility: wgpu::ShaderStages::COMPUTE,
+			ty: wgpu::BindingType::Buffer {
+				ty: wgpu::BufferBindingType::Storage { read_only: false },
+				has_dynamic_offset: false,
+				min_binding_size: None,
+			},
+			count: None,
+		};
+
+		let bind_group_layout =
+			renderer
+				.device()
+				.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+					label: Some("Voxel Fragment Dispatch BindGroup Layout"),
+					entries: &[storage_entry(0), storage_entry(1)],
+				});
+
+		let layout = renderer
+			.device()
+			.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+				label: Some("Voxel Fragment Dispatch Layout"),
+				bind_group_layouts: &[&bind_group_layout],
+				push_constant_ranges: &[],
+			});
+
+		let pipeline = renderer
+			.device()
+			.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+				label: Some("Voxel Fragment Dispatch pipeline"),
+				layout: Some(&layout),
+				module: &shader,
+				entry_point: "prepare_dispatch",
+			});
+
+		Self {
+			pipeline,
+			bind_group_layout,
+		}
+	}
+
+	pub fn encode(&self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, fragment_list: &VoxelFragmentList) {
+		let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+			label: Some("Voxel Fragment Dispatch Bind Group"),
+			layout: &self.bind_group_layout,
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: fragment_list.fragments.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 1,
+					resource: fragment_list.counter.as_entire_binding(),
+				},
+			],
+		});
+
+		{
+			let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+				label: Some("Voxel Fragment Dispatch pass"),
+				timestamp_writes: None,
+			});
+
+			compute_pass.set_pipeline(&self.pipeline);
+			compute_pass.set_bind_group(0, &bind_group, &[]);
+			compute_pass.dispatch_workgroups(1, 1, 1);
+		}
+
+		fragment_list.encode_copy_indirect(encoder);
+	}
+}
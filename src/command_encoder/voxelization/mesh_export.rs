@@ -0,0 +1,189 @@
+use std::io::{self, Write};
+
+use super::{Vertex, VoxelsMeshResource};
+use crate::{readback::BufferReadback, Renderer};
+
+// The quads a MeshifyPass built, copied back to the CPU
+pub struct VoxelMesh {
+	pub vertices: Vec<Vertex>,
+	pub indices: Vec<u32>,
+	// Quads that didn't fit the capacity of the MeshifyPass, the surface has holes when this isn't 0
+	pub dropped_quads: u32,
+}
+
+impl VoxelMesh {
+	// Blocks until the drawn part of the buffers was copied back, quads past the capacity are counted in dropped_quads
+	pub fn read(renderer: &Renderer, voxels_mesh: &VoxelsMeshResource) -> Result<Self, wgpu::BufferAsyncError> {
+		let (total_quad_count, index_count) = voxels_mesh.read_counts(renderer)?;
+		let quad_count = index_count as u64 / 6;
+		let dropped_quads = total_quad_count - quad_count as u32;
+		if quad_count == 0 {
+			return Ok(Self {
+				vertices: Vec::new(),
+				indices: Vec::new(),
+				dropped_quads,
+			});
+		}
+
+		let mut encoder = renderer
+			.device()
+			.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+				label: Some("Voxel Mesh Export Encoder"),
+			});
+		let vertices = BufferReadback::new(
+			renderer.device(),
+			&mut encoder,
+			&voxels_mesh.vertices,
+			quad_count * 4 * std::mem::size_of::<Vertex>() as u64,
+		);
+		let indices = BufferReadback::new(renderer.device(), &mut encoder, &voxels_mesh.indices, index_count as u64 * 4);
+		renderer.queue().submit(Some(encoder.finish()));
+
+		Ok(Self {
+			vertices: bytemuck::pod_collect_to_vec(&vertices.read(renderer.device())?),
+			indices: bytemuck::pod_collect_to_vec(&indices.read(renderer.device())?),
+			dropped_quads,
+		})
+	}
+
+	// Vertex colours follow the positions, which most tools read
+	pub fn write_obj(&self, writer: &mut impl Write) -> io::Result<()> {
+		writeln!(writer, "# {} voxel quads", self.indices.len() / 6)?;
+		for vertex in self.vertices.iter() {
+			let ([x, y, z], [r, g, b]) = (vertex.position, vertex.color);
+			writeln!(writer, "v {x} {y} {z} {r} {g} {b}")?;
+		}
+		for vertex in self.vertices.iter() {
+			let [x, y, z] = vertex.normal;
+			writeln!(writer, "vn {x} {y} {z}")?;
+		}
+		for triangle in self.indices.chunks_exact(3) {
+			let [a, b, c] = [triangle[0] + 1, triangle[1] + 1, triangle[2] + 1];
+			writeln!(writer, "f {a}//{a} {b}//{b} {c}//{c}")?;
+		}
+
+		Ok(())
+	}
+
+	pub fn write_ply(&self, writer: &mut impl Write) -> io::Result<()> {
+		write!(
+			writer,
+			"ply\nformat binary_little_endian 1.0\n\
+			element vertex {}\n\
+			property float x\nproperty float y\nproperty float z\n\
+			property float nx\nproperty float ny\nproperty float nz\n\
+			property uchar red\nproperty uchar green\nproperty uchar blue\n\
+			element face {}\n\
+			property list uchar uint vertex_indices\n\
+			end_header\n",
+			self.vertices.len(),
+			self.indices.len() / 3
+		)?;
+
+		for vertex in self.vertices.iter() {
+			writer.write_all(bytemuck::cast_slice(&vertex.position))?;
+			writer.write_all(bytemuck::cast_slice(&vertex.normal))?;
+			writer.write_all(&vertex.color.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8))?;
+		}
+		for triangle in self.indices.chunks_exact(3) {
+			writer.write_all(&[3])?;
+			writer.write_all(bytemuck::cast_slice(triangle))?;
+		}
+
+		Ok(())
+	}
+
+	// A single mesh with positions, normals, COLOR_0 and indices in one binary buffer
+	pub fn write_glb(&self, writer: &mut impl Write) -> io::Result<()> {
+		if self.indices.is_empty() {
+			return Err(io::Error::new(io::ErrorKind::InvalidInput, "glTF accessors can't be empty"));
+		}
+
+		let attribute = |field: fn(&Vertex) -> [f32; 3]| -> Vec<u8> {
+			self.vertices
+				.iter()
+				.flat_map(|vertex| bytemuck::cast::<[f32; 3], [u8; 12]>(field(vertex)))
+				.collect()
+		};
+		let views = [
+			attribute(|vertex| vertex.position),
+			attribute(|vertex| vertex.normal),
+			attribute(|vertex| vertex.color),
+			bytemuck::cast_slice(&self.indices).to_vec(),
+		];
+
+		let (min, max) = self.vertices.iter().fold(
+			([f32::MAX; 3], [f32::MIN; 3]),
+			|(min, max), vertex| {
+				(
+					[0, 1, 2].map(|i| min[i].min(vertex.position[i])),
+					[0, 1, 2].map(|i| max[i].max(vertex.position[i])),
+				)
+			},
+		);
+
+		let mut offset = 0;
+		let buffer_views = views
+			.iter()
+			.enumerate()
+			.map(|(i, view)| {
+				let target = if i == 3 { 34963 } else { 34962 };
+				let buffer_view = format!(
+					"{{\"buffer\":0,\"byteOffset\":{offset},\"byteLength\":{},\"target\":{target}}}",
+					view.len()
+				);
+				offset += view.len();
+				buffer_view
+			})
+			.collect::<Vec<_>>()
+			.join(",");
+
+		let vertex_count = self.vertices.len();
+		let json = format!(
+			"{{\"asset\":{{\"version\":\"2.0\",\"generator\":\"vct\"}},\
+			\"scene\":0,\"scenes\":[{{\"nodes\":[0]}}],\"nodes\":[{{\"mesh\":0}}],\
+			\"meshes\":[{{\"primitives\":[{{\"attributes\":{{\"POSITION\":0,\"NORMAL\":1,\"COLOR_0\":2}},\"indices\":3,\"mode\":4}}]}}],\
+			\"accessors\":[\
+			{{\"bufferView\":0,\"componentType\":5126,\"count\":{vertex_count},\"type\":\"VEC3\",\"min\":[{:?},{:?},{:?}],\"max\":[{:?},{:?},{:?}]}},\
+			{{\"bufferView\":1,\"componentType\":5126,\"count\":{vertex_count},\"type\":\"VEC3\"}},\
+			{{\"bufferView\":2,\"componentType\":5126,\"count\":{vertex_count},\"type\":\"VEC3\"}},\
+			{{\"bufferView\":3,\"componentType\":5125,\"count\":{},\"type\":\"SCALAR\"}}],\
+			\"bufferViews\":[{buffer_views}],\
+			\"buffers\":[{{\"byteLength\":{offset}}}]}}",
+			min[0],
+			min[1],
+			min[2],
+			max[0],
+			max[1],
+			max[2],
+			self.indices.len(),
+		);
+
+		// Chunks are padded to 4 bytes, JSON with spaces and the binary buffer with zeros
+		let json_length = json.len().next_multiple_of(4);
+		let bin_length = offset.next_multiple_of(4);
+		let total_length = 12 + 8 + json_length + 8 + bin_length;
+
+		writer.write_all(b"glTF")?;
+		writer.write_all(&2u32.to_le_bytes())?;
+		writer.write_all(&(total_length as u32).to_le_bytes())?;
+
+		writer.write_all(&(json_length as u32).to_le_bytes())?;
+		writer.write_all(b"JSON")?;
+		writer.write_all(json.as_bytes())?;
+		writer.write_all(&vec![b' '; json_length - json.len()])?;
+
+		writer.write_all(&(bin_length as u32).to_le_bytes())?;
+		writer.write_all(b"BIN\0")?;
+		for view in views.iter() {
+			writer.write_all(view)?;
+		}
+		writer.write_all(&vec![0; bin_length - offset])
+	}
+}
+
+impl VoxelsMeshResource {
+	pub fn read_mesh(&self, renderer: &Renderer) -> Result<VoxelMesh, wgpu::BufferAsyncError> {
+		VoxelMesh::read(renderer, self)
+	}
+}
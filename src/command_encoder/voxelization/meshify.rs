@@ -2,15 +2,19 @@ use crate::command_encoder::*;
 use wgpu::util::DeviceExt;
 use wgpu_helper::bind_group::{BindGroup, BindGroupType};
 
-use crate::Renderer;
+use super::VoxelChannel;
+
+use crate::{readback::BufferReadback, Renderer};
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
-	position: [f32; 3],
+	pub position: [f32; 3],
 	_pad1: u32,
-	color: [f32; 3],
+	pub color: [f32; 3],
 	_pad2: u32,
+	pub normal: [f32; 3],
+	_pad3: u32,
 }
 
 impl Vertex {
@@ -29,14 +33,129 @@ impl Vertex {
 					shader_location: 1,
 					format: wgpu::VertexFormat::Float32x3,
 				},
+				wgpu::VertexAttribute {
+					offset: std::mem::size_of::<[f32; 8]>() as u64,
+					shader_location: 2,
+					format: wgpu::VertexFormat::Float32x3,
+				},
 			],
 		}
 	}
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MeshifyMode {
+	// Every face of each voxel with an empty neighbour
+	#[default]
+	Cubes,
+	// Only faces with an empty neighbour in front of them
+	ExposedFaces,
+	// Exposed faces with rows of equal colour merged, and identical rows stacked into larger quads
+	Greedy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeshifySettings {
+	pub mode: MeshifyMode,
+	// Clamped to the coarsest level of the volume
+	pub mip_level: u32,
+	// Faces are still built from the occupied voxels, the channel only decides their colour
+	pub channel: VoxelChannel,
+}
+
+impl Default for MeshifySettings {
+	fn default() -> Self {
+		Self {
+			mode: MeshifyMode::default(),
+			mip_level: 1,
+			channel: VoxelChannel::default(),
+		}
+	}
+}
+
+impl MeshifySettings {
+	fn to_uniform(self) -> MeshifyUniform {
+		MeshifyUniform {
+			channel: self.channel as u32,
+			_padding: [0; 3],
+		}
+	}
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct MeshifyUniform {
+	channel: u32,
+	_padding: [u32; 3],
+}
+
+// Quads of the voxels at one mip level, filled on the GPU every time the MeshifyPass executes.
+// Each quad takes four vertices and six indices
 pub struct VoxelsMeshResource {
 	pub vertices: wgpu::Buffer,
 	pub indices: wgpu::Buffer,
+	// DrawIndexedIndirect arguments covering at most capacity quads
+	pub draw: wgpu::Buffer,
+	// Every quad, including those that didn't fit
+	pub count: wgpu::Buffer,
+	pub capacity: u32,
+}
+
+impl VoxelsMeshResource {
+	fn new(device: &wgpu::Device, capacity: u32) -> Self {
+		let vertices = device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Voxel Vertex Buffer"),
+			size: capacity as u64 * (std::mem::size_of::<Vertex>() * 4) as u64,
+			usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_SRC,
+			mapped_at_creation: false,
+		});
+
+		let indices = device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Voxel Index Buffer"),
+			size: capacity as u64 * (4 * 6) as u64,
+			usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_SRC,
+			mapped_at_creation: false,
+		});
+
+		let draw = device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Voxel Draw Buffer"),
+			size: std::mem::size_of::<wgpu::util::DrawIndexedIndirectArgs>() as u64,
+			usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_SRC,
+			mapped_at_creation: false,
+		});
+
+		let count = device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Voxel Count Buffer"),
+			size: std::mem::size_of::<i32>() as u64,
+			usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+
+		Self {
+			vertices,
+			indices,
+			draw,
+			count,
+			capacity,
+		}
+	}
+
+	// Blocks until the quad count and the index count of the draw were copied back, only meant for debugging
+	pub fn read_counts(&self, renderer: &Renderer) -> Result<(u32, u32), wgpu::BufferAsyncError> {
+		let mut encoder = renderer
+			.device()
+			.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+				label: Some("Voxel Mesh Readback Encoder"),
+			});
+		let count = BufferReadback::new(renderer.device(), &mut encoder, &self.count, self.count.size());
+		let draw = BufferReadback::new(renderer.device(), &mut encoder, &self.draw, self.draw.size());
+		renderer.queue().submit(Some(encoder.finish()));
+
+		let count: i32 = bytemuck::pod_read_unaligned(&count.read(renderer.device())?);
+		let draw: [u32; 5] = bytemuck::pod_read_unaligned(&draw.read(renderer.device())?);
+
+		Ok((count.max(0) as u32, draw[0]))
+	}
 }
 
 impl crate::Resource for VoxelsMeshResource {
@@ -44,54 +163,43 @@ impl crate::Resource for VoxelsMeshResource {
 }
 
 pub struct MeshifyPass {
-	empty_count_buffer: wgpu::Buffer,
-	count_buffer: wgpu::Buffer,
-	count_staging_buffer: wgpu::Buffer,
-	count_bind_group_layout: wgpu::BindGroupLayout,
-	count_pipeline: wgpu::ComputePipeline,
-	instance_bind_group_layout: wgpu::BindGroupLayout,
-	instance_pipeline: wgpu::ComputePipeline,
+	capacity: u32,
+	settings: MeshifySettings,
+	settings_buffer: wgpu::Buffer,
+	face_bind_group_layout: wgpu::BindGroupLayout,
+	cubes_pipeline: wgpu::ComputePipeline,
+	exposed_faces_pipeline: wgpu::ComputePipeline,
+	greedy_pipeline: wgpu::ComputePipeline,
+	draw_bind_group_layout: wgpu::BindGroupLayout,
+	draw_pipeline: wgpu::ComputePipeline,
 }
 
 impl MeshifyPass {
-	pub fn new(renderer: &Renderer) -> Self {
-		let count_shader = renderer
-			.device()
-			.create_shader_module(wgpu::ShaderModuleDescriptor {
-				label: Some("Count Pass Shader"),
-				source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!(
-					"../shaders/count_voxels.wgsl"
-				))),
-			});
+	// Enough for the surface of most scenes at a 512 voxel resolution
+	pub const DEFAULT_CAPACITY: u32 = 1 << 19;
 
-		let empty_count_buffer =
-			renderer
-				.device()
-				.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-					label: Some("Count Clear Buffer"),
-					contents: bytemuck::cast_slice(&[0_i32]),
-					usage: wgpu::BufferUsages::COPY_SRC,
-				});
+	pub fn new(renderer: &Renderer) -> Self {
+		Self::with_capacity(renderer, Self::DEFAULT_CAPACITY)
+	}
 
-		let count_buffer =
+	// Capacity is the number of quads the preallocated vertex and index buffers hold
+	pub fn with_capacity(renderer: &Renderer, capacity: u32) -> Self {
+		let face_shader =
 			renderer
 				.device()
-				.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-					label: Some("Counter buffer"),
-					contents: bytemuck::cast_slice(&[0_i32]),
-					usage: wgpu::BufferUsages::COPY_DST
-						| wgpu::BufferUsages::COPY_SRC
-						| wgpu::BufferUsages::STORAGE,
+				.create_shader_module(wgpu::ShaderModuleDescriptor {
+					label: Some("Voxel Face Shader"),
+					source: wgpu::ShaderSource::Wgsl(
+						format!(
+							"{}\n{}",
+							include_str!("../shaders/voxel_debug_channel.wgsl"),
+							include_str!("../shaders/voxel_faces.wgsl")
+						)
+						.into(),
+					),
 				});
 
-		let count_staging_buffer = renderer.device().create_buffer(&wgpu::BufferDescriptor {
-			label: Some("Counter Staging Buffer"),
-			size: std::mem::size_of::<i32>() as u64,
-			usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
-			mapped_at_creation: false,
-		});
-
-		let count_bind_group_layout =
+		let face_bind_group_layout =
 			renderer
 				.device()
 				.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -117,56 +225,18 @@ impl MeshifyPass {
 							},
 							count: None,
 						},
-					],
-				});
-
-		let count_pipeline_layout =
-			renderer
-				.device()
-				.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-					label: None,
-					bind_group_layouts: &[&count_bind_group_layout],
-					push_constant_ranges: &[],
-				});
-
-		let count_pipeline =
-			renderer
-				.device()
-				.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-					label: Some("Voxel Count Pass"),
-					layout: Some(&count_pipeline_layout),
-					module: &count_shader,
-					entry_point: "get_voxel_sum",
-				});
-
-		let instance_shader =
-			renderer
-				.device()
-				.create_shader_module(wgpu::ShaderModuleDescriptor {
-					label: Some("Voxel Instance Shader"),
-					source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!(
-						"../shaders/voxel_instancing.wgsl"
-					))),
-				});
-
-		let instance_bind_group_layout =
-			renderer
-				.device()
-				.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-					label: None,
-					entries: &[
 						wgpu::BindGroupLayoutEntry {
-							binding: 0,
+							binding: 2,
 							visibility: wgpu::ShaderStages::COMPUTE,
-							ty: wgpu::BindingType::Texture {
-								sample_type: wgpu::TextureSampleType::Float { filterable: false },
-								view_dimension: wgpu::TextureViewDimension::D3,
-								multisampled: false,
+							ty: wgpu::BindingType::Buffer {
+								ty: wgpu::BufferBindingType::Storage { read_only: false },
+								has_dynamic_offset: false,
+								min_binding_size: None,
 							},
 							count: None,
 						},
 						wgpu::BindGroupLayoutEntry {
-							binding: 1,
+							binding: 3,
 							visibility: wgpu::ShaderStages::COMPUTE,
 							ty: wgpu::BindingType::Buffer {
 								ty: wgpu::BufferBindingType::Storage { read_only: false },
@@ -176,20 +246,30 @@ impl MeshifyPass {
 							count: None,
 						},
 						wgpu::BindGroupLayoutEntry {
-							binding: 2,
+							binding: 4,
 							visibility: wgpu::ShaderStages::COMPUTE,
 							ty: wgpu::BindingType::Buffer {
-								ty: wgpu::BufferBindingType::Storage { read_only: false },
+								ty: wgpu::BufferBindingType::Uniform,
 								has_dynamic_offset: false,
 								min_binding_size: None,
 							},
 							count: None,
 						},
 						wgpu::BindGroupLayoutEntry {
-							binding: 3,
+							binding: 5,
+							visibility: wgpu::ShaderStages::COMPUTE,
+							ty: wgpu::BindingType::Texture {
+								sample_type: wgpu::TextureSampleType::Float { filterable: false },
+								view_dimension: wgpu::TextureViewDimension::D3,
+								multisampled: false,
+							},
+							count: None,
+						},
+						wgpu::BindGroupLayoutEntry {
+							binding: 6,
 							visibility: wgpu::ShaderStages::COMPUTE,
 							ty: wgpu::BindingType::Buffer {
-								ty: wgpu::BufferBindingType::Storage { read_only: false },
+								ty: wgpu::BufferBindingType::Uniform,
 								has_dynamic_offset: false,
 								min_binding_size: None,
 							},
@@ -198,80 +278,160 @@ impl MeshifyPass {
 					],
 				});
 
-		let instance_pipeline_layout =
+		let face_pipeline_layout =
 			renderer
 				.device()
 				.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
 					label: None,
-					bind_group_layouts: &[&instance_bind_group_layout],
+					bind_group_layouts: &[&face_bind_group_layout],
 					push_constant_ranges: &[],
 				});
 
-		let instance_pipeline =
+		let new_face_pipeline = |entry_point| {
 			renderer
 				.device()
 				.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-					label: Some("Voxel Instancing Pass"),
-					layout: Some(&instance_pipeline_layout),
-					module: &instance_shader,
+					label: Some("Voxel Face Pass"),
+					layout: Some(&face_pipeline_layout),
+					module: &face_shader,
+					entry_point,
+				})
+		};
+
+		let draw_shader = renderer
+			.device()
+			.create_shader_module(wgpu::ShaderModuleDescriptor {
+				label: Some("Voxel Draw Shader"),
+				source: wgpu::ShaderSource::Wgsl(
+					format!(
+						"{}\n{}",
+						include_str!("../shaders/_structs.wgsl"),
+						include_str!("../shaders/meshify_draw.wgsl")
+					)
+					.into(),
+				),
+			});
+
+		let storage_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+			binding,
+			visibility: wgpu::ShaderStages::COMPUTE,
+			ty: wgpu::BindingType::Buffer {
+				ty: wgpu::BufferBindingType::Storage { read_only },
+				has_dynamic_offset: false,
+				min_binding_size: None,
+			},
+			count: None,
+		};
+
+		let draw_bind_group_layout =
+			renderer
+				.device()
+				.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+					label: Some("Voxel Draw Bind Group Layout"),
+					entries: &[storage_entry(0, false), storage_entry(1, false), storage_entry(2, true)],
+				});
+
+		let draw_pipeline_layout =
+			renderer
+				.device()
+				.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+					label: None,
+					bind_group_layouts: &[&draw_bind_group_layout],
+					push_constant_ranges: &[],
+				});
+
+		let draw_pipeline =
+			renderer
+				.device()
+				.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+					label: Some("Voxel Draw Pass"),
+					layout: Some(&draw_pipeline_layout),
+					module: &draw_shader,
 					entry_point: "main",
 				});
 
+		let settings = MeshifySettings::default();
+		let settings_buffer = renderer
+			.device()
+			.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+				label: Some("Meshify Settings Buffer"),
+				contents: bytemuck::cast_slice(&[settings.to_uniform()]),
+				usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			});
+
 		Self {
-			empty_count_buffer,
-			count_buffer,
-			count_staging_buffer,
-			count_bind_group_layout,
-			count_pipeline,
-
-			instance_bind_group_layout,
-			instance_pipeline,
+			capacity: capacity.max(1),
+			settings,
+			settings_buffer,
+			face_bind_group_layout,
+			cubes_pipeline: new_face_pipeline("cubes"),
+			exposed_faces_pipeline: new_face_pipeline("exposed_faces"),
+			greedy_pipeline: new_face_pipeline("greedy_faces"),
+			draw_bind_group_layout,
+			draw_pipeline,
 		}
 	}
-}
 
-impl RenderPassTrait for MeshifyPass {
-	fn execute<'manager>(
+	pub fn settings(&self) -> MeshifySettings {
+		self.settings
+	}
+
+	pub fn set_settings(&mut self, settings: MeshifySettings) {
+		self.settings = settings;
+	}
+
+	// Records the meshing into an encoder shared with other work, see VoxelDebugPass
+	pub(crate) fn encode<'manager>(
 		&mut self,
 		command_encoder: &'manager CommandEncoder,
 		global_resources: &mut crate::ResourceManagerHandle<'manager>,
-	) -> Option<wgpu::CommandBuffer> {
-		let mut encoder =
-			command_encoder
-				.device()
-				.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-					label: Some("Count Encoder"),
-				});
+		encoder: &mut wgpu::CommandEncoder,
+	) {
+		let capacity = global_resources
+			.get_resource::<VoxelsMeshResource>()
+			.map(|voxels_mesh| voxels_mesh.capacity);
+		if capacity != Some(self.capacity) {
+			global_resources.insert_resource(VoxelsMeshResource::new(command_encoder.device(), self.capacity));
+		}
 
 		let voxels_resource = global_resources
 			.get_resource::<super::VoxelsResource>()
 			.unwrap();
+		let voxels_mesh = global_resources
+			.get_resource::<VoxelsMeshResource>()
+			.unwrap();
 
-		// Clear count buffer.
-		encoder.copy_buffer_to_buffer(
-			&self.empty_count_buffer,
+		command_encoder.queue().write_buffer(
+			&self.settings_buffer,
 			0,
-			&self.count_buffer,
-			0,
-			std::mem::size_of::<i32>() as u64,
+			bytemuck::cast_slice(&[self.settings.to_uniform()]),
 		);
 
-		let view = voxels_resource
-			.color
-			.texture
-			.create_view(&wgpu::TextureViewDescriptor {
-				label: Some("Voxel Mipmapping output"),
-				base_mip_level: 1,
+		encoder.clear_buffer(&voxels_mesh.count, 0, None);
+
+		let mip_level = self.settings.mip_level.min(voxels_resource.color.texture.mip_level_count() - 1);
+		let mip_view = |texture: &wgpu::Texture| {
+			texture.create_view(&wgpu::TextureViewDescriptor {
+				label: Some("Voxel Meshify View"),
+				base_mip_level: mip_level,
 				mip_level_count: Some(1),
 				..Default::default()
-			});
+			})
+		};
+		let view = mip_view(&voxels_resource.color.texture);
+		let channel_view = mip_view(&self.settings.channel.texture(&voxels_resource).texture);
+		let size = voxels_resource
+			.color
+			.texture
+			.size()
+			.mip_level_size(mip_level, wgpu::TextureDimension::D3);
 
-		let count_bind_group =
+		let face_bind_group =
 			command_encoder
 				.device()
 				.create_bind_group(&wgpu::BindGroupDescriptor {
-					label: None,
-					layout: &self.count_bind_group_layout,
+					label: Some("Voxel Face Bindgroup"),
+					layout: &self.face_bind_group_layout,
 					entries: &[
 						wgpu::BindGroupEntry {
 							binding: 0,
@@ -279,61 +439,80 @@ impl RenderPassTrait for MeshifyPass {
 						},
 						wgpu::BindGroupEntry {
 							binding: 1,
-							resource: self.count_buffer.as_entire_binding(),
+							resource: voxels_mesh.vertices.as_entire_binding(),
+						},
+						wgpu::BindGroupEntry {
+							binding: 2,
+							resource: voxels_mesh.indices.as_entire_binding(),
+						},
+						wgpu::BindGroupEntry {
+							binding: 3,
+							resource: voxels_mesh.count.as_entire_binding(),
+						},
+						wgpu::BindGroupEntry {
+							binding: 4,
+							resource: voxels_resource.volume_buffer.as_entire_binding(),
+						},
+						wgpu::BindGroupEntry {
+							binding: 5,
+							resource: wgpu::BindingResource::TextureView(&channel_view),
+						},
+						wgpu::BindGroupEntry {
+							binding: 6,
+							resource: self.settings_buffer.as_entire_binding(),
+						},
+					],
+				});
+
+		let draw_bind_group =
+			command_encoder
+				.device()
+				.create_bind_group(&wgpu::BindGroupDescriptor {
+					label: Some("Voxel Draw Bindgroup"),
+					layout: &self.draw_bind_group_layout,
+					entries: &[
+						wgpu::BindGroupEntry {
+							binding: 0,
+							resource: voxels_mesh.count.as_entire_binding(),
+						},
+						wgpu::BindGroupEntry {
+							binding: 1,
+							resource: voxels_mesh.draw.as_entire_binding(),
+						},
+						wgpu::BindGroupEntry {
+							binding: 2,
+							resource: voxels_mesh.indices.as_entire_binding(),
 						},
 					],
 				});
 
 		{
-			let mut counter_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-				label: Some("Count pass"),
+			let mut face_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+				label: Some("Voxel Face pass"),
 				timestamp_writes: None,
 			});
 
-			counter_pass.set_bind_group(0, &count_bind_group, &[]);
-			counter_pass.set_pipeline(&self.count_pipeline);
-			counter_pass.dispatch_workgroups(
-				voxels_resource.color.texture.size().width,
-				voxels_resource.color.texture.size().height,
-				voxels_resource.color.texture.size().depth_or_array_layers,
-			);
-		}
-
-		encoder.copy_buffer_to_buffer(
-			&self.count_buffer,
-			0,
-			&self.count_staging_buffer,
-			0,
-			std::mem::size_of::<i32>() as u64,
-		);
-
-		command_encoder.queue().submit(Some(encoder.finish()));
-
-		let (tx, rx) = std::sync::mpsc::channel::<Result<(), wgpu::BufferAsyncError>>();
-
-		self.count_staging_buffer
-			.slice(..)
-			.map_async(wgpu::MapMode::Read, move |res| {
-				tx.send(res).unwrap();
+			face_pass.set_bind_group(0, &face_bind_group, &[]);
+			face_pass.set_pipeline(match self.settings.mode {
+				MeshifyMode::Cubes => &self.cubes_pipeline,
+				MeshifyMode::ExposedFaces => &self.exposed_faces_pipeline,
+				MeshifyMode::Greedy => &self.greedy_pipeline,
 			});
+			face_pass.dispatch_workgroups(size.width, size.height, size.depth_or_array_layers);
 
-		let count;
-		'main: loop {
-			command_encoder.device().poll(wgpu::Maintain::Wait);
-
-			match rx.try_recv() {
-				Ok(_) => {
-					let view = self.count_staging_buffer.slice(..).get_mapped_range();
-
-					count = *unsafe { &*(view.as_ptr() as *const i32) };
-					break 'main;
-				}
-				Err(_) => {}
-			}
+			face_pass.set_bind_group(0, &draw_bind_group, &[]);
+			face_pass.set_pipeline(&self.draw_pipeline);
+			face_pass.dispatch_workgroups(1, 1, 1);
 		}
+	}
+}
 
-		self.count_staging_buffer.unmap();
-
+impl RenderPassTrait for MeshifyPass {
+	fn execute<'manager>(
+		&mut self,
+		command_encoder: &'manager CommandEncoder,
+		global_resources: &mut crate::ResourceManagerHandle<'manager>,
+	) -> Option<wgpu::CommandBuffer> {
 		let mut encoder =
 			command_encoder
 				.device()
@@ -341,92 +520,9 @@ impl RenderPassTrait for MeshifyPass {
 					label: Some("Meshify Encoder"),
 				});
 
-		// Clear count buffer.
-		encoder.copy_buffer_to_buffer(
-			&self.empty_count_buffer,
-			0,
-			&self.count_buffer,
-			0,
-			std::mem::size_of::<i32>() as u64,
-		);
-
-		if count <= 0 {
-			return None;
-		}
-
-		let vertex_buffer = command_encoder
-			.device()
-			.create_buffer(&wgpu::BufferDescriptor {
-				label: Some("Voxel Vertex Buffer"),
-				size: count as u64 * (std::mem::size_of::<Vertex>() * 8) as u64,
-				usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX,
-				mapped_at_creation: false,
-			});
-
-		let index_buffer = command_encoder
-			.device()
-			.create_buffer(&wgpu::BufferDescriptor {
-				label: Some("Voxel Index Buffer"),
-				size: count as u64 * (4 * 36) as u64,
-				usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDEX,
-				mapped_at_creation: false,
-			});
-
-		{
-			let bind_group =
-				command_encoder
-					.device()
-					.create_bind_group(&wgpu::BindGroupDescriptor {
-						label: Some("Voxel Instancing Bindgroup"),
-						layout: &self.instance_bind_group_layout,
-						entries: &[
-							wgpu::BindGroupEntry {
-								binding: 0,
-								resource: wgpu::BindingResource::TextureView(&view),
-							},
-							wgpu::BindGroupEntry {
-								binding: 1,
-								resource: vertex_buffer.as_entire_binding(),
-							},
-							wgpu::BindGroupEntry {
-								binding: 2,
-								resource: index_buffer.as_entire_binding(),
-							},
-							wgpu::BindGroupEntry {
-								binding: 3,
-								resource: self.count_buffer.as_entire_binding(),
-							},
-						],
-					});
+		self.encode(command_encoder, global_resources, &mut encoder);
 
-			let mut instance_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-				label: Some("Voxel Instancing pass"),
-				timestamp_writes: None,
-			});
-
-			instance_pass.set_bind_group(0, &bind_group, &[]);
-			instance_pass.set_pipeline(&self.instance_pipeline);
-			instance_pass.dispatch_workgroups(
-				voxels_resource.color.texture.size().width,
-				voxels_resource.color.texture.size().height,
-				voxels_resource.color.texture.size().depth_or_array_layers,
-			);
-		}
-
-		command_encoder
-			.queue()
-			.submit(std::iter::once(encoder.finish()));
-
-		let voxel_mesh_resource = VoxelsMeshResource {
-			vertices: vertex_buffer,
-			indices: index_buffer,
-		};
-
-		drop(voxels_resource);
-
-		global_resources.insert_resource(voxel_mesh_resource);
-
-		None
+		Some(encoder.finish())
 	}
 }
 
@@ -502,20 +598,18 @@ impl RenderMeshifyPass {
 
 		Self { render_pipeline }
 	}
-}
 
-impl RenderPassTrait for RenderMeshifyPass {
-	fn execute<'manager>(
+	pub(crate) fn encode<'manager>(
 		&mut self,
 		command_encoder: &'manager CommandEncoder,
 		global_resources: &mut crate::ResourceManagerHandle<'manager>,
-	) -> Option<wgpu::CommandBuffer> {
+		encoder: &mut wgpu::CommandEncoder,
+	) {
 		if global_resources
 			.get_resource::<VoxelsMeshResource>()
 			.is_none()
 		{
-			println!("No Voxels Mesh... Returning");
-			return None;
+			return;
 		}
 
 		let view = command_encoder.get_surface_texture_view();
@@ -540,10 +634,6 @@ impl RenderPassTrait for RenderMeshifyPass {
 				.unwrap()
 		};
 
-		let mut encoder = command_encoder
-			.device()
-			.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-
 		let voxels_mesh = global_resources
 			.get_resource::<VoxelsMeshResource>()
 			.unwrap();
@@ -590,8 +680,22 @@ impl RenderPassTrait for RenderMeshifyPass {
 			render_pass.set_vertex_buffer(0, voxels_mesh.vertices.slice(..));
 			render_pass.set_index_buffer(voxels_mesh.indices.slice(..), wgpu::IndexFormat::Uint32);
 
-			render_pass.draw_indexed(0..(voxels_mesh.indices.size() as u32) / 4, 0, 0..1);
+			render_pass.draw_indexed_indirect(&voxels_mesh.draw, 0);
 		}
+	}
+}
+
+impl RenderPassTrait for RenderMeshifyPass {
+	fn execute<'manager>(
+		&mut self,
+		command_encoder: &'manager CommandEncoder,
+		global_resources: &mut crate::ResourceManagerHandle<'manager>,
+	) -> Option<wgpu::CommandBuffer> {
+		let mut encoder = command_encoder
+			.device()
+			.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+		self.encode(command_encoder, global_resources, &mut encoder);
 
 		Some(encoder.finish())
 	}
@@ -1,188 +1,395 @@
+use std::collections::HashMap;
+
 use crate::Renderer;
 use wgpu::core::command;
-use wgpu_helper::bind_group::BindGroupType;
+use wgpu::util::DeviceExt;
 
 mod meshify;
 pub use meshify::*;
+mod mesh_export;
+pub use mesh_export::*;
+mod radiance;
+pub use radiance::*;
+mod voxelizer;
+use voxelizer::*;
+mod clipmap;
+pub use clipmap::*;
+mod fragments;
+pub use fragments::*;
+mod svo;
+pub use svo::*;
+mod export;
+pub use export::*;
+mod cache;
+pub use cache::*;
+mod debug_view;
+pub use debug_view::*;
 
 use super::CommandEncoder;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VoxelVolumeDescriptor {
+	pub min: glm::Vec3,
+	pub max: glm::Vec3,
+	pub resolution: u32,
+}
+
+impl Default for VoxelVolumeDescriptor {
+	fn default() -> Self {
+		Self {
+			min: glm::vec3(-25.0, -25.0, -25.0),
+			max: glm::vec3(25.0, 25.0, 25.0),
+			resolution: 512,
+		}
+	}
+}
+
+impl VoxelVolumeDescriptor {
+	// Fits a cubic volume around every loaded mesh, with a voxel of padding on each side
+	pub fn fit_to_meshes(renderer: &Renderer, resolution: u32) -> Option<Self> {
+		let bounds = renderer
+			.renderer
+			.meshes
+			.iter()
+			.map(|mesh| mesh.bounds)
+			.reduce(|a, b| a.union(&b))?;
+
+		let center = (bounds.min + bounds.max) * 0.5;
+		let extent = glm::comp_max(&(bounds.max - bounds.min)).max(f32::EPSILON);
+		let voxel_size = extent / (resolution.max(3) - 2) as f32;
+		let half_width = glm::vec3(1.0, 1.0, 1.0) * (voxel_size * resolution as f32 * 0.5);
+
+		Some(Self {
+			min: center - half_width,
+			max: center + half_width,
+			resolution,
+		})
+	}
+
+	pub fn size(&self) -> glm::Vec3 {
+		self.max - self.min
+	}
+
+	pub fn voxel_size(&self) -> f32 {
+		glm::comp_max(&self.size()) / self.resolution as f32
+	}
+
+	pub fn extent(&self) -> wgpu::Extent3d {
+		wgpu::Extent3d {
+			width: self.resolution,
+			height: self.resolution,
+			depth_or_array_layers: self.resolution,
+		}
+	}
+
+	// The voxels touching bounds plus one voxel of padding, None when they lie outside the volume
+	pub(crate) fn region_of(&self, bounds: &crate::mesh::Aabb) -> Option<([i32; 3], [i32; 3])> {
+		let voxel_size = self.size() / self.resolution as f32;
+		let to_grid = |point: &glm::Vec3, i: usize| ((point[i] - self.min[i]) / voxel_size[i]).floor() as i32;
+
+		let resolution = self.resolution as i32;
+		let min = [0, 1, 2].map(|i| (to_grid(&bounds.min, i) - 1).max(0));
+		let max = [0, 1, 2].map(|i| (to_grid(&bounds.max, i) + 2).min(resolution));
+
+		(0..3).all(|i| min[i] < max[i]).then_some((min, max))
+	}
+
+	pub(crate) fn region_bounds(&self, min: [i32; 3], max: [i32; 3]) -> crate::mesh::Aabb {
+		let voxel_size = self.size() / self.resolution as f32;
+		let to_world = |grid: [i32; 3]| self.min + glm::vec3(grid[0] as f32, grid[1] as f32, grid[2] as f32).component_mul(&voxel_size);
+
+		crate::mesh::Aabb {
+			min: to_world(min),
+			max: to_world(max),
+		}
+	}
+
+	pub fn to_uniform(self) -> VoxelVolumeUniform {
+		VoxelVolumeUniform {
+			min: self.min.into(),
+			resolution: self.resolution,
+			size: self.size().into(),
+			voxel_size: self.voxel_size(),
+			mip_level_count: self.extent().max_mips(wgpu::TextureDimension::D3),
+			anisotropic: 0,
+			_padding: [0; 2],
+		}
+	}
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct VoxelVolumeUniform {
+	min: [f32; 3],
+	resolution: u32,
+	size: [f32; 3],
+	voxel_size: f32,
+	mip_level_count: u32,
+	anisotropic: u32,
+	_padding: [u32; 2],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VoxelMipMode {
+	// Plain box filtered mips of the radiance volume
+	#[default]
+	Isotropic,
+	// Coarser levels are stored in six directional volumes, see VoxelsResource::anisotropic_radiance
+	Anisotropic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VoxelWriteMode {
+	// Whichever triangle writes a voxel last decides its colour
+	#[default]
+	LastWriter,
+	// Every colour written to a voxel is averaged with compare-and-swap atomics, so backends lacking them can't use it.
+	// Averaged voxels are opaque, and the average is kept at 8 bits per channel
+	Average,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VoxelizationMethod {
+	// One compute invocation walks the edges and scanlines of each triangle
+	#[default]
+	Compute,
+	// Triangles are rasterized along their dominant axis at voxel resolution, much faster for large triangles
+	Rasterization,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VoxelizationSettings {
+	// The first bounce is the injected direct light, every further bounce cone traces the previous one
+	pub bounce_count: u32,
+	pub mip_mode: VoxelMipMode,
+	// Also appends every voxelized point to VoxelsResource::fragments, holding up to this many.
+	// The VoxelizationPass panics when it's set for a volume finer than MAX_FRAGMENT_RESOLUTION
+	pub fragment_capacity: Option<u32>,
+	pub write_mode: VoxelWriteMode,
+	pub method: VoxelizationMethod,
+	// Writes the colour, normal, emissive and material volumes. Without them only the fragment list is filled,
+	// which is all the SparseVoxelOctreePass reads, and the RadianceInjectionPass leaves the dense radiance alone
+	pub dense_volume: bool,
+}
+
+impl Default for VoxelizationSettings {
+	fn default() -> Self {
+		Self {
+			bounce_count: 1,
+			mip_mode: VoxelMipMode::default(),
+			fragment_capacity: None,
+			write_mode: VoxelWriteMode::default(),
+			method: VoxelizationMethod::default(),
+			dense_volume: true,
+		}
+	}
+}
+
 pub struct VoxelsResource {
 	pub color: crate::mesh::Texture,
+	pub normal: crate::mesh::Texture,
+	pub emissive: crate::mesh::Texture,
+	// Metallic in red and roughness in green
+	pub material: crate::mesh::Texture,
+	// Lit voxels written by the RadianceInjectionPass, alpha holds the opacity
+	pub radiance: crate::mesh::Texture,
+	// Half resolution radiance pre-integrated towards +X, -X, +Y, -Y, +Z and -Z, only kept in VoxelMipMode::Anisotropic
+	pub anisotropic_radiance: Option<[crate::mesh::Texture; 6]>,
+	// Every point written by the voxelizer, several may share a voxel. Only kept with VoxelizationSettings::fragment_capacity
+	pub fragments: Option<VoxelFragmentList>,
 	pub size: wgpu::Extent3d,
+	pub volume: VoxelVolumeDescriptor,
+	pub volume_buffer: wgpu::Buffer,
+	pub settings: VoxelizationSettings,
 }
 
 impl crate::Resource for VoxelsResource {
 	fn updated_settings(&mut self, _renderer: &Renderer) {}
 }
 
+// Static meshes voxelized on their own, copied under the dynamic meshes every frame.
+// The textures are only allocated once a dynamic mesh shows up, until then every mesh is voxelized straight into the volumes
+struct StaticVoxelCache {
+	// Color, normal, emissive and material at mip 0
+	textures: Option<[wgpu::Texture; 4]>,
+	fragments: Option<VoxelFragmentList>,
+	bind_group: Option<wgpu::BindGroup>,
+	// Method, write mode, fragment capacity and whether the dense volume was written when the cache was voxelized
+	key: Option<(VoxelizationMethod, VoxelWriteMode, Option<u32>, bool)>,
+	region_buffer: wgpu::Buffer,
+}
+
+impl StaticVoxelCache {
+	fn new(device: &wgpu::Device) -> Self {
+		let region_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Static Voxelization Region Buffer"),
+			size: std::mem::size_of::<VoxelizationRegionUniform>() as u64,
+			usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+
+		Self {
+			textures: None,
+			fragments: None,
+			bind_group: None,
+			key: None,
+			region_buffer,
+		}
+	}
+
+	// The static meshes are voxelized into the new textures on the next execute
+	fn allocate(&mut self, device: &wgpu::Device, size: wgpu::Extent3d) {
+		let new_texture = |format| {
+			device.create_texture(&wgpu::TextureDescriptor {
+				label: Some("Static Voxel Texture"),
+				size,
+				mip_level_count: 1,
+				sample_count: 1,
+				dimension: wgpu::TextureDimension::D3,
+				format,
+				usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST,
+				view_formats: &[],
+			})
+		};
+
+		self.textures = Some([
+			new_texture(wgpu::TextureFormat::Rgba8Unorm),
+			new_texture(wgpu::TextureFormat::Rgba8Snorm),
+			new_texture(wgpu::TextureFormat::Rgba8Unorm),
+			new_texture(wgpu::TextureFormat::Rgba8Unorm),
+		]);
+		self.bind_group = None;
+		self.key = None;
+	}
+}
+
 pub struct VoxelizationPass {
-	voxelizer_pipeline: wgpu::ComputePipeline,
-	voxels_bind_group_layout: wgpu::BindGroupLayout,
+	voxelizer: Voxelizer,
+	region_buffer: wgpu::Buffer,
 
-	mip_mapping_pipeline: wgpu::ComputePipeline,
-	mip_map_bind_group_layout: wgpu::BindGroupLayout,
+	mip_mapper: VoxelMipMapper,
+	normal_mip_mapper: VoxelMipMapper,
+	dispatch_preparer: FragmentDispatchPreparer,
+	// Only allocated for VoxelWriteMode::Average, sized to the largest region voxelized so far
+	color_accumulator: Option<wgpu::Buffer>,
+	// Rebuilt whenever the fragment list or the colour accumulator is reallocated
+	voxels_bind_group: Option<wgpu::BindGroup>,
+	static_cache: StaticVoxelCache,
+	// World bounds, staticness and transform generation of every mesh when it was last voxelized,
+	// moved meshes only re-voxelize around these
+	voxelized_bounds: HashMap<crate::Id, (crate::mesh::Aabb, bool, u32)>,
+	dirty_region: Option<([i32; 3], [i32; 3])>,
+	settings: VoxelizationSettings,
 }
 
 impl VoxelizationPass {
-	pub fn new(renderer: &Renderer) -> Self {
-		let size = wgpu::Extent3d {
-			width: 512,
-			height: 512,
-			depth_or_array_layers: 512,
-		};
+	pub fn new(renderer: &Renderer, volume: &VoxelVolumeDescriptor) -> Self {
+		let size = volume.extent();
 
-		let sampler_descriptor = wgpu::SamplerDescriptor {
-			address_mode_u: wgpu::AddressMode::ClampToEdge,
-			address_mode_v: wgpu::AddressMode::ClampToEdge,
-			address_mode_w: wgpu::AddressMode::ClampToEdge,
-			mag_filter: wgpu::FilterMode::Nearest,
-			min_filter: wgpu::FilterMode::Nearest,
-			mipmap_filter: wgpu::FilterMode::Nearest,
-			compare: None,
-			lod_min_clamp: 0.0,
-			lod_max_clamp: 100.0,
-			..Default::default()
-		};
+		let voxel_color = new_voxel_texture(renderer.device(), size, "Voxel Color", wgpu::TextureFormat::Rgba8Unorm);
+		let voxel_normal = new_voxel_texture(renderer.device(), size, "Voxel Normal", wgpu::TextureFormat::Rgba8Snorm);
+		let voxel_emissive = new_voxel_texture(renderer.device(), size, "Voxel Emissive", wgpu::TextureFormat::Rgba8Unorm);
+		let voxel_material = new_voxel_texture(renderer.device(), size, "Voxel Material", wgpu::TextureFormat::Rgba8Unorm);
+		let voxel_radiance = new_voxel_texture(renderer.device(), size, "Voxel Radiance", wgpu::TextureFormat::Rgba8Unorm);
 
-		let mip_level_count = size.max_mips(wgpu::TextureDimension::D3);
+		let volume_buffer = renderer
+			.device()
+			.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+				label: Some("Voxel Volume Buffer"),
+				contents: bytemuck::cast_slice(&[volume.to_uniform()]),
+				usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			});
 
-		let voxel_color_texture = renderer.device().create_texture(&wgpu::TextureDescriptor {
-			label: Some("Voxel Color Texture"),
+		let voxels_resource = VoxelsResource {
+			color: voxel_color,
+			normal: voxel_normal,
+			emissive: voxel_emissive,
+			material: voxel_material,
+			radiance: voxel_radiance,
+			anisotropic_radiance: None,
+			fragments: None,
 			size,
-			mip_level_count,
-			sample_count: 1,
-			dimension: wgpu::TextureDimension::D3,
-			format: wgpu::TextureFormat::Rgba8Unorm,
-			usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
-			view_formats: &[],
-		});
-
-		let voxel_color_view = voxel_color_texture.create_view(&wgpu::TextureViewDescriptor {
-			label: Some("Voxel Color View"),
-			format: Some(wgpu::TextureFormat::Rgba8Unorm),
-			dimension: Some(wgpu::TextureViewDimension::D3),
-			..Default::default()
-		});
-		let voxel_color_sampler = renderer.device().create_sampler(&sampler_descriptor);
-
-		let voxel_color = crate::mesh::Texture {
-			texture: voxel_color_texture,
-			view: voxel_color_view,
-			sampler: voxel_color_sampler,
+			volume: *volume,
+			volume_buffer,
+			settings: VoxelizationSettings::default(),
 		};
 
-		let voxels_resource = VoxelsResource { color: voxel_color, size, };
-
 		renderer.insert_resource(voxels_resource);
 
-		let voxelizer_shader =
-			renderer
-				.device()
-				.create_shader_module(wgpu::ShaderModuleDescriptor {
-					label: Some("Voxelizer shader"),
-					source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!(
-						"../shaders/voxelization.wgsl"
-					))),
-				});
+		let resolution = volume.resolution as i32;
+		let region_buffer = renderer
+			.device()
+			.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+				label: Some("Voxelization Region Buffer"),
+				contents: bytemuck::cast_slice(&[VoxelizationRegionUniform::new(
+					[0; 3],
+					[resolution; 3],
+					0,
+				)]),
+				usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			});
 
-		let voxels_bind_group_layout =
-			renderer
-				.device()
-				.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-					label: Some("Voxels BindGroup Layout"),
-					entries: &[wgpu::BindGroupLayoutEntry {
-						binding: 0,
-						visibility: wgpu::ShaderStages::COMPUTE,
-						ty: wgpu::BindingType::StorageTexture {
-							access: wgpu::StorageTextureAccess::WriteOnly,
-							format: wgpu::TextureFormat::Rgba8Unorm,
-							view_dimension: wgpu::TextureViewDimension::D3,
-						},
-						count: None,
-					}],
-				});
+		let mip_mapper = VoxelMipMapper::new(renderer, VoxelStorageFormat::Rgba8Unorm);
+		let normal_mip_mapper = VoxelMipMapper::new(renderer, VoxelStorageFormat::Rgba8Snorm);
 
-		let voxelizer_layout =
-			renderer
-				.device()
-				.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-					label: Some("Voxelizer Layout"),
-					bind_group_layouts: &[
-						&voxels_bind_group_layout,
-						&crate::mesh::ComputeMeshBindGroup::get_bind_group_layout(
-							&renderer.device(),
-						),
-						&crate::ModelBindGroup::get_bind_group_layout(&renderer.device()),
-						&crate::MaterialBindGroup::get_bind_group_layout(&renderer.device()),
-					],
-					push_constant_ranges: &[],
-				});
+		Self {
+			voxelizer: Voxelizer::new(renderer),
+			region_buffer,
 
-		let voxelizer_pipeline =
-			renderer
-				.device()
-				.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-					label: Some("Voxelizer pipeline"),
-					layout: Some(&voxelizer_layout),
-					module: &voxelizer_shader,
-					entry_point: "main",
-				});
+			mip_mapper,
+			normal_mip_mapper,
+			dispatch_preparer: FragmentDispatchPreparer::new(renderer),
+			color_accumulator: None,
+			voxels_bind_group: None,
+			static_cache: StaticVoxelCache::new(renderer.device()),
+			voxelized_bounds: HashMap::new(),
+			dirty_region: None,
+			settings: VoxelizationSettings::default(),
+		}
+	}
 
+	// The voxels [min, max) the last execute re-voxelized, None when nothing had changed
+	pub fn dirty_region(&self) -> Option<([i32; 3], [i32; 3])> {
+		self.dirty_region
+	}
 
-		let mip_map_shader = renderer.device().create_shader_module(wgpu::ShaderModuleDescriptor {
-			label: Some("Voxel Mipmapping shader"),
-			source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!("../shaders/voxel_mip_map.wgsl")))
-		});
+	// Static meshes are voxelized again on the next execute, for when they were moved or edited
+	pub fn invalidate_static(&mut self) {
+		self.static_cache.key = None;
+	}
 
-		let mip_map_bind_group_layout = renderer.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-			label: Some("Voxel Mipmapping bind group layout"),
-			entries: &[
-				wgpu::BindGroupLayoutEntry {
-					binding: 0,
-					visibility: wgpu::ShaderStages::COMPUTE,
-					ty: wgpu::BindingType::Texture {
-						sample_type: wgpu::TextureSampleType::Float { filterable: false },
-						view_dimension: wgpu::TextureViewDimension::D3,
-						multisampled: false,
-					},
-					count: None,
-				},
-				wgpu::BindGroupLayoutEntry {
-					binding: 1,
-					visibility: wgpu::ShaderStages::COMPUTE,
-					ty: wgpu::BindingType::StorageTexture {
-						access: wgpu::StorageTextureAccess::WriteOnly,
-						format: wgpu::TextureFormat::Rgba8Unorm,
-						view_dimension: wgpu::TextureViewDimension::D3,
-					},
-					count: None,
-				}
-			]
-		});
+	// Fills the volumes from a cache matching the volume and source, executing the pass then does nothing until a mesh moves.
+	// Fragment lists aren't cached, so nothing is loaded while VoxelizationSettings::fragment_capacity is set.
+	// A cache holds every mesh as if it was static, so nothing is loaded either once the scene has a dynamic mesh
+	pub fn load_cache(&mut self, renderer: &Renderer, cache: &VoxelCache, source_hash: u64) -> bool {
+		let voxels_resource = renderer.get_resource::<VoxelsResource>().unwrap();
+		if !cache.matches(&voxels_resource.volume, source_hash)
+			|| self.settings.fragment_capacity.is_some()
+			|| renderer.renderer.meshes.iter().any(|mesh| !mesh.is_static)
+		{
+			return false;
+		}
 
-		let mip_map_layout = renderer.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-			label: Some("Voxel mipmapping pipeline layout"),
-			bind_group_layouts: &[
-				&mip_map_bind_group_layout,
-			],
-			push_constant_ranges: &[],
-		});
+		// The static cache is only allocated for dynamic meshes, which voxelize the static ones again first
+		cache.upload(renderer, &voxels_resource);
 
-		let mip_mapping_pipeline = renderer.device().create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-			label: Some("Voxel Mipmapping pipeline"),
-			layout: Some(&mip_map_layout),
-			module: &mip_map_shader,
-			entry_point: "main",
-		});
+		self.static_cache.key = Some((self.settings.method, self.settings.write_mode, None, true));
+		self.voxelized_bounds = renderer
+			.renderer
+			.meshes
+			.iter()
+			.map(|mesh| (*mesh.key(), (mesh.bounds, mesh.is_static, mesh.transform_generation)))
+			.collect();
 
-		Self {
-			voxelizer_pipeline,
-			voxels_bind_group_layout,
+		true
+	}
 
-			mip_mapping_pipeline,
-			mip_map_bind_group_layout,
-		}
+	pub fn settings(&self) -> VoxelizationSettings {
+		self.settings
+	}
+
+	pub fn set_settings(&mut self, settings: VoxelizationSettings) {
+		self.settings = settings;
 	}
 }
 
@@ -194,65 +401,136 @@ impl super::RenderPassTrait for VoxelizationPass {
 		command_encoder: &'manager CommandEncoder,
 		global_resources: &mut crate::ResourceManagerHandle<'manager>,
 	) -> Option<wgpu::CommandBuffer> {
-		let create_mesh_bind_group = |mesh: &crate::mesh::Mesh, offset: u64, size: u64| {
-			command_encoder
-			.device()
-			.create_bind_group(&wgpu::BindGroupDescriptor {
-					label: Some("Mesh Bind Group"),
-					layout: &crate::mesh::ComputeMeshBindGroup::get_bind_group_layout(
-						&command_encoder.device(),
-					),
-					entries: &[
-						wgpu::BindGroupEntry {
-							binding: 0,
-							resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-								buffer: &mesh.index_buffer,
-								offset,
-								size: std::num::NonZeroU64::new(size),
-							}),
-						},
-						wgpu::BindGroupEntry {
-							binding: 1,
-							resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-								buffer: &mesh.vertex_buffer,
-								offset: mesh.positions.start,
-								size: std::num::NonZeroU64::new(
-									mesh.positions.end - mesh.positions.start,
-								),
-							}),
-						},
-						wgpu::BindGroupEntry {
-							binding: 2,
-							resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-								buffer: &mesh.vertex_buffer,
-								offset: mesh.normals.start,
-								size: std::num::NonZeroU64::new(
-									mesh.normals.end - mesh.normals.start,
-								),
-							}),
-						},
-						wgpu::BindGroupEntry {
-							binding: 3,
-							resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-								buffer: &mesh.vertex_buffer,
-								offset: mesh.colors.start,
-								size: std::num::NonZeroU64::new(
-									mesh.colors.end - mesh.colors.start,
-								),
-							}),
-						},
-					],
-				})
-		};
+		{
+			let mut voxels_resource = global_resources.get_mut_resource::<VoxelsResource>().unwrap();
+			voxels_resource.settings = self.settings;
+
+			assert!(
+				self.settings.fragment_capacity.is_none() || voxels_resource.volume.resolution <= MAX_FRAGMENT_RESOLUTION,
+				"Fragment lists can't address volumes finer than {MAX_FRAGMENT_RESOLUTION} voxels, got {}",
+				voxels_resource.volume.resolution
+			);
+
+			let capacity = voxels_resource.fragments.as_ref().map(|fragments| fragments.capacity);
+			if capacity != self.settings.fragment_capacity {
+				let new_fragments = |capacity| VoxelFragmentList::new(command_encoder.device(), capacity);
+				voxels_resource.fragments = self.settings.fragment_capacity.map(new_fragments);
+				self.static_cache.fragments = self.settings.fragment_capacity.map(new_fragments);
+				self.voxels_bind_group = None;
+				self.static_cache.bind_group = None;
+			}
+		}
+
+		let resolution = global_resources.get_resource::<VoxelsResource>().unwrap().volume.resolution;
+		if self.settings.method == VoxelizationMethod::Rasterization {
+			self.voxelizer.prepare_rasterization(command_encoder.device(), resolution);
+		}
+
+		if self.settings.write_mode == VoxelWriteMode::Average {
+			self.voxelizer.prepare_average(command_encoder.device());
+		} else if self.color_accumulator.take().is_some() {
+			self.voxels_bind_group = None;
+			self.static_cache.bind_group = None;
+		}
 
 		let voxels_resource = global_resources.get_resource::<VoxelsResource>().unwrap();
 
-		let voxelization_view = voxels_resource.color.texture.create_view(&wgpu::TextureViewDescriptor {
-			label: Some("Voxel Mipmapping source"),
-			base_mip_level: 0,
-			mip_level_count: Some(1),
-			..Default::default()
-		});
+		let volume_uniform = VoxelVolumeUniform {
+			anisotropic: (self.settings.mip_mode == VoxelMipMode::Anisotropic) as u32,
+			..voxels_resource.volume.to_uniform()
+		};
+		command_encoder.queue().write_buffer(
+			&voxels_resource.volume_buffer,
+			0,
+			bytemuck::cast_slice(&[volume_uniform]),
+		);
+
+		let volume = voxels_resource.volume;
+		let new_region = |(min, max)| VoxelizationRegionUniform {
+			append_fragments: voxels_resource.fragments.is_some() as u32,
+			write_volume: self.settings.dense_volume as u32,
+			..VoxelizationRegionUniform::new(min, max, 0)
+		};
+		let full_region = new_region(([0; 3], [volume.resolution as i32; 3]));
+
+		let meshes: HashMap<_, _> = command_encoder
+			.get_meshes()
+			.meshes
+			.iter()
+			.map(|mesh| (*mesh.key(), (mesh.bounds, mesh.is_static, mesh.transform_generation)))
+			.collect();
+
+		if self.static_cache.textures.is_none() && meshes.values().any(|(_, is_static, _)| !is_static) {
+			self.static_cache.allocate(command_encoder.device(), voxels_resource.size);
+		}
+
+		// Every moved, added or removed mesh dirties its old and new bounds, in the static cache as well if it is static
+		let key = Some((
+			self.settings.method,
+			self.settings.write_mode,
+			self.settings.fragment_capacity,
+			self.settings.dense_volume,
+		));
+		let (static_region, region) = if self.static_cache.key != key {
+			self.static_cache.key = key;
+			(Some(full_region), Some(full_region))
+		} else {
+			let mut static_dirty: Option<crate::mesh::Aabb> = None;
+			let mut dirty: Option<crate::mesh::Aabb> = None;
+			let mut mark = |(bounds, is_static, _): &(crate::mesh::Aabb, bool, u32)| {
+				dirty = Some(dirty.map_or(*bounds, |dirty| dirty.union(bounds)));
+				if *is_static {
+					static_dirty = Some(static_dirty.map_or(*bounds, |dirty| dirty.union(bounds)));
+				}
+			};
+
+			for (id, current) in meshes.iter() {
+				match self.voxelized_bounds.get(id) {
+					Some(old) if old == current => {}
+					old => {
+						old.into_iter().for_each(&mut mark);
+						mark(current);
+					}
+				}
+			}
+			for (id, old) in self.voxelized_bounds.iter() {
+				if !meshes.contains_key(id) {
+					mark(old);
+				}
+			}
+
+			// Fragment lists can't be patched in place, so any change rebuilds them from scratch
+			if voxels_resource.fragments.is_some() && dirty.is_some() {
+				(Some(full_region), Some(full_region))
+			} else {
+				(
+					static_dirty.and_then(|bounds| volume.region_of(&bounds)).map(new_region),
+					dirty.and_then(|bounds| volume.region_of(&bounds)).map(new_region),
+				)
+			}
+		};
+		let static_region = static_region.filter(|_| self.static_cache.textures.is_some());
+		self.voxelized_bounds = meshes;
+		self.dirty_region = region.map(|region| (region.min, region.max));
+
+		// The accumulator only covers the regions voxelized, so it grows with the largest of them
+		if self.settings.write_mode == VoxelWriteMode::Average {
+			let voxel_count = [static_region, region]
+				.iter()
+				.flatten()
+				.map(|region| region.size().map(u64::from).iter().product())
+				.max()
+				.unwrap_or(0);
+			let capacity = self
+				.color_accumulator
+				.as_ref()
+				.map_or(0, |buffer| buffer.size() / std::mem::size_of::<u32>() as u64);
+			if voxel_count > capacity {
+				self.color_accumulator = Some(new_color_accumulator(command_encoder.device(), voxel_count));
+				self.voxels_bind_group = None;
+				self.static_cache.bind_group = None;
+			}
+		}
 
 		let mut encoder =
 		command_encoder
@@ -261,150 +539,610 @@ impl super::RenderPassTrait for VoxelizationPass {
 					label: Some("Voxelization Encoder"),
 				});
 
-		let voxels_bind_group =
-		command_encoder
-		.device()
-		.create_bind_group(&wgpu::BindGroupDescriptor {
-			label: Some("Voxels Bind Group"),
-			layout: &self.voxels_bind_group_layout,
-			entries: &[wgpu::BindGroupEntry {
-				binding: 0,
-				resource: wgpu::BindingResource::TextureView(&voxelization_view),
-			}],
+		let voxels_bind_group = self.voxels_bind_group.get_or_insert_with(|| {
+			self.voxelizer.new_bind_group(
+				command_encoder.device(),
+				&voxels_resource.volume_buffer,
+				&self.region_buffer,
+				[
+					&mip_view(&voxels_resource.color.texture, 0),
+					&mip_view(&voxels_resource.normal.texture, 0),
+					&mip_view(&voxels_resource.emissive.texture, 0),
+					&mip_view(&voxels_resource.material.texture, 0),
+				],
+				voxels_resource.fragments.as_ref(),
+				self.color_accumulator.as_ref(),
+			)
 		});
-				
-		let meshes = command_encoder.get_meshes();
-		let mut runs = Vec::new();
-		for mesh in meshes.meshes.iter() {
-			let mut mesh_runs = Vec::new();
-
-			for primitive in mesh.primitives.iter() {
-				let indices_num = primitive.index.end - primitive.index.start;
-				let triangles_num = indices_num / 3;
-				let alignment = command_encoder
-					.device()
-					.limits()
-					.min_storage_buffer_offset_alignment
-					* 3;
-				let dispatch_group =
-					(65535 /*Max num workgroup dispatches*/ / alignment) * alignment;
-
-				let run = if triangles_num <= dispatch_group {
-					let bind_group = create_mesh_bind_group(
-						&mesh,
-						primitive.index.start as u64
-							* std::mem::size_of::<crate::mesh::Index>() as u64,
-						(primitive.index.end - primitive.index.start) as u64
-							* std::mem::size_of::<crate::mesh::Index>() as u64,
-					);
 
-					vec![(bind_group, triangles_num)]
-				} else {
-					let mut offsets = Vec::new();
-					let number_of_runs =
-						f64::ceil(triangles_num as f64 / dispatch_group as f64) as u32;
-
-					for i in 0..number_of_runs {
-						let offset = primitive.index.start + (dispatch_group * 3 * i);
-
-						let size = if (triangles_num - (dispatch_group * i)) > dispatch_group {
-							dispatch_group
-						} else {
-							triangles_num - (dispatch_group * i)
-						};
-
-						let bind_group = create_mesh_bind_group(
-							&mesh,
-							offset as u64 * std::mem::size_of::<crate::mesh::Index>() as u64,
-							size as u64 * std::mem::size_of::<crate::mesh::Index>() as u64,
-						);
-						offsets.push((bind_group, size));
-					}
+		let static_cache = &mut self.static_cache;
+		if let (Some(textures), Some(static_region)) = (&static_cache.textures, static_region) {
+			let static_bind_group = static_cache.bind_group.get_or_insert_with(|| {
+				let views = textures
+					.each_ref()
+					.map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+
+				self.voxelizer.new_bind_group(
+					command_encoder.device(),
+					&voxels_resource.volume_buffer,
+					&static_cache.region_buffer,
+					views.each_ref(),
+					static_cache.fragments.as_ref(),
+					self.color_accumulator.as_ref(),
+				)
+			});
+
+			command_encoder.queue().write_buffer(
+				&static_cache.region_buffer,
+				0,
+				bytemuck::cast_slice(&[static_region]),
+			);
+
+			self.voxelizer.encode_clear(&mut encoder, static_bind_group, &static_region);
+			encode_voxelize(
+				&self.voxelizer,
+				command_encoder,
+				&mut encoder,
+				static_bind_group,
+				static_cache.fragments.as_ref(),
+				self.color_accumulator.as_ref(),
+				&self.settings,
+				&static_region,
+				MeshSelection {
+					is_static: Some(true),
+					overlapping: Some(volume.region_bounds(static_region.min, static_region.max)),
+				},
+			);
+		}
+
+		let Some(region) = region else {
+			return Some(encoder.finish());
+		};
+
+		command_encoder
+			.queue()
+			.write_buffer(&self.region_buffer, 0, bytemuck::cast_slice(&[region]));
 
-					offsets
+		// The static voxels of the region are restored under the dynamic meshes, without the cache every mesh is voxelized again
+		let (fragments, is_static) = match &static_cache.textures {
+			Some(textures) => {
+				let [width, height, depth] = region.size();
+				let origin = wgpu::Origin3d {
+					x: region.min[0] as u32,
+					y: region.min[1] as u32,
+					z: region.min[2] as u32,
 				};
+				for (source, destination) in textures.iter().zip([
+					&voxels_resource.color.texture,
+					&voxels_resource.normal.texture,
+					&voxels_resource.emissive.texture,
+					&voxels_resource.material.texture,
+				]) {
+					encoder.copy_texture_to_texture(
+						wgpu::ImageCopyTexture {
+							origin,
+							..source.as_image_copy()
+						},
+						wgpu::ImageCopyTexture {
+							origin,
+							..destination.as_image_copy()
+						},
+						wgpu::Extent3d {
+							width,
+							height,
+							depth_or_array_layers: depth,
+						},
+					);
+				}
+				if let (Some(source), Some(destination)) = (&static_cache.fragments, &voxels_resource.fragments) {
+					destination.encode_copy_from(&mut encoder, source);
+				}
 
-				mesh_runs.push(run);
+				(None, Some(false))
+			}
+			None => {
+				self.voxelizer.encode_clear(&mut encoder, voxels_bind_group, &region);
+				(voxels_resource.fragments.as_ref(), None)
 			}
+		};
+
+		encode_voxelize(
+			&self.voxelizer,
+			command_encoder,
+			&mut encoder,
+			voxels_bind_group,
+			fragments,
+			self.color_accumulator.as_ref(),
+			&self.settings,
+			&region,
+			MeshSelection {
+				is_static,
+				overlapping: Some(volume.region_bounds(region.min, region.max)),
+			},
+		);
 
-			runs.push(mesh_runs);
+		if let Some(fragments) = &voxels_resource.fragments {
+			self.dispatch_preparer.encode(command_encoder.device(), &mut encoder, fragments);
 		}
 
-		let materials = command_encoder.get_materials();
+		if !self.settings.dense_volume {
+			return Some(encoder.finish());
+		}
 
-		{
-			let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-				label: Some("Voxelization pass"),
-				timestamp_writes: None,
-			});
+		let (min, max) = (region.min.map(|v| v as u32), region.max.map(|v| v as u32));
+		for texture in [
+			&voxels_resource.color.texture,
+			&voxels_resource.emissive.texture,
+			&voxels_resource.material.texture,
+		] {
+			self.mip_mapper.generate_region(
+				command_encoder,
+				&mut encoder,
+				texture,
+				voxels_resource.size,
+				min,
+				max,
+			);
+		}
+		self.normal_mip_mapper.generate_region(
+			command_encoder,
+			&mut encoder,
+			&voxels_resource.normal.texture,
+			voxels_resource.size,
+			min,
+			max,
+		);
 
-			compute_pass.set_pipeline(&self.voxelizer_pipeline);
-			compute_pass.set_bind_group(0, &voxels_bind_group, &[]);
+		Some(encoder.finish())
+	}
+}
 
-			for (mesh, mesh_runs) in meshes.meshes.iter().zip(runs.iter()) {
-				compute_pass.set_bind_group(2, &mesh.model_bind_group, &[]);
-				for (primitive, prim_runs) in mesh.primitives.iter().zip(mesh_runs.iter()) {
-					compute_pass.set_bind_group(
-						3,
-						&materials.materials.get(&primitive.material).unwrap().bind_group,
-						&[],
-					);
+// Voxelizes the selected meshes into the bind group's volumes, appending to fragments when given
+#[allow(clippy::too_many_arguments)]
+fn encode_voxelize(
+	voxelizer: &Voxelizer,
+	command_encoder: &CommandEncoder,
+	encoder: &mut wgpu::CommandEncoder,
+	voxels_bind_group: &wgpu::BindGroup,
+	fragments: Option<&VoxelFragmentList>,
+	color_accumulator: Option<&wgpu::Buffer>,
+	settings: &VoxelizationSettings,
+	region: &VoxelizationRegionUniform,
+	selection: MeshSelection,
+) {
+	if let Some(fragments) = fragments {
+		fragments.encode_clear(encoder);
+	}
+	// Only the words of the region are used, see voxel_store_average.wgsl
+	if let Some(color_accumulator) = color_accumulator {
+		let voxel_count = region.size().iter().map(|&v| v as u64).product::<u64>();
+		if voxel_count > 0 {
+			encoder.clear_buffer(color_accumulator, 0, Some(voxel_count * std::mem::size_of::<u32>() as u64));
+		}
+	}
 
-					for (bind_group, size) in prim_runs.iter() {
-						compute_pass.set_bind_group(1, bind_group, &[]);
+	match settings.method {
+		VoxelizationMethod::Compute => voxelizer.encode(
+			command_encoder,
+			encoder,
+			&[voxels_bind_group],
+			settings.write_mode,
+			selection,
+		),
+		VoxelizationMethod::Rasterization => voxelizer.encode_rasterized(
+			command_encoder,
+			encoder,
+			voxels_bind_group,
+			region,
+			settings.write_mode,
+			selection,
+		),
+	}
 
-						compute_pass.dispatch_workgroups(*size, 1, 1);
-					}
-				}
-			}
-		}
+	if color_accumulator.is_some() {
+		voxelizer.encode_resolve(encoder, voxels_bind_group, region);
+	}
+}
 
-		// Mipmapping
-		for mip_level in 1..voxels_resource.size.max_mips(wgpu::TextureDimension::D3) {
-			let size = voxels_resource.size.mip_level_size(mip_level, wgpu::TextureDimension::D3);
+pub(crate) fn voxel_sampler_descriptor() -> wgpu::SamplerDescriptor<'static> {
+	wgpu::SamplerDescriptor {
+		address_mode_u: wgpu::AddressMode::ClampToEdge,
+		address_mode_v: wgpu::AddressMode::ClampToEdge,
+		address_mode_w: wgpu::AddressMode::ClampToEdge,
+		mag_filter: wgpu::FilterMode::Nearest,
+		min_filter: wgpu::FilterMode::Nearest,
+		mipmap_filter: wgpu::FilterMode::Nearest,
+		compare: None,
+		lod_min_clamp: 0.0,
+		lod_max_clamp: 100.0,
+		..Default::default()
+	}
+}
 
-			let source_view = voxels_resource.color.texture.create_view(&wgpu::TextureViewDescriptor {
-				label: Some("Voxel Mipmapping source"),
-				base_mip_level: mip_level - 1,
-				mip_level_count: Some(1),
-				..Default::default()
-			});
+pub(crate) fn new_voxel_texture(
+	device: &wgpu::Device,
+	size: wgpu::Extent3d,
+	label: &str,
+	format: wgpu::TextureFormat,
+) -> crate::mesh::Texture {
+	let texture = device.create_texture(&wgpu::TextureDescriptor {
+		label: Some(&format!("{label} Texture")),
+		size,
+		mip_level_count: size.max_mips(wgpu::TextureDimension::D3),
+		sample_count: 1,
+		dimension: wgpu::TextureDimension::D3,
+		format,
+		usage: wgpu::TextureUsages::STORAGE_BINDING
+			| wgpu::TextureUsages::TEXTURE_BINDING
+			| wgpu::TextureUsages::COPY_SRC
+			| wgpu::TextureUsages::COPY_DST,
+		view_formats: &[],
+	});
+
+	let view = texture.create_view(&wgpu::TextureViewDescriptor {
+		label: Some(&format!("{label} View")),
+		format: Some(format),
+		dimension: Some(wgpu::TextureViewDimension::D3),
+		..Default::default()
+	});
+	let sampler = device.create_sampler(&voxel_sampler_descriptor());
+
+	crate::mesh::Texture {
+		texture,
+		view,
+		sampler,
+	}
+}
+
+fn new_mip_map_bind_group_layout(device: &wgpu::Device, format: wgpu::TextureFormat) -> wgpu::BindGroupLayout {
+	device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+		label: Some("Voxel Mipmapping bind group layout"),
+		entries: &[
+			wgpu::BindGroupLayoutEntry {
+				binding: 0,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::Texture {
+					sample_type: wgpu::TextureSampleType::Float { filterable: false },
+					view_dimension: wgpu::TextureViewDimension::D3,
+					multisampled: false,
+				},
+				count: None,
+			},
+			wgpu::BindGroupLayoutEntry {
+				binding: 1,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::StorageTexture {
+					access: wgpu::StorageTextureAccess::WriteOnly,
+					format,
+					view_dimension: wgpu::TextureViewDimension::D3,
+				},
+				count: None,
+			},
+			wgpu::BindGroupLayoutEntry {
+				binding: 2,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::Buffer {
+					ty: wgpu::BufferBindingType::Uniform,
+					has_dynamic_offset: false,
+					min_binding_size: None,
+				},
+				count: None,
+			},
+		]
+	})
+}
 
-			let output_view = voxels_resource.color.texture.create_view(&wgpu::TextureViewDescriptor {
-				label: Some("Voxel Mipmapping output"),
-				base_mip_level: mip_level,
-				mip_level_count: Some(1),
-				..Default::default()
+fn mip_view(texture: &wgpu::Texture, mip_level: u32) -> wgpu::TextureView {
+	texture.create_view(&wgpu::TextureViewDescriptor {
+		label: Some("Voxel Mipmapping view"),
+		base_mip_level: mip_level,
+		mip_level_count: Some(1),
+		..Default::default()
+	})
+}
+
+// The origin buffer and bind group downsampling into one level of a texture
+struct DownsampleLevel {
+	origin: [u32; 3],
+	origin_buffer: wgpu::Buffer,
+	bind_group: wgpu::BindGroup,
+}
+
+// Kept by source and output texture and output level, dropped all at once past MAX_DOWNSAMPLE_LEVELS
+// so replaced textures don't stay alive
+#[derive(Default)]
+struct DownsampleCache {
+	levels: HashMap<(wgpu::Id<wgpu::Texture>, wgpu::Id<wgpu::Texture>, u32), DownsampleLevel>,
+}
+
+const MAX_DOWNSAMPLE_LEVELS: usize = 128;
+
+impl DownsampleCache {
+	#[allow(clippy::too_many_arguments)]
+	fn bind_group(
+		&mut self,
+		command_encoder: &CommandEncoder,
+		bind_group_layout: &wgpu::BindGroupLayout,
+		source: &wgpu::Texture,
+		source_level: u32,
+		output: &wgpu::Texture,
+		output_level: u32,
+		origin: [u32; 3],
+	) -> &wgpu::BindGroup {
+		let key = (source.global_id(), output.global_id(), output_level);
+		if !self.levels.contains_key(&key) && self.levels.len() >= MAX_DOWNSAMPLE_LEVELS {
+			self.levels.clear();
+		}
+
+		let level = self.levels.entry(key).or_insert_with(|| {
+			let device = command_encoder.device();
+			let origin_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+				label: Some("Voxel Mipmapping origin buffer"),
+				contents: bytemuck::cast_slice(&[origin[0], origin[1], origin[2], 0]),
+				usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
 			});
 
-			let bind_group = command_encoder.device().create_bind_group(&wgpu::BindGroupDescriptor {
+			let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
 				label: Some("Voxel Mipmapping bind group"),
-				layout: &self.mip_map_bind_group_layout,
+				layout: bind_group_layout,
 				entries: &[
 					wgpu::BindGroupEntry {
 						binding: 0,
-						resource: wgpu::BindingResource::TextureView(&source_view),
+						resource: wgpu::BindingResource::TextureView(&mip_view(source, source_level)),
 					},
 					wgpu::BindGroupEntry {
 						binding: 1,
-						resource: wgpu::BindingResource::TextureView(&output_view),
+						resource: wgpu::BindingResource::TextureView(&mip_view(output, output_level)),
+					},
+					wgpu::BindGroupEntry {
+						binding: 2,
+						resource: origin_buffer.as_entire_binding(),
 					},
 				],
 			});
-			
-			{
-				let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-					label: Some("mip map pass: "),
-					timestamp_writes: None,
-				});
 
-				compute_pass.set_pipeline(&self.mip_mapping_pipeline);
-				compute_pass.set_bind_group(0, &bind_group, &[]);
-
-				compute_pass.dispatch_workgroups(size.width, size.height, size.depth_or_array_layers);
+			DownsampleLevel {
+				origin,
+				origin_buffer,
+				bind_group,
 			}
+		});
+
+		if level.origin != origin {
+			level.origin = origin;
+			command_encoder.queue().write_buffer(
+				&level.origin_buffer,
+				0,
+				bytemuck::cast_slice(&[origin[0], origin[1], origin[2], 0]),
+			);
 		}
 
-		Some(encoder.finish())
+		&level.bind_group
+	}
+}
+
+// Runs one invocation per output voxel from the bind group's origin on, the source is expected to be twice the size
+fn dispatch_downsample(
+	encoder: &mut wgpu::CommandEncoder,
+	pipeline: &wgpu::ComputePipeline,
+	bind_group: &wgpu::BindGroup,
+	size: wgpu::Extent3d,
+) {
+	let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+		label: Some("mip map pass: "),
+		timestamp_writes: None,
+	});
+
+	compute_pass.set_pipeline(pipeline);
+	compute_pass.set_bind_group(0, bind_group, &[]);
+
+	compute_pass.dispatch_workgroups(size.width, size.height, size.depth_or_array_layers);
+}
+
+// The formats voxel volumes are mipmapped in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum VoxelStorageFormat {
+	Rgba8Unorm,
+	Rgba8Snorm,
+}
+
+impl VoxelStorageFormat {
+	pub(crate) fn texture_format(self) -> wgpu::TextureFormat {
+		match self {
+			Self::Rgba8Unorm => wgpu::TextureFormat::Rgba8Unorm,
+			Self::Rgba8Snorm => wgpu::TextureFormat::Rgba8Snorm,
+		}
+	}
+
+	// Declares the VoxelOutput storage texture type the shaders write through
+	fn wgsl_prelude(self) -> &'static str {
+		match self {
+			Self::Rgba8Unorm => "alias VoxelOutput = texture_storage_3d<rgba8unorm, write>;",
+			Self::Rgba8Snorm => "alias VoxelOutput = texture_storage_3d<rgba8snorm, write>;",
+		}
+	}
+}
+
+// Downsamples every mip level of a voxel texture from the one above it
+pub(crate) struct VoxelMipMapper {
+	pipeline: wgpu::ComputePipeline,
+	bind_group_layout: wgpu::BindGroupLayout,
+	bind_groups: DownsampleCache,
+}
+
+impl VoxelMipMapper {
+	// Storage textures are typed by format in WGSL, so every format gets its own pipeline
+	pub(crate) fn new(renderer: &Renderer, format: VoxelStorageFormat) -> Self {
+		let shader = renderer.device().create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("Voxel Mipmapping shader"),
+			source: wgpu::ShaderSource::Wgsl(
+				format!("{}\n{}", format.wgsl_prelude(), include_str!("../shaders/voxel_mip_map.wgsl")).into()
+			)
+		});
+
+		let bind_group_layout = new_mip_map_bind_group_layout(renderer.device(), format.texture_format());
+
+		let layout = renderer.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: Some("Voxel mipmapping pipeline layout"),
+			bind_group_layouts: &[
+				&bind_group_layout,
+			],
+			push_constant_ranges: &[],
+		});
+
+		let pipeline = renderer.device().create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+			label: Some("Voxel Mipmapping pipeline"),
+			layout: Some(&layout),
+			module: &shader,
+			entry_point: "main",
+		});
+
+		Self {
+			pipeline,
+			bind_group_layout,
+			bind_groups: DownsampleCache::default(),
+		}
+	}
+
+	pub(crate) fn generate(
+		&mut self,
+		command_encoder: &CommandEncoder,
+		encoder: &mut wgpu::CommandEncoder,
+		texture: &wgpu::Texture,
+		size: wgpu::Extent3d,
+	) {
+		let max = [size.width, size.height, size.depth_or_array_layers];
+		self.generate_region(command_encoder, encoder, texture, size, [0; 3], max);
+	}
+
+	// Only rebuilds the mip voxels covering [min, max) of level 0
+	pub(crate) fn generate_region(
+		&mut self,
+		command_encoder: &CommandEncoder,
+		encoder: &mut wgpu::CommandEncoder,
+		texture: &wgpu::Texture,
+		size: wgpu::Extent3d,
+		mut min: [u32; 3],
+		mut max: [u32; 3],
+	) {
+		for mip_level in 1..size.max_mips(wgpu::TextureDimension::D3) {
+			let level_size = size.mip_level_size(mip_level, wgpu::TextureDimension::D3);
+			let level_max = [level_size.width, level_size.height, level_size.depth_or_array_layers];
+
+			min = min.map(|v| v / 2);
+			max = [0, 1, 2].map(|i| max[i].div_ceil(2).min(level_max[i]));
+
+			let bind_group = self.bind_groups.bind_group(
+				command_encoder,
+				&self.bind_group_layout,
+				texture,
+				mip_level - 1,
+				texture,
+				mip_level,
+				min,
+			);
+			dispatch_downsample(
+				encoder,
+				&self.pipeline,
+				bind_group,
+				wgpu::Extent3d {
+					width: max[0] - min[0],
+					height: max[1] - min[1],
+					depth_or_array_layers: max[2] - min[2],
+				},
+			);
+		}
+	}
+}
+
+// Entry points of voxel_anisotropic_mip_map.wgsl, in the order of VoxelsResource::anisotropic_radiance
+const ANISOTROPIC_ENTRY_POINTS: [&str; 6] = [
+	"main_pos_x",
+	"main_neg_x",
+	"main_pos_y",
+	"main_neg_y",
+	"main_pos_z",
+	"main_neg_z",
+];
+
+// Builds the six directional volumes from the isotropic level 0, each one pre-integrated along its axis
+pub(crate) struct AnisotropicMipMapper {
+	pipelines: Vec<wgpu::ComputePipeline>,
+	bind_group_layout: wgpu::BindGroupLayout,
+	bind_groups: DownsampleCache,
+}
+
+impl AnisotropicMipMapper {
+	pub(crate) fn new(renderer: &Renderer) -> Self {
+		let shader = renderer.device().create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("Voxel Anisotropic Mipmapping shader"),
+			source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!(
+				"../shaders/voxel_anisotropic_mip_map.wgsl"
+			))),
+		});
+
+		let bind_group_layout = new_mip_map_bind_group_layout(renderer.device(), wgpu::TextureFormat::Rgba8Unorm);
+
+		let layout = renderer.device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: Some("Voxel anisotropic mipmapping pipeline layout"),
+			bind_group_layouts: &[
+				&bind_group_layout,
+			],
+			push_constant_ranges: &[],
+		});
+
+		let pipelines = ANISOTROPIC_ENTRY_POINTS
+			.iter()
+			.map(|entry_point| {
+				renderer.device().create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+					label: Some("Voxel Anisotropic Mipmapping pipeline"),
+					layout: Some(&layout),
+					module: &shader,
+					entry_point,
+				})
+			})
+			.collect();
+
+		Self {
+			pipelines,
+			bind_group_layout,
+			bind_groups: DownsampleCache::default(),
+		}
+	}
+
+	pub(crate) fn generate(
+		&mut self,
+		command_encoder: &CommandEncoder,
+		encoder: &mut wgpu::CommandEncoder,
+		source: &wgpu::Texture,
+		directions: &[crate::mesh::Texture; 6],
+	) {
+		for (pipeline, direction) in self.pipelines.iter().zip(directions.iter()) {
+			let size = direction.texture.size();
+
+			for mip_level in 0..direction.texture.mip_level_count() {
+				let (source, source_level) = if mip_level == 0 {
+					(source, 0)
+				} else {
+					(&direction.texture, mip_level - 1)
+				};
+
+				let bind_group = self.bind_groups.bind_group(
+					command_encoder,
+					&self.bind_group_layout,
+					source,
+					source_level,
+					&direction.texture,
+					mip_level,
+					[0; 3],
+				);
+				dispatch_downsample(
+					encoder,
+					pipeline,
+					bind_group,
+					size.mip_level_size(mip_level, wgpu::TextureDimension::D3),
+				);
+			}
+		}
 	}
 }
@@ -0,0 +1,655 @@
+use crate::command_encoder::*;
+use wgpu::util::DeviceExt;
+
+use crate::Renderer;
+
+use super::{AnisotropicMipMapper, VoxelClipmapResource, VoxelMipMapper, VoxelMipMode, VoxelStorageFormat, VoxelsResource};
+
+// Scratch volumes only needed once there is more than a single bounce
+struct BounceVolumes {
+	direct: wgpu::Texture,
+	direct_view: wgpu::TextureView,
+	output: wgpu::Texture,
+	output_view: wgpu::TextureView,
+	size: wgpu::Extent3d,
+}
+
+impl BounceVolumes {
+	fn new(device: &wgpu::Device, size: wgpu::Extent3d) -> Self {
+		let new_texture = |label| {
+			device.create_texture(&wgpu::TextureDescriptor {
+				label: Some(label),
+				size,
+				mip_level_count: 1,
+				sample_count: 1,
+				dimension: wgpu::TextureDimension::D3,
+				format: wgpu::TextureFormat::Rgba8Unorm,
+				usage: wgpu::TextureUsages::STORAGE_BINDING
+					| wgpu::TextureUsages::TEXTURE_BINDING
+					| wgpu::TextureUsages::COPY_SRC
+					| wgpu::TextureUsages::COPY_DST,
+				view_formats: &[],
+			})
+		};
+
+		let direct = new_texture("Voxel Direct Radiance Texture");
+		let output = new_texture("Voxel Bounce Radiance Texture");
+
+		Self {
+			direct_view: direct.create_view(&Default::default()),
+			direct,
+			output_view: output.create_view(&Default::default()),
+			output,
+			size,
+		}
+	}
+}
+
+// The cones every bounce traces from a voxel, one along its normal and the rest spread around it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RadianceBounceSettings {
+	pub cone_count: u32,
+	// Tangent of the cone half angle
+	pub cone_aperture: f32,
+}
+
+impl Default for RadianceBounceSettings {
+	fn default() -> Self {
+		Self {
+			cone_count: 6,
+			cone_aperture: 0.577,
+		}
+	}
+}
+
+impl RadianceBounceSettings {
+	fn to_uniform(self) -> RadianceBounceUniform {
+		RadianceBounceUniform {
+			cone_count: self.cone_count.max(1),
+			cone_aperture: self.cone_aperture,
+			_padding: [0; 2],
+		}
+	}
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct RadianceBounceUniform {
+	cone_count: u32,
+	cone_aperture: f32,
+	_padding: [u32; 2],
+}
+
+// Lights every occupied voxel of the VoxelizationPass output into VoxelsResource::radiance,
+// then runs one bounce dispatch for every bounce past the first.
+// A VoxelClipmapResource, when present, gets its direct light injected as well
+pub struct RadianceInjectionPass {
+	pipeline: wgpu::ComputePipeline,
+	clipmap_pipeline: wgpu::ComputePipeline,
+	voxels_bind_group_layout: wgpu::BindGroupLayout,
+	lights_bind_group_layout: wgpu::BindGroupLayout,
+	lights: crate::lights::LightsBuffer,
+
+	bounce_pipeline: wgpu::ComputePipeline,
+	bounce_bind_group_layout: wgpu::BindGroupLayout,
+	bounce_volumes: Option<BounceVolumes>,
+	bounce_settings: RadianceBounceSettings,
+	bounce_buffer: wgpu::Buffer,
+	dirty_bounce_settings: bool,
+
+	mip_mapper: VoxelMipMapper,
+	anisotropic_mip_mapper: AnisotropicMipMapper,
+}
+
+impl RadianceInjectionPass {
+	pub fn new(renderer: &Renderer) -> Self {
+		let shader = renderer
+			.device()
+			.create_shader_module(wgpu::ShaderModuleDescriptor {
+				label: Some("Radiance Injection shader"),
+				source: wgpu::ShaderSource::Wgsl(
+					format!(
+						"{}\n{}",
+						include_str!("../shaders/_lights.wgsl"),
+						include_str!("../shaders/radiance_injection.wgsl")
+					)
+					.into(),
+				),
+			});
+
+		let voxels_bind_group_layout =
+			renderer
+				.device()
+				.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+					label: Some("Radiance Injection Voxels BindGroup Layout"),
+					entries: &[
+						wgpu::BindGroupLayoutEntry {
+							binding: 0,
+							visibility: wgpu::ShaderStages::COMPUTE,
+							ty: wgpu::BindingType::Buffer {
+								ty: wgpu::BufferBindingType::Uniform,
+								has_dynamic_offset: false,
+								min_binding_size: None,
+							},
+							count: None,
+						},
+						wgpu::BindGroupLayoutEntry {
+							binding: 1,
+							visibility: wgpu::ShaderStages::COMPUTE,
+							ty: wgpu::BindingType::Texture {
+								sample_type: wgpu::TextureSampleType::Float { filterable: false },
+								view_dimension: wgpu::TextureViewDimension::D3,
+								multisampled: false,
+							},
+							count: None,
+						},
+						wgpu::BindGroupLayoutEntry {
+							binding: 2,
+							visibility: wgpu::ShaderStages::COMPUTE,
+							ty: wgpu::BindingType::StorageTexture {
+								access: wgpu::StorageTextureAccess::WriteOnly,
+								format: wgpu::TextureFormat::Rgba8Unorm,
+								view_dimension: wgpu::TextureViewDimension::D3,
+							},
+							count: None,
+						},
+						wgpu::BindGroupLayoutEntry {
+							binding: 3,
+							visibility: wgpu::ShaderStages::COMPUTE,
+							ty: wgpu::BindingType::Texture {
+								sample_type: wgpu::TextureSampleType::Float { filterable: false },
+								view_dimension: wgpu::TextureViewDimension::D3,
+								multisampled: false,
+							},
+							count: None,
+						},
+						wgpu::BindGroupLayoutEntry {
+							binding: 4,
+							visibility: wgpu::ShaderStages::COMPUTE,
+							ty: wgpu::BindingType::Texture {
+								sample_type: wgpu::TextureSampleType::Float { filterable: false },
+								view_dimension: wgpu::TextureViewDimension::D3,
+								multisampled: false,
+							},
+							count: None,
+						},
+					],
+				});
+
+		let lights_bind_group_layout =
+			renderer
+				.device()
+				.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+					label: Some("Radiance Injection Lights BindGroup Layout"),
+					entries: &[wgpu::BindGroupLayoutEntry {
+						binding: 0,
+						visibility: wgpu::ShaderStages::COMPUTE,
+						ty: wgpu::BindingType::Buffer {
+							ty: wgpu::BufferBindingType::Storage { read_only: true },
+							has_dynamic_offset: false,
+							min_binding_size: None,
+						},
+						count: None,
+					}],
+				});
+
+		let layout = renderer
+			.device()
+			.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+				label: Some("Radiance Injection Layout"),
+				bind_group_layouts: &[&voxels_bind_group_layout, &lights_bind_group_layout],
+				push_constant_ranges: &[],
+			});
+
+		let pipeline = renderer
+			.device()
+			.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+				label: Some("Radiance Injection pipeline"),
+				layout: Some(&layout),
+				module: &shader,
+				entry_point: "main",
+			});
+
+		let clipmap_shader = renderer
+			.device()
+			.create_shader_module(wgpu::ShaderModuleDescriptor {
+				label: Some("Clipmap Radiance Injection shader"),
+				source: wgpu::ShaderSource::Wgsl(
+					format!(
+						"{}\n{}",
+						include_str!("../shaders/_lights.wgsl"),
+						include_str!("../shaders/clipmap_radiance_injection.wgsl")
+					)
+					.into(),
+				),
+			});
+
+		let clipmap_pipeline = renderer
+			.device()
+			.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+				label: Some("Clipmap Radiance Injection pipeline"),
+				layout: Some(&layout),
+				module: &clipmap_shader,
+				entry_point: "main",
+			});
+
+		let bounce_shader = renderer
+			.device()
+			.create_shader_module(wgpu::ShaderModuleDescriptor {
+				label: Some("Radiance Bounce shader"),
+				source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!(
+					"../shaders/radiance_bounce.wgsl"
+				))),
+			});
+
+		let sampled_texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+			binding,
+			visibility: wgpu::ShaderStages::COMPUTE,
+			ty: wgpu::BindingType::Texture {
+				sample_type: wgpu::TextureSampleType::Float { filterable: false },
+				view_dimension: wgpu::TextureViewDimension::D3,
+				multisampled: false,
+			},
+			count: None,
+		};
+
+		let bounce_bind_group_layout =
+			renderer
+				.device()
+				.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+					label: Some("Radiance Bounce BindGroup Layout"),
+					entries: &[
+						wgpu::BindGroupLayoutEntry {
+							binding: 0,
+							visibility: wgpu::ShaderStages::COMPUTE,
+							ty: wgpu::BindingType::Buffer {
+								ty: wgpu::BufferBindingType::Uniform,
+								has_dynamic_offset: false,
+								min_binding_size: None,
+							},
+							count: None,
+						},
+						sampled_texture_entry(1),
+						sampled_texture_entry(2),
+						sampled_texture_entry(3),
+						sampled_texture_entry(4),
+						wgpu::BindGroupLayoutEntry {
+							binding: 5,
+							visibility: wgpu::ShaderStages::COMPUTE,
+							ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
+							count: None,
+						},
+						wgpu::BindGroupLayoutEntry {
+							binding: 6,
+							visibility: wgpu::ShaderStages::COMPUTE,
+							ty: wgpu::BindingType::StorageTexture {
+								access: wgpu::StorageTextureAccess::WriteOnly,
+								format: wgpu::TextureFormat::Rgba8Unorm,
+								view_dimension: wgpu::TextureViewDimension::D3,
+							},
+							count: None,
+						},
+						wgpu::BindGroupLayoutEntry {
+							binding: 7,
+							visibility: wgpu::ShaderStages::COMPUTE,
+							ty: wgpu::BindingType::Buffer {
+								ty: wgpu::BufferBindingType::Uniform,
+								has_dynamic_offset: false,
+								min_binding_size: None,
+							},
+							count: None,
+						},
+					],
+				});
+
+		let bounce_settings = RadianceBounceSettings::default();
+		let bounce_buffer = renderer
+			.device()
+			.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+				label: Some("Radiance Bounce Buffer"),
+				contents: bytemuck::cast_slice(&[bounce_settings.to_uniform()]),
+				usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			});
+
+		let bounce_layout = renderer
+			.device()
+			.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+				label: Some("Radiance Bounce Layout"),
+				bind_group_layouts: &[&bounce_bind_group_layout],
+				push_constant_ranges: &[],
+			});
+
+		let bounce_pipeline = renderer
+			.device()
+			.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+				label: Some("Radiance Bounce pipeline"),
+				layout: Some(&bounce_layout),
+				module: &bounce_shader,
+				entry_point: "main",
+			});
+
+		Self {
+			pipeline,
+			clipmap_pipeline,
+			voxels_bind_group_layout,
+			lights_bind_group_layout,
+			lights: crate::lights::LightsBuffer::new(renderer.device()),
+
+			bounce_pipeline,
+			bounce_bind_group_layout,
+			bounce_volumes: None,
+			bounce_settings,
+			bounce_buffer,
+			dirty_bounce_settings: false,
+
+			mip_mapper: VoxelMipMapper::new(renderer, VoxelStorageFormat::Rgba8Unorm),
+			anisotropic_mip_mapper: AnisotropicMipMapper::new(renderer),
+		}
+	}
+
+	pub fn bounce_settings(&self) -> RadianceBounceSettings {
+		self.bounce_settings
+	}
+
+	pub fn set_bounce_settings(&mut self, settings: RadianceBounceSettings) {
+		self.bounce_settings = settings;
+		self.dirty_bounce_settings = true;
+	}
+}
+
+impl RenderPassTrait for RadianceInjectionPass {
+	fn execute<'manager>(
+		&mut self,
+		command_encoder: &'manager CommandEncoder,
+		global_resources: &mut crate::ResourceManagerHandle<'manager>,
+	) -> Option<wgpu::CommandBuffer> {
+		if let Some(mut voxels_resource) = global_resources.get_mut_resource::<VoxelsResource>() {
+			match voxels_resource.settings.mip_mode {
+				VoxelMipMode::Isotropic => voxels_resource.anisotropic_radiance = None,
+				VoxelMipMode::Anisotropic if voxels_resource.anisotropic_radiance.is_none() => {
+					let size = voxels_resource.size.mip_level_size(1, wgpu::TextureDimension::D3);
+					let new_texture = |label| {
+						super::new_voxel_texture(command_encoder.device(), size, label, wgpu::TextureFormat::Rgba8Unorm)
+					};
+
+					voxels_resource.anisotropic_radiance = Some([
+						new_texture("Voxel Radiance +X"),
+						new_texture("Voxel Radiance -X"),
+						new_texture("Voxel Radiance +Y"),
+						new_texture("Voxel Radiance -Y"),
+						new_texture("Voxel Radiance +Z"),
+						new_texture("Voxel Radiance -Z"),
+					]);
+				}
+				VoxelMipMode::Anisotropic => {}
+			}
+		}
+
+		let lights_buffer = self.lights.update(command_encoder.device(), command_encoder.queue(), &command_encoder.get_lights());
+
+		let lights_bind_group = command_encoder
+			.device()
+			.create_bind_group(&wgpu::BindGroupDescriptor {
+				label: Some("Radiance Injection Lights Bind Group"),
+				layout: &self.lights_bind_group_layout,
+				entries: &[wgpu::BindGroupEntry {
+					binding: 0,
+					resource: lights_buffer.as_entire_binding(),
+				}],
+			});
+
+		let mut encoder = command_encoder
+			.device()
+			.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+				label: Some("Radiance Injection Encoder"),
+			});
+
+		if let Some(voxels_resource) = global_resources
+			.get_resource::<VoxelsResource>()
+			.filter(|voxels_resource| voxels_resource.settings.dense_volume)
+		{
+			self.encode_dense(command_encoder, &voxels_resource, &lights_bind_group, &mut encoder);
+		}
+
+		if let Some(clipmap_resource) = global_resources.get_resource::<VoxelClipmapResource>() {
+			self.encode_clipmap(command_encoder, &clipmap_resource, &lights_bind_group, &mut encoder);
+		}
+
+		Some(encoder.finish())
+	}
+}
+
+impl RadianceInjectionPass {
+	fn encode_dense(
+		&mut self,
+		command_encoder: &CommandEncoder,
+		voxels_resource: &VoxelsResource,
+		lights_bind_group: &wgpu::BindGroup,
+		encoder: &mut wgpu::CommandEncoder,
+	) {
+		let radiance_view = voxels_resource.radiance.texture.create_view(&wgpu::TextureViewDescriptor {
+			label: Some("Voxel Radiance output"),
+			base_mip_level: 0,
+			mip_level_count: Some(1),
+			..Default::default()
+		});
+
+		let voxels_bind_group = command_encoder
+			.device()
+			.create_bind_group(&wgpu::BindGroupDescriptor {
+				label: Some("Radiance Injection Voxels Bind Group"),
+				layout: &self.voxels_bind_group_layout,
+				entries: &[
+					wgpu::BindGroupEntry {
+						binding: 0,
+						resource: voxels_resource.volume_buffer.as_entire_binding(),
+					},
+					wgpu::BindGroupEntry {
+						binding: 1,
+						resource: wgpu::BindingResource::TextureView(&voxels_resource.color.view),
+					},
+					wgpu::BindGroupEntry {
+						binding: 2,
+						resource: wgpu::BindingResource::TextureView(&radiance_view),
+					},
+					wgpu::BindGroupEntry {
+						binding: 3,
+						resource: wgpu::BindingResource::TextureView(&voxels_resource.emissive.view),
+					},
+					wgpu::BindGroupEntry {
+						binding: 4,
+						resource: wgpu::BindingResource::TextureView(&voxels_resource.normal.view),
+					},
+				],
+			});
+
+		let workgroups = voxels_resource.volume.resolution.div_ceil(4);
+
+		{
+			let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+				label: Some("Radiance Injection pass"),
+				timestamp_writes: None,
+			});
+
+			compute_pass.set_pipeline(&self.pipeline);
+			compute_pass.set_bind_group(0, &voxels_bind_group, &[]);
+			compute_pass.set_bind_group(1, lights_bind_group, &[]);
+
+			compute_pass.dispatch_workgroups(workgroups, workgroups, workgroups);
+		}
+
+		self.mip_mapper.generate(
+			command_encoder,
+			encoder,
+			&voxels_resource.radiance.texture,
+			voxels_resource.size,
+		);
+
+		if voxels_resource.settings.bounce_count > 1 {
+			self.encode_bounces(command_encoder, voxels_resource, encoder);
+		} else {
+			self.bounce_volumes = None;
+		}
+
+		if let Some(anisotropic_radiance) = &voxels_resource.anisotropic_radiance {
+			self.anisotropic_mip_mapper.generate(
+				command_encoder,
+				encoder,
+				&voxels_resource.radiance.texture,
+				anisotropic_radiance,
+			);
+		}
+	}
+
+	fn encode_clipmap(
+		&self,
+		command_encoder: &CommandEncoder,
+		clipmap_resource: &VoxelClipmapResource,
+		lights_bind_group: &wgpu::BindGroup,
+		encoder: &mut wgpu::CommandEncoder,
+	) {
+		let voxels_bind_group = command_encoder
+			.device()
+			.create_bind_group(&wgpu::BindGroupDescriptor {
+				label: Some("Clipmap Radiance Injection Voxels Bind Group"),
+				layout: &self.voxels_bind_group_layout,
+				entries: &[
+					wgpu::BindGroupEntry {
+						binding: 0,
+						resource: clipmap_resource.clipmap_buffer.as_entire_binding(),
+					},
+					wgpu::BindGroupEntry {
+						binding: 1,
+						resource: wgpu::BindingResource::TextureView(&clipmap_resource.color.view),
+					},
+					wgpu::BindGroupEntry {
+						binding: 2,
+						resource: wgpu::BindingResource::TextureView(&clipmap_resource.radiance.view),
+					},
+					wgpu::BindGroupEntry {
+						binding: 3,
+						resource: wgpu::BindingResource::TextureView(&clipmap_resource.emissive.view),
+					},
+					wgpu::BindGroupEntry {
+						binding: 4,
+						resource: wgpu::BindingResource::TextureView(&clipmap_resource.normal.view),
+					},
+				],
+			});
+
+		let size = clipmap_resource.descriptor.extent();
+
+		let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+			label: Some("Clipmap Radiance Injection pass"),
+			timestamp_writes: None,
+		});
+
+		compute_pass.set_pipeline(&self.clipmap_pipeline);
+		compute_pass.set_bind_group(0, &voxels_bind_group, &[]);
+		compute_pass.set_bind_group(1, lights_bind_group, &[]);
+
+		compute_pass.dispatch_workgroups(
+			size.width.div_ceil(4),
+			size.height.div_ceil(4),
+			size.depth_or_array_layers.div_ceil(4),
+		);
+	}
+
+	fn encode_bounces(
+		&mut self,
+		command_encoder: &CommandEncoder,
+		voxels_resource: &VoxelsResource,
+		encoder: &mut wgpu::CommandEncoder,
+	) {
+		let workgroups = voxels_resource.volume.resolution.div_ceil(4);
+
+		if self.bounce_volumes.as_ref().map(|volumes| volumes.size) != Some(voxels_resource.size) {
+			self.bounce_volumes = Some(BounceVolumes::new(command_encoder.device(), voxels_resource.size));
+		}
+		let bounce_volumes = self.bounce_volumes.as_ref().unwrap();
+
+		if self.dirty_bounce_settings {
+			command_encoder.queue().write_buffer(
+				&self.bounce_buffer,
+				0,
+				bytemuck::cast_slice(&[self.bounce_settings.to_uniform()]),
+			);
+			self.dirty_bounce_settings = false;
+		}
+
+		// Every bounce adds to the direct light, so keep it around
+		encoder.copy_texture_to_texture(
+			voxels_resource.radiance.texture.as_image_copy(),
+			bounce_volumes.direct.as_image_copy(),
+			voxels_resource.size,
+		);
+
+		let bounce_bind_group = command_encoder
+			.device()
+			.create_bind_group(&wgpu::BindGroupDescriptor {
+				label: Some("Radiance Bounce Bind Group"),
+				layout: &self.bounce_bind_group_layout,
+				entries: &[
+					wgpu::BindGroupEntry {
+						binding: 0,
+						resource: voxels_resource.volume_buffer.as_entire_binding(),
+					},
+					wgpu::BindGroupEntry {
+						binding: 1,
+						resource: wgpu::BindingResource::TextureView(&voxels_resource.color.view),
+					},
+					wgpu::BindGroupEntry {
+						binding: 2,
+						resource: wgpu::BindingResource::TextureView(&voxels_resource.normal.view),
+					},
+					wgpu::BindGroupEntry {
+						binding: 3,
+						resource: wgpu::BindingResource::TextureView(&bounce_volumes.direct_view),
+					},
+					wgpu::BindGroupEntry {
+						binding: 4,
+						resource: wgpu::BindingResource::TextureView(&voxels_resource.radiance.view),
+					},
+					wgpu::BindGroupEntry {
+						binding: 5,
+						resource: wgpu::BindingResource::Sampler(&voxels_resource.radiance.sampler),
+					},
+					wgpu::BindGroupEntry {
+						binding: 6,
+						resource: wgpu::BindingResource::TextureView(&bounce_volumes.output_view),
+					},
+					wgpu::BindGroupEntry {
+						binding: 7,
+						resource: self.bounce_buffer.as_entire_binding(),
+					},
+				],
+			});
+
+		for _ in 1..voxels_resource.settings.bounce_count {
+			{
+				let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+					label: Some("Radiance Bounce pass"),
+					timestamp_writes: None,
+				});
+
+				compute_pass.set_pipeline(&self.bounce_pipeline);
+				compute_pass.set_bind_group(0, &bounce_bind_group, &[]);
+
+				compute_pass.dispatch_workgroups(workgroups, workgroups, workgroups);
+			}
+
+			encoder.copy_texture_to_texture(
+				bounce_volumes.output.as_image_copy(),
+				voxels_resource.radiance.texture.as_image_copy(),
+				voxels_resource.size,
+			);
+
+			self.mip_mapper.generate(
+				command_encoder,
+				encoder,
+				&voxels_resource.radiance.texture,
+				voxels_resource.size,
+			);
+		}
+	}
+}
@@ -0,0 +1,420 @@
+use crate::command_encoder::*;
+use wgpu::util::DeviceExt;
+
+use crate::{readback::BufferReadback, Renderer};
+
+use super::{VoxelsResource, FRAGMENT_SHADER_PRELUDE};
+
+// Cells are addressed with 10 bits per axis, like the fragment positions
+pub const MAX_OCTREE_DEPTH: u32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SparseVoxelOctreeDescriptor {
+	// The deepest level has 2^depth cells per axis over the voxel volume
+	pub depth: u32,
+	// Tiles of eight nodes that can be allocated, including the root
+	pub tile_capacity: u32,
+}
+
+impl Default for SparseVoxelOctreeDescriptor {
+	fn default() -> Self {
+		Self {
+			depth: 7,
+			tile_capacity: 1 << 16,
+		}
+	}
+}
+
+impl SparseVoxelOctreeDescriptor {
+	pub fn resolution(&self) -> u32 {
+		1 << self.depth
+	}
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SparseVoxelOctreeUniform {
+	min: [f32; 3],
+	depth: u32,
+	size: [f32; 3],
+	voxel_size: f32,
+	tile_capacity: u32,
+	_padding: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct OctreeLevelUniform {
+	level: u32,
+	_padding: [u32; 3],
+}
+
+// Every tile stores the child tile pointers (nodes) and the packed radiance (values) of its eight octants,
+// tile 0 is the root and a child pointer of 0 marks an empty octant.
+// There are no bricks, an octant of the deepest level is a single voxel with one value
+pub struct SparseVoxelOctreeResource {
+	pub nodes: wgpu::Buffer,
+	pub values: wgpu::Buffer,
+	pub tile_count: wgpu::Buffer,
+	pub descriptor: SparseVoxelOctreeDescriptor,
+	pub octree_buffer: wgpu::Buffer,
+}
+
+impl SparseVoxelOctreeResource {
+	// Blocks until the allocated tiles and the values of the root were copied back, only meant for debugging.
+	// The count includes tiles past the capacity that couldn't be allocated
+	pub fn read_root(&self, renderer: &Renderer) -> Result<(u32, [[u8; 4]; 8]), wgpu::BufferAsyncError> {
+		let mut encoder = renderer
+			.device()
+			.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+				label: Some("Octree Readback Encoder"),
+			});
+		let tile_count = BufferReadback::new(renderer.device(), &mut encoder, &self.tile_count, self.tile_count.size());
+		let root = BufferReadback::new(renderer.device(), &mut encoder, &self.values, 8 * std::mem::size_of::<u32>() as u64);
+		renderer.queue().submit(Some(encoder.finish()));
+
+		let tile_count: u32 = bytemuck::pod_read_unaligned(&tile_count.read(renderer.device())?);
+		let root: [[u8; 4]; 8] = bytemuck::pod_read_unaligned(&root.read(renderer.device())?);
+
+		Ok((tile_count, root))
+	}
+}
+
+impl crate::Resource for SparseVoxelOctreeResource {
+	fn updated_settings(&mut self, _renderer: &Renderer) {}
+}
+
+// Rebuilds the SparseVoxelOctreeResource from VoxelsResource::fragments alone, so the VoxelizationPass needs a
+// VoxelizationSettings::fragment_capacity and can leave VoxelizationSettings::dense_volume off.
+// The leaves are lit from the scene's lights with shadows traced through the octree, without bounces
+pub struct SparseVoxelOctreePass {
+	subdivide_pipeline: wgpu::ComputePipeline,
+	write_leaves_pipeline: wgpu::ComputePipeline,
+	light_leaves_pipeline: wgpu::ComputePipeline,
+	mip_pipeline: wgpu::ComputePipeline,
+
+	octree_bind_group_layout: wgpu::BindGroupLayout,
+	fragments_bind_group_layout: wgpu::BindGroupLayout,
+	lights_bind_group_layout: wgpu::BindGroupLayout,
+	lights: crate::lights::LightsBuffer,
+
+	level_buffers: Vec<wgpu::Buffer>,
+}
+
+impl SparseVoxelOctreePass {
+	pub fn new(renderer: &Renderer, descriptor: &SparseVoxelOctreeDescriptor) -> Self {
+		assert!(descriptor.depth >= 1 && descriptor.depth <= MAX_OCTREE_DEPTH);
+
+		let node_pool_size = descriptor.tile_capacity.max(1) as u64 * 8 * std::mem::size_of::<u32>() as u64;
+		let new_pool = |label| {
+			renderer.device().create_buffer(&wgpu::BufferDescriptor {
+				label: Some(label),
+				size: node_pool_size,
+				usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+				mapped_at_creation: false,
+			})
+		};
+
+		let octree_resource = SparseVoxelOctreeResource {
+			nodes: new_pool("Octree Node Pool Buffer"),
+			values: new_pool("Octree Value Pool Buffer"),
+			tile_count: renderer.device().create_buffer(&wgpu::BufferDescriptor {
+				label: Some("Octree Tile Count Buffer"),
+				size: std::mem::size_of::<u32>() as u64,
+				usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+				mapped_at_creation: false,
+			}),
+			descriptor: *descriptor,
+			octree_buffer: renderer.device().create_buffer(&wgpu::BufferDescriptor {
+				label: Some("Octree Buffer"),
+				size: std::mem::size_of::<SparseVoxelOctreeUniform>() as u64,
+				usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+				mapped_at_creation: false,
+			}),
+		};
+
+		renderer.insert_resource(octree_resource);
+
+		let shader = renderer
+			.device()
+			.create_shader_module(wgpu::ShaderModuleDescriptor {
+				label: Some("Octree Build shader"),
+				source: wgpu::ShaderSource::Wgsl(
+					format!(
+						"{}\n{}\n{}",
+						FRAGMENT_SHADER_PRELUDE,
+						include_str!("../shaders/_lights.wgsl"),
+						include_str!("../shaders/svo_build.wgsl")
+					)
+					.into(),
+				),
+			});
+
+		let uniform_entry = |binding| wgpu::BindGroupLayoutEntry {
+			binding,
+			visibility: wgpu::ShaderStages::COMPUTE,
+			ty: wgpu::BindingType::Buffer {
+				ty: wgpu::BufferBindingType::Uniform,
+				has_dynamic_offset: false,
+				min_binding_size: None,
+			},
+			count: None,
+		};
+
+		let storage_entry = |binding| wgpu::BindGroupLayoutEntry {
+			binding,
+			visibility: wgpu::ShaderStages::COMPUTE,
+			ty: wgpu::BindingType::Buffer {
+				ty: wgpu::BufferBindingType::Storage { read_only: false },
+				has_dynamic_offset: false,
+				min_binding_size: None,
+			},
+			count: None,
+		};
+
+
+		let octree_bind_group_layout =
+			renderer
+				.device()
+				.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+					label: Some("Octree BindGroup Layout"),
+					entries: &[
+						uniform_entry(0),
+						storage_entry(1),
+						storage_entry(2),
+						storage_entry(3),
+						uniform_entry(4),
+					],
+				});
+
+		let fragments_bind_group_layout =
+			renderer
+				.device()
+				.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+					label: Some("Voxel Fragments BindGroup Layout"),
+					entries: &[storage_entry(0), storage_entry(1)],
+				});
+
+		let lights_bind_group_layout =
+			renderer
+				.device()
+				.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+					label: Some("Octree Lights BindGroup Layout"),
+					entries: &[
+						uniform_entry(0),
+						wgpu::BindGroupLayoutEntry {
+							binding: 1,
+							visibility: wgpu::ShaderStages::COMPUTE,
+							ty: wgpu::BindingType::Buffer {
+								ty: wgpu::BufferBindingType::Storage { read_only: true },
+								has_dynamic_offset: false,
+								min_binding_size: None,
+							},
+							count: None,
+						},
+					],
+				});
+
+		let layout = renderer
+			.device()
+			.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+				label: Some("Octree Build Layout"),
+				bind_group_layouts: &[
+					&octree_bind_group_layout,
+					&fragments_bind_group_layout,
+					&lights_bind_group_layout,
+				],
+				push_constant_ranges: &[],
+			});
+
+		let new_pipeline = |entry_point| {
+			renderer
+				.device()
+				.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+					label: Some("Octree Build pipeline"),
+					layout: Some(&layout),
+					module: &shader,
+					entry_point,
+				})
+		};
+
+		let level_buffers = (0..descriptor.depth)
+			.map(|level| {
+				renderer
+					.device()
+					.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+						label: Some("Octree Level Buffer"),
+						contents: bytemuck::cast_slice(&[OctreeLevelUniform {
+							level,
+							_padding: [0; 3],
+						}]),
+						usage: wgpu::BufferUsages::UNIFORM,
+					})
+			})
+			.collect();
+
+		Self {
+			subdivide_pipeline: new_pipeline("subdivide"),
+			write_leaves_pipeline: new_pipeline("write_leaves"),
+			light_leaves_pipeline: new_pipeline("light_leaves"),
+			mip_pipeline: new_pipeline("mip"),
+
+			octree_bind_group_layout,
+			fragments_bind_group_layout,
+			lights_bind_group_layout,
+			lights: crate::lights::LightsBuffer::new(renderer.device()),
+
+			level_buffers,
+		}
+	}
+}
+
+impl RenderPassTrait for SparseVoxelOctreePass {
+	fn execute<'manager>(
+		&mut self,
+		command_encoder: &'manager CommandEncoder,
+		global_resources: &mut crate::ResourceManagerHandle<'manager>,
+	) -> Option<wgpu::CommandBuffer> {
+		let voxels_resource = global_resources.get_resource::<VoxelsResource>().unwrap();
+		let fragment_list = voxels_resource
+			.fragments
+			.as_ref()
+			.expect("The SparseVoxelOctreePass needs VoxelizationSettings::fragment_capacity");
+		let octree_resource = global_resources.get_resource::<SparseVoxelOctreeResource>().unwrap();
+		let depth = octree_resource.descriptor.depth;
+
+		let volume = voxels_resource.volume;
+		command_encoder.queue().write_buffer(
+			&octree_resource.octree_buffer,
+			0,
+			bytemuck::cast_slice(&[SparseVoxelOctreeUniform {
+				min: volume.min.into(),
+				depth,
+				size: volume.size().into(),
+				voxel_size: glm::comp_max(&volume.size()) / octree_resource.descriptor.resolution() as f32,
+				tile_capacity: octree_resource.descriptor.tile_capacity,
+				_padding: [0; 3],
+			}]),
+		);
+		// Only the root is allocated up front
+		command_encoder
+			.queue()
+			.write_buffer(&octree_resource.tile_count, 0, bytemuck::cast_slice(&[1u32]));
+
+		let octree_bind_groups: Vec<_> = self
+			.level_buffers
+			.iter()
+			.map(|level_buffer| {
+				command_encoder
+					.device()
+					.create_bind_group(&wgpu::BindGroupDescriptor {
+						label: Some("Octree Bind Group"),
+						layout: &self.octree_bind_group_layout,
+						entries: &[
+							wgpu::BindGroupEntry {
+								binding: 0,
+								resource: octree_resource.octree_buffer.as_entire_binding(),
+							},
+							wgpu::BindGroupEntry {
+								binding: 1,
+								resource: octree_resource.nodes.as_entire_binding(),
+							},
+							wgpu::BindGroupEntry {
+								binding: 2,
+								resource: octree_resource.values.as_entire_binding(),
+							},
+							wgpu::BindGroupEntry {
+								binding: 3,
+								resource: octree_resource.tile_count.as_entire_binding(),
+							},
+							wgpu::BindGroupEntry {
+								binding: 4,
+								resource: level_buffer.as_entire_binding(),
+							},
+						],
+					})
+			})
+			.collect();
+
+		let fragments_bind_group = command_encoder
+			.device()
+			.create_bind_group(&wgpu::BindGroupDescriptor {
+				label: Some("Voxel Fragments Bind Group"),
+				layout: &self.fragments_bind_group_layout,
+				entries: &[
+					wgpu::BindGroupEntry {
+						binding: 0,
+						resource: fragment_list.fragments.as_entire_binding(),
+					},
+					wgpu::BindGroupEntry {
+						binding: 1,
+						resource: fragment_list.counter.as_entire_binding(),
+					},
+				],
+			});
+
+		let lights_buffer = self.lights.update(command_encoder.device(), command_encoder.queue(), &command_encoder.get_lights());
+
+		let lights_bind_group = command_encoder
+			.device()
+			.create_bind_group(&wgpu::BindGroupDescriptor {
+				label: Some("Octree Lights Bind Group"),
+				layout: &self.lights_bind_group_layout,
+				entries: &[
+					wgpu::BindGroupEntry {
+						binding: 0,
+						resource: voxels_resource.volume_buffer.as_entire_binding(),
+					},
+					wgpu::BindGroupEntry {
+						binding: 1,
+						resource: lights_buffer.as_entire_binding(),
+					},
+				],
+			});
+
+		let mut encoder = command_encoder
+			.device()
+			.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+				label: Some("Octree Build Encoder"),
+			});
+
+		encoder.clear_buffer(&octree_resource.nodes, 0, None);
+		encoder.clear_buffer(&octree_resource.values, 0, None);
+
+		{
+			let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+				label: Some("Octree Build pass"),
+				timestamp_writes: None,
+			});
+
+			compute_pass.set_bind_group(1, &fragments_bind_group, &[]);
+			compute_pass.set_bind_group(2, &lights_bind_group, &[]);
+
+			let indirect = &fragment_list.indirect;
+
+			// Each level only exists once its parent was allocated, so they are built top down
+			compute_pass.set_pipeline(&self.subdivide_pipeline);
+			for bind_group in &octree_bind_groups[..depth as usize - 1] {
+				compute_pass.set_bind_group(0, bind_group, &[]);
+				compute_pass.dispatch_workgroups_indirect(indirect, 0);
+			}
+
+			compute_pass.set_bind_group(0, &octree_bind_groups[0], &[]);
+			compute_pass.set_pipeline(&self.write_leaves_pipeline);
+			compute_pass.dispatch_workgroups_indirect(indirect, 0);
+
+			// Every leaf is occupied before any of them traces its shadows
+			compute_pass.set_pipeline(&self.light_leaves_pipeline);
+			compute_pass.dispatch_workgroups_indirect(indirect, 0);
+
+			// And filtered bottom up
+			compute_pass.set_pipeline(&self.mip_pipeline);
+			for bind_group in octree_bind_groups[..depth as usize - 1].iter().rev() {
+				compute_pass.set_bind_group(0, bind_group, &[]);
+				compute_pass.dispatch_workgroups_indirect(indirect, 0);
+			}
+		}
+
+		Some(encoder.finish())
+	}
+}
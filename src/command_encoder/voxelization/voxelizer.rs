@@ -0,0 +1,519 @@
+use crate::command_encoder::*;
+use wgpu_helper::bind_group::BindGroupType;
+
+use crate::Renderer;
+
+use super::{VoxelFragmentList, VoxelWriteMode, FRAGMENT_SHADER_PRELUDE};
+
+// Nothing is written to the target, it only sets the raster size and sample count
+const RASTER_TARGET_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+const RASTER_SAMPLE_COUNT: u32 = 4;
+
+// Limits the voxelizer to a box of the volume's grid, stores land `z_offset` texels further along z.
+// When append_fragments is set every stored voxel also goes to the bound fragment list,
+// without write_volume that list is all that gets written
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct VoxelizationRegionUniform {
+	pub min: [i32; 3],
+	pub z_offset: u32,
+	pub max: [i32; 3],
+	pub append_fragments: u32,
+	pub write_volume: u32,
+	pub _padding: [u32; 3],
+}
+
+impl VoxelizationRegionUniform {
+	pub fn new(min: [i32; 3], max: [i32; 3], z_offset: u32) -> Self {
+		Self {
+			min,
+			z_offset,
+			max,
+			append_fragments: 0,
+			write_volume: 1,
+			_padding: [0; 3],
+		}
+	}
+
+	pub fn size(&self) -> [u32; 3] {
+		[0, 1, 2].map(|i| (self.max[i] - self.min[i]).max(0) as u32)
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct MeshSelection {
+	// Static and dynamic meshes alike when None
+	pub is_static: Option<bool>,
+	// Only meshes whose world bounds touch this box
+	pub overlapping: Option<crate::mesh::Aabb>,
+}
+
+impl MeshSelection {
+	pub const ALL: Self = Self {
+		is_static: None,
+		overlapping: None,
+	};
+
+	fn contains(&self, mesh: &crate::mesh::Mesh) -> bool {
+		self.is_static.is_none_or(|is_static| mesh.is_static == is_static)
+			&& self.overlapping.is_none_or(|bounds| mesh.bounds.intersects(&bounds))
+	}
+}
+
+// The triangle voxelizer shared by the dense volume and the clipmap
+pub(crate) struct Voxelizer {
+	pipeline: wgpu::ComputePipeline,
+	clear_pipeline: wgpu::ComputePipeline,
+	raster_pipeline: wgpu::RenderPipeline,
+	// The side of the square raster target and its multisampled view
+	raster_target: Option<(u32, wgpu::TextureView)>,
+	voxels_bind_group_layout: wgpu::BindGroupLayout,
+	// Bound in place of a fragment list when fragments aren't appended
+	empty_fragments: VoxelFragmentList,
+	empty_accumulator: wgpu::Buffer,
+
+	// Not every backend can compile compare-and-swap, so the averaging pipelines wait until they are used
+	layout: wgpu::PipelineLayout,
+	clear_layout: wgpu::PipelineLayout,
+	average_shader: wgpu::ShaderModule,
+	average_pipelines: Option<AveragePipelines>,
+}
+
+struct AveragePipelines {
+	voxelize: wgpu::ComputePipeline,
+	rasterize: wgpu::RenderPipeline,
+	resolve: wgpu::ComputePipeline,
+}
+
+impl Voxelizer {
+	pub fn new(renderer: &Renderer) -> Self {
+		let new_shader = |label, store_color| {
+			renderer
+				.device()
+				.create_shader_module(wgpu::ShaderModuleDescriptor {
+					label: Some(label),
+					source: wgpu::ShaderSource::Wgsl(
+						format!(
+							"{}\n{}\n{}\n{}",
+							FRAGMENT_SHADER_PRELUDE,
+							include_str!("../shaders/voxelization.wgsl"),
+							include_str!("../shaders/voxelization_raster.wgsl"),
+							store_color
+						)
+						.into(),
+					),
+				})
+		};
+
+		let shader = new_shader("Voxelizer shader", include_str!("../shaders/voxel_store_last_writer.wgsl"));
+		let average_shader = new_shader(
+			"Averaging Voxelizer shader",
+			include_str!("../shaders/voxel_store_average.wgsl"),
+		);
+
+		let storage_entry = |binding, format| wgpu::BindGroupLayoutEntry {
+			binding,
+			visibility: wgpu::ShaderStages::union(wgpu::ShaderStages::COMPUTE, wgpu::ShaderStages::FRAGMENT),
+			ty: wgpu::BindingType::StorageTexture {
+				access: wgpu::StorageTextureAccess::WriteOnly,
+				format,
+				view_dimension: wgpu::TextureViewDimension::D3,
+			},
+			count: None,
+		};
+
+		let uniform_entry = |binding| wgpu::BindGroupLayoutEntry {
+			binding,
+			visibility: wgpu::ShaderStages::union(wgpu::ShaderStages::COMPUTE, wgpu::ShaderStages::VERTEX_FRAGMENT),
+			ty: wgpu::BindingType::Buffer {
+				ty: wgpu::BufferBindingType::Uniform,
+				has_dynamic_offset: false,
+				min_binding_size: None,
+			},
+			count: None,
+		};
+
+		// Also used for the colour accumulator of VoxelWriteMode::Average
+		let fragments_entry = |binding| wgpu::BindGroupLayoutEntry {
+			binding,
+			visibility: wgpu::ShaderStages::union(wgpu::ShaderStages::COMPUTE, wgpu::ShaderStages::FRAGMENT),
+			ty: wgpu::BindingType::Buffer {
+				ty: wgpu::BufferBindingType::Storage { read_only: false },
+				has_dynamic_offset: false,
+				min_binding_size: None,
+			},
+			count: None,
+		};
+
+		let voxels_bind_group_layout =
+			renderer
+				.device()
+				.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+					label: Some("Voxels BindGroup Layout"),
+					entries: &[
+						uniform_entry(0),
+						storage_entry(1, wgpu::TextureFormat::Rgba8Unorm),
+						storage_entry(2, wgpu::TextureFormat::Rgba8Snorm),
+						storage_entry(3, wgpu::TextureFormat::Rgba8Unorm),
+						storage_entry(4, wgpu::TextureFormat::Rgba8Unorm),
+						uniform_entry(5),
+						fragments_entry(6),
+						fragments_entry(7),
+						fragments_entry(8),
+					],
+				});
+
+		let layout = renderer
+			.device()
+			.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+				label: Some("Voxelizer Layout"),
+				bind_group_layouts: &[
+					&voxels_bind_group_layout,
+					crate::mesh::ComputeMeshBindGroup::get_bind_group_layout(renderer.device()),
+					crate::ModelBindGroup::get_bind_group_layout(renderer.device()),
+					crate::MaterialBindGroup::get_bind_group_layout(renderer.device()),
+				],
+				push_constant_ranges: &[],
+			});
+
+		let pipeline = renderer
+			.device()
+			.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+				label: Some("Voxelizer pipeline"),
+				layout: Some(&layout),
+				module: &shader,
+				entry_point: "main",
+			});
+
+		let clear_layout = renderer
+			.device()
+			.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+				label: Some("Voxel Clear Layout"),
+				bind_group_layouts: &[&voxels_bind_group_layout],
+				push_constant_ranges: &[],
+			});
+
+		let clear_pipeline = renderer
+			.device()
+			.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+				label: Some("Voxel Clear pipeline"),
+				layout: Some(&clear_layout),
+				module: &shader,
+				entry_point: "clear",
+			});
+
+		let raster_pipeline = new_raster_pipeline(renderer.device(), &layout, &shader);
+
+		Self {
+			pipeline,
+			clear_pipeline,
+			raster_pipeline,
+			raster_target: None,
+			voxels_bind_group_layout,
+			empty_fragments: VoxelFragmentList::new(renderer.device(), 1),
+			empty_accumulator: new_color_accumulator(renderer.device(), 1),
+
+			layout,
+			clear_layout,
+			average_shader,
+			average_pipelines: None,
+		}
+	}
+
+	// Has to be called before encoding with VoxelWriteMode::Average
+	pub fn prepare_average(&mut self, device: &wgpu::Device) {
+		if self.average_pipelines.is_some() {
+			return;
+		}
+
+		let new_pipeline = |layout, entry_point| {
+			device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+				label: Some("Averaging Voxelizer pipeline"),
+				layout: Some(layout),
+				module: &self.average_shader,
+				entry_point,
+			})
+		};
+
+		self.average_pipelines = Some(AveragePipelines {
+			voxelize: new_pipeline(&self.layout, "main"),
+			rasterize: new_raster_pipeline(device, &self.layout, &self.average_shader),
+			resolve: new_pipeline(&self.clear_layout, "resolve_average"),
+		});
+	}
+
+	// Has to be called before encode_rasterized, with the largest side of the regions to rasterize
+	pub fn prepare_rasterization(&mut self, device: &wgpu::Device, extent: u32) {
+		if matches!(&self.raster_target, Some((size, _)) if *size == extent) {
+			return;
+		}
+
+		let texture = device.create_texture(&wgpu::TextureDescriptor {
+			label: Some("Voxelizer Raster Target"),
+			size: wgpu::Extent3d {
+				width: extent,
+				height: extent,
+				depth_or_array_layers: 1,
+			},
+			mip_level_count: 1,
+			sample_count: RASTER_SAMPLE_COUNT,
+			dimension: wgpu::TextureDimension::D2,
+			format: RASTER_TARGET_FORMAT,
+			usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+			view_formats: &[],
+		});
+
+		self.raster_target = Some((extent, texture.create_view(&wgpu::TextureViewDescriptor::default())));
+	}
+
+	// Views are color, normal, emissive and material, all with a single mip level
+	pub fn new_bind_group(
+		&self,
+		device: &wgpu::Device,
+		volume_buffer: &wgpu::Buffer,
+		region_buffer: &wgpu::Buffer,
+		views: [&wgpu::TextureView; 4],
+		fragments: Option<&VoxelFragmentList>,
+		color_accumulator: Option<&wgpu::Buffer>,
+	) -> wgpu::BindGroup {
+		let fragments = fragments.unwrap_or(&self.empty_fragments);
+		let color_accumulator = color_accumulator.unwrap_or(&self.empty_accumulator);
+
+		device.create_bind_group(&wgpu::BindGroupDescriptor {
+			label: Some("Voxels Bind Group"),
+			layout: &self.voxels_bind_group_layout,
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: volume_buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 1,
+					resource: wgpu::BindingResource::TextureView(views[0]),
+				},
+				wgpu::BindGroupEntry {
+					binding: 2,
+					resource: wgpu::BindingResource::TextureView(views[1]),
+				},
+				wgpu::BindGroupEntry {
+					binding: 3,
+					resource: wgpu::BindingResource::TextureView(views[2]),
+				},
+				wgpu::BindGroupEntry {
+					binding: 4,
+					resource: wgpu::BindingResource::TextureView(views[3]),
+				},
+				wgpu::BindGroupEntry {
+					binding: 5,
+					resource: region_buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 6,
+					resource: fragments.fragments.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 7,
+					resource: fragments.counter.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 8,
+					resource: color_accumulator.as_entire_binding(),
+				},
+			],
+		})
+	}
+
+	pub fn encode_clear(
+		&self,
+		encoder: &mut wgpu::CommandEncoder,
+		voxels_bind_group: &wgpu::BindGroup,
+		region: &VoxelizationRegionUniform,
+	) {
+		encode_region(encoder, &self.clear_pipeline, voxels_bind_group, region);
+	}
+
+	// Moves the averages of VoxelWriteMode::Average into the colour volume
+	pub fn encode_resolve(
+		&self,
+		encoder: &mut wgpu::CommandEncoder,
+		voxels_bind_group: &wgpu::BindGroup,
+		region: &VoxelizationRegionUniform,
+	) {
+		let average_pipelines = self.average_pipelines.as_ref().expect("Voxelizer::prepare_average wasn't called");
+		encode_region(encoder, &average_pipelines.resolve, voxels_bind_group, region);
+	}
+
+	// Voxelizes every selected mesh once for each of the bind groups
+	pub fn encode(
+		&self,
+		command_encoder: &CommandEncoder,
+		encoder: &mut wgpu::CommandEncoder,
+		voxels_bind_groups: &[&wgpu::BindGroup],
+		write_mode: VoxelWriteMode,
+		selection: MeshSelection,
+	) {
+		let pipeline = match write_mode {
+			VoxelWriteMode::LastWriter => &self.pipeline,
+			VoxelWriteMode::Average => {
+				&self.average_pipelines.as_ref().expect("Voxelizer::prepare_average wasn't called").voxelize
+			}
+		};
+
+		let meshes = command_encoder.get_meshes();
+		let materials = command_encoder.get_materials();
+		let max_workgroups = command_encoder.device().limits().max_compute_workgroups_per_dimension;
+
+		for voxels_bind_group in voxels_bind_groups {
+			let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+				label: Some("Voxelization pass"),
+				timestamp_writes: None,
+			});
+
+			compute_pass.set_pipeline(pipeline);
+			compute_pass.set_bind_group(0, voxels_bind_group, &[]);
+
+			for mesh in meshes.meshes.iter().filter(|mesh| selection.contains(mesh)) {
+				compute_pass.set_bind_group(2, &mesh.model_bind_group, &[]);
+				for primitive in mesh.primitives.iter() {
+					compute_pass.set_bind_group(
+						3,
+						&materials.materials.get(&primitive.material).unwrap().bind_group,
+						&[],
+					);
+					compute_pass.set_bind_group(1, &mesh.compute_bind_group, &[primitive.uniform_offset]);
+
+					let triangle_count = (primitive.index.end - primitive.index.start) / 3;
+					let workgroups = triangle_count.div_ceil(64);
+					let width = workgroups.min(max_workgroups);
+					if width > 0 {
+						compute_pass.dispatch_workgroups(width, workgroups.div_ceil(width), 1);
+					}
+				}
+			}
+		}
+	}
+
+	// Rasterizes every selected mesh into the region of the bind group, see voxelization_raster.wgsl
+	pub fn encode_rasterized(
+		&self,
+		command_encoder: &CommandEncoder,
+		encoder: &mut wgpu::CommandEncoder,
+		voxels_bind_group: &wgpu::BindGroup,
+		region: &VoxelizationRegionUniform,
+		write_mode: VoxelWriteMode,
+		selection: MeshSelection,
+	) {
+		let pipeline = match write_mode {
+			VoxelWriteMode::LastWriter => &self.raster_pipeline,
+			VoxelWriteMode::Average => {
+				&self.average_pipelines.as_ref().expect("Voxelizer::prepare_average wasn't called").rasterize
+			}
+		};
+		let (_, raster_view) = self
+			.raster_target
+			.as_ref()
+			.expect("Voxelizer::prepare_rasterization wasn't called");
+
+		let meshes = command_encoder.get_meshes();
+		let materials = command_encoder.get_materials();
+
+		let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+			label: Some("Voxelization Raster pass"),
+			color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+				view: raster_view,
+				resolve_target: None,
+				ops: wgpu::Operations {
+					load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+					store: wgpu::StoreOp::Discard,
+				},
+			})],
+			depth_stencil_attachment: None,
+			timestamp_writes: None,
+			occlusion_query_set: None,
+		});
+
+		// One pixel per voxel across the region's largest side
+		let extent = region.size().into_iter().max().unwrap_or(0) as f32;
+		render_pass.set_viewport(0.0, 0.0, extent, extent, 0.0, 1.0);
+
+		render_pass.set_pipeline(pipeline);
+		render_pass.set_bind_group(0, voxels_bind_group, &[]);
+
+		for mesh in meshes.meshes.iter().filter(|mesh| selection.contains(mesh)) {
+			render_pass.set_bind_group(2, &mesh.model_bind_group, &[]);
+			for primitive in mesh.primitives.iter() {
+				render_pass.set_bind_group(
+					3,
+					&materials.materials.get(&primitive.material).unwrap().bind_group,
+					&[],
+				);
+				render_pass.set_bind_group(1, &mesh.compute_bind_group, &[primitive.uniform_offset]);
+				render_pass.draw(0..primitive.index.end - primitive.index.start, 0..1);
+			}
+		}
+	}
+}
+
+fn encode_region(
+	encoder: &mut wgpu::CommandEncoder,
+	pipeline: &wgpu::ComputePipeline,
+	voxels_bind_group: &wgpu::BindGroup,
+	region: &VoxelizationRegionUniform,
+) {
+	let size = region.size().map(|size| size.div_ceil(4));
+
+	let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+		label: Some("Voxel region pass"),
+		timestamp_writes: None,
+	});
+
+	compute_pass.set_pipeline(pipeline);
+	compute_pass.set_bind_group(0, voxels_bind_group, &[]);
+	compute_pass.dispatch_workgroups(size[0], size[1], size[2]);
+}
+
+// One word per voxel of the region, see voxel_store_average.wgsl
+pub(crate) fn new_color_accumulator(device: &wgpu::Device, voxel_count: u64) -> wgpu::Buffer {
+	device.create_buffer(&wgpu::BufferDescriptor {
+		label: Some("Voxel Color Accumulator Buffer"),
+		size: voxel_count * std::mem::size_of::<u32>() as u64,
+		usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+		mapped_at_creation: false,
+	})
+}
+
+fn new_raster_pipeline(
+	device: &wgpu::Device,
+	layout: &wgpu::PipelineLayout,
+	module: &wgpu::ShaderModule,
+) -> wgpu::RenderPipeline {
+	device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+		label: Some("Voxelizer Raster pipeline"),
+		layout: Some(layout),
+		vertex: wgpu::VertexState {
+			module,
+			entry_point: "raster_vertex",
+			buffers: &[],
+		},
+		fragment: Some(wgpu::FragmentState {
+			module,
+			entry_point: "raster_fragment",
+			targets: &[Some(wgpu::ColorTargetState {
+				format: RASTER_TARGET_FORMAT,
+				blend: None,
+				write_mask: wgpu::ColorWrites::empty(),
+			})],
+		}),
+		primitive: wgpu::PrimitiveState {
+			topology: wgpu::PrimitiveTopology::TriangleList,
+			cull_mode: None,
+			..Default::default()
+		},
+		depth_stencil: None,
+		multisample: wgpu::MultisampleState {
+			count: RASTER_SAMPLE_COUNT,
+			..Default::default()
+		},
+		multiview: None,
+	})
+}
@@ -18,6 +18,7 @@ pub mod command_encoder;
 pub mod lights;
 pub mod load_gltf;
 pub mod mesh;
+pub mod readback;
 pub mod resources;
 mod scene;
 pub mod transform;
@@ -99,6 +100,28 @@ pub const MATERIAL_BIND_GROUP_LAYOUT: &'static wgpu::BindGroupLayoutDescriptor =
 				ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
 				count: None,
 			},
+			wgpu::BindGroupLayoutEntry {
+				binding: 6,
+				visibility: wgpu::ShaderStages::union(
+					wgpu::ShaderStages::FRAGMENT,
+					wgpu::ShaderStages::COMPUTE,
+				),
+				ty: wgpu::BindingType::Texture {
+					sample_type: wgpu::TextureSampleType::Float { filterable: true },
+					view_dimension: wgpu::TextureViewDimension::D2,
+					multisampled: false,
+				},
+				count: None,
+			},
+			wgpu::BindGroupLayoutEntry {
+				binding: 7,
+				visibility: wgpu::ShaderStages::union(
+					wgpu::ShaderStages::FRAGMENT,
+					wgpu::ShaderStages::COMPUTE,
+				),
+				ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+				count: None,
+			},
 		],
 	};
 
@@ -111,6 +134,8 @@ pub struct MaterialBindGroup<'a> {
 	pub s_metal: &'a wgpu::Sampler,
 	pub t_normal: &'a wgpu::TextureView,
 	pub s_normal: &'a wgpu::Sampler,
+	pub t_emissive: &'a wgpu::TextureView,
+	pub s_emissive: &'a wgpu::Sampler,
 }
 
 pub const MODEL_BIND_GROUP_LAYOUT: &'static wgpu::BindGroupLayoutDescriptor =
@@ -167,10 +192,31 @@ impl Renderer {
 		}
 	}
 
+	// Fails unless frames of the format can be read back, see readback::is_readable_format
+	pub async fn new_headless(
+		resolution: [u32; 2],
+		format: wgpu::TextureFormat,
+		settings: RendererSettings,
+	) -> Result<Self, readback::ReadbackError> {
+		if !readback::is_readable_format(format) {
+			return Err(readback::ReadbackError::UnreadableFormat(format));
+		}
+
+		let renderer = InternalRenderer::new_headless(resolution, format, settings).await;
+
+		Ok(Self {
+			renderer: Rc::new(renderer),
+		})
+	}
+
 	pub fn device(&self) -> &wgpu::Device {
 		&self.renderer.device
 	}
 
+	pub fn queue(&self) -> &wgpu::Queue {
+		&self.renderer.queue
+	}
+
 	pub fn resize(&self, width: u32, height: u32) {
 		self.renderer.resize(width, height);
 	}
@@ -213,10 +259,67 @@ impl Renderer {
 		}
 	}
 
+	pub fn add_point_light(&self, light: lights::PointLight) {
+		let id = self.renderer.new_id();
+		self.renderer.lights.insert(id, light);
+	}
+
+	// Meshes loaded with is_static = false, the ones that may be moved with set_mesh_transform
+	pub fn dynamic_meshes(&self) -> Vec<Id> {
+		self.renderer
+			.meshes
+			.iter()
+			.filter(|mesh| !mesh.is_static)
+			.map(|mesh| *mesh.key())
+			.collect()
+	}
+
+	// Replaces the model matrix of a mesh, static meshes keep their voxels until they are voxelized again
+	pub fn set_mesh_transform(&self, id: Id, transform: &glm::Mat4) {
+		let Some(mut mesh) = self.renderer.meshes.get_mut(&id) else {
+			return;
+		};
+
+		let normal_matrix = glm::mat3_to_mat4(&glm::inverse_transpose(glm::mat4_to_mat3(transform)));
+
+		self.renderer
+			.queue
+			.write_buffer(&mesh.transform_buffer, 0, bytemuck::cast_slice(transform.as_slice()));
+		self.renderer
+			.queue
+			.write_buffer(&mesh.normal_buffer, 0, bytemuck::cast_slice(normal_matrix.as_slice()));
+
+		mesh.bounds = mesh.local_bounds.transform(transform);
+		mesh.transform_generation = mesh.transform_generation.wrapping_add(1);
+	}
+
 	pub(crate) fn get_resolution(&self) -> [u32; 2] {
 		self.renderer.get_resolution()
 	}
 
+	// Only offscreen targets outlive a frame, swapchain images have to be read with CommandEncoder::finish_and_read
+	pub fn read_frame(&self) -> Option<Result<image::RgbaImage, readback::ReadbackError>> {
+		let texture = match &*self.renderer.target.lock().unwrap() {
+			RenderTarget::Surface(_) => return None,
+			RenderTarget::Offscreen(texture) => Arc::clone(texture),
+		};
+
+		let mut encoder = self
+			.device()
+			.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+				label: Some("Frame Readback Encoder"),
+			});
+
+		let readback = match readback::TextureReadback::new(self.device(), &mut encoder, &texture) {
+			Ok(readback) => readback,
+			Err(error) => return Some(Err(error)),
+		};
+
+		self.renderer.queue.submit(Some(encoder.finish()));
+
+		Some(readback.read(self.device()))
+	}
+
 	pub(crate) fn get_resource_manager(&self) -> ResourceManagerHandle {
 		self.renderer.resource_manager.get_handle()
 	}
@@ -225,6 +328,10 @@ impl Renderer {
 		self.renderer.resource_manager.insert_resource(resource)
 	}
 
+	pub fn get_resource<T: crate::Resource>(&self) -> Option<ResourceHandle<'_, T>> {
+		self.renderer.resource_manager.get_resource()
+	}
+
 	pub(crate) fn get_handle(&self) -> Rc<InternalRenderer> {
 		Rc::clone(&self.renderer)
 	}
@@ -240,8 +347,13 @@ impl Renderer {
 	}
 }
 
+pub(crate) enum RenderTarget {
+	Surface(wgpu::Surface<'static>),
+	Offscreen(Arc<wgpu::Texture>),
+}
+
 pub(crate) struct InternalRenderer {
-	pub surface: wgpu::Surface<'static>,
+	pub target: Mutex<RenderTarget>,
 	pub device: wgpu::Device,
 	pub queue: wgpu::Queue,
 	pub config: Mutex<wgpu::SurfaceConfiguration>,
@@ -252,6 +364,7 @@ pub(crate) struct InternalRenderer {
 	pub meshes: DashMap<Id, mesh::Mesh>,
 	pub materials: DashMap<Id, mesh::Material>,
 	pub cameras: DashMap<Id, camera::InternalCamera>,
+	pub lights: DashMap<Id, lights::PointLight>,
 	pub resource_manager: ResourceManager,
 	current_id: core::sync::atomic::AtomicU64,
 }
@@ -280,13 +393,99 @@ impl InternalRenderer {
 			.await
 			.unwrap();
 
+		let (device, queue) = Self::request_device(&adapter).await;
+
+		let capabilities = surface.get_capabilities(&adapter);
+
+		// Config for surface
+		let config = wgpu::SurfaceConfiguration {
+			// COPY_SRC allows frames to be read back when the platform supports it
+			usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+				| (capabilities.usages & wgpu::TextureUsages::COPY_SRC),
+			format: capabilities.formats[0],
+			width: size.width,
+			height: size.height,
+			present_mode: wgpu::PresentMode::Fifo,
+			alpha_mode: wgpu::CompositeAlphaMode::Auto,
+			desired_maximum_frame_latency: 2,
+			view_formats: vec![],
+		};
+		surface.configure(&device, &config);
+
+		Self::from_parts(RenderTarget::Surface(surface), device, queue, config, settings)
+	}
+
+	pub async fn new_headless(
+		resolution: [u32; 2],
+		format: wgpu::TextureFormat,
+		settings: RendererSettings,
+	) -> Self {
+		let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+			backends: wgpu::Backends::all(),
+			..Default::default()
+		});
+
+		// Any adapter will do without a surface, fall back to software rasterizers such as lavapipe or llvmpipe
+		let mut adapter = instance
+			.request_adapter(&wgpu::RequestAdapterOptions {
+				power_preference: wgpu::PowerPreference::HighPerformance,
+				compatible_surface: None,
+				force_fallback_adapter: false,
+			})
+			.await;
+
+		if adapter.is_none() {
+			adapter = instance
+				.request_adapter(&wgpu::RequestAdapterOptions {
+					power_preference: wgpu::PowerPreference::None,
+					compatible_surface: None,
+					force_fallback_adapter: true,
+				})
+				.await;
+		}
+
+		let adapter = adapter.unwrap();
+
+		let (device, queue) = Self::request_device(&adapter).await;
+
+		// Not used to configure a surface, only keeps the resolution and format of the offscreen target
+		let config = wgpu::SurfaceConfiguration {
+			usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+			format,
+			width: resolution[0],
+			height: resolution[1],
+			present_mode: wgpu::PresentMode::Fifo,
+			alpha_mode: wgpu::CompositeAlphaMode::Auto,
+			desired_maximum_frame_latency: 2,
+			view_formats: vec![],
+		};
+
+		let texture = Self::new_offscreen_texture(&device, &config);
+
+		Self::from_parts(
+			RenderTarget::Offscreen(Arc::new(texture)),
+			device,
+			queue,
+			config,
+			settings,
+		)
+	}
+
+	async fn request_device(adapter: &wgpu::Adapter) -> (wgpu::Device, wgpu::Queue) {
 		// TODO: Remove, as they are temporary to allow for voxel debug rendering
-		let mut limits = wgpu::Limits::default();
-		limits.max_buffer_size = 268_435_456 * 4;
-		limits.max_storage_buffer_binding_size = 134_217_728 * 8;
-		limits.max_uniform_buffer_binding_size = 134_217_728 * 8;
+		let adapter_limits = adapter.limits();
+		let limits = wgpu::Limits {
+			max_buffer_size: adapter_limits.max_buffer_size.min(268_435_456 * 4),
+			max_storage_buffer_binding_size: adapter_limits
+				.max_storage_buffer_binding_size
+				.min(134_217_728 * 8),
+			max_uniform_buffer_binding_size: adapter_limits
+				.max_uniform_buffer_binding_size
+				.min(134_217_728 * 8),
+			..Default::default()
+		};
 
-		let (device, queue) = adapter
+		adapter
 			.request_device(
 				&wgpu::DeviceDescriptor {
 					label: None,
@@ -298,23 +497,18 @@ impl InternalRenderer {
 				None,
 			)
 			.await
-			.unwrap();
-
-		// Config for surface
-		let config = wgpu::SurfaceConfiguration {
-			usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-			format: surface.get_capabilities(&adapter).formats[0],
-			width: size.width,
-			height: size.height,
-			present_mode: wgpu::PresentMode::Fifo,
-			alpha_mode: wgpu::CompositeAlphaMode::Auto,
-			desired_maximum_frame_latency: 2,
-			view_formats: vec![],
-		};
-		surface.configure(&device, &config);
+			.unwrap()
+	}
 
+	fn from_parts(
+		target: RenderTarget,
+		device: wgpu::Device,
+		queue: wgpu::Queue,
+		config: wgpu::SurfaceConfiguration,
+		settings: RendererSettings,
+	) -> Self {
 		Self {
-			surface,
+			target: Mutex::new(target),
 			device,
 			queue,
 			config: Mutex::new(config),
@@ -323,17 +517,45 @@ impl InternalRenderer {
 			meshes: DashMap::new(),
 			materials: DashMap::new(),
 			cameras: DashMap::new(),
+			lights: DashMap::new(),
 			resource_manager: ResourceManager::new(),
 			current_id: core::sync::atomic::AtomicU64::new(0),
 		}
 	}
 
+	fn new_offscreen_texture(
+		device: &wgpu::Device,
+		config: &wgpu::SurfaceConfiguration,
+	) -> wgpu::Texture {
+		device.create_texture(&wgpu::TextureDescriptor {
+			label: Some("Offscreen Color Target"),
+			size: wgpu::Extent3d {
+				width: config.width,
+				height: config.height,
+				depth_or_array_layers: 1,
+			},
+			mip_level_count: 1,
+			sample_count: 1,
+			dimension: wgpu::TextureDimension::D2,
+			format: config.format,
+			usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+				| wgpu::TextureUsages::TEXTURE_BINDING
+				| wgpu::TextureUsages::COPY_SRC,
+			view_formats: &[],
+		})
+	}
+
 	pub fn resize(&self, width: u32, height: u32) {
 		let mut conf = self.config.lock().unwrap();
 		if width != conf.width || height != conf.height {
 			conf.width = width;
 			conf.height = height;
-			self.surface.configure(&self.device, &conf);
+			match &mut *self.target.lock().unwrap() {
+				RenderTarget::Surface(surface) => surface.configure(&self.device, &conf),
+				RenderTarget::Offscreen(texture) => {
+					*texture = Arc::new(Self::new_offscreen_texture(&self.device, &conf));
+				}
+			}
 			*self.dirty_settings.lock().unwrap() = true;
 		}
 
@@ -1,4 +1,3 @@
-
 #[derive(Debug, Copy, Clone)]
 pub struct PointLight {
 	pub position: [f32; 3],
@@ -12,18 +11,79 @@ impl PointLight {
 			position: self.position,
 			_padding: 0,
 			color: self.color,
-			_padding2: 0,
 			intensity: self.intensity,
 		}
 	}
 }
 
+// Matches the WGSL PointLight, whose intensity fills the padding after the colour
 #[repr(C)]
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct PointLightUniform {
 	position: [f32; 3],
 	_padding: u32,
 	color: [f32; 3],
-	_padding2: u32,
 	intensity: f32,
-} 
\ No newline at end of file
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightsHeader {
+	count: u32,
+	_padding: [u32; 3],
+}
+
+// The Lights struct of _lights.wgsl, a count followed by the lights. Rewritten when the scene's lights change and only
+// reallocated when they outgrow it. Never empty, as storage bindings can't be
+pub(crate) struct LightsBuffer {
+	lights: Vec<PointLightUniform>,
+	buffer: wgpu::Buffer,
+	capacity: usize,
+}
+
+impl LightsBuffer {
+	pub(crate) fn new(device: &wgpu::Device) -> Self {
+		Self {
+			lights: Vec::new(),
+			buffer: Self::new_buffer(device, 1),
+			capacity: 1,
+		}
+	}
+
+	fn new_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+		device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Lights Buffer"),
+			size: (std::mem::size_of::<LightsHeader>() + capacity * std::mem::size_of::<PointLightUniform>()) as u64,
+			usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		})
+	}
+
+	pub(crate) fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, lights: &[PointLight]) -> &wgpu::Buffer {
+		let lights: Vec<_> = lights.iter().map(|light| light.to_uniform()).collect();
+		if lights == self.lights {
+			return &self.buffer;
+		}
+
+		if lights.len() > self.capacity {
+			self.capacity = lights.len();
+			self.buffer = Self::new_buffer(device, self.capacity);
+		}
+
+		let header = LightsHeader {
+			count: lights.len() as u32,
+			_padding: [0; 3],
+		};
+		queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&header));
+		if !lights.is_empty() {
+			queue.write_buffer(
+				&self.buffer,
+				std::mem::size_of::<LightsHeader>() as u64,
+				bytemuck::cast_slice(&lights),
+			);
+		}
+
+		self.lights = lights;
+		&self.buffer
+	}
+}
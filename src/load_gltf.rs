@@ -10,7 +10,7 @@ use super::mesh::*;
 pub(crate) fn load_gltf<P: AsRef<std::path::Path>>(
 	renderer: &InternalRenderer,
 	path: P,
-	_is_static: bool,
+	is_static: bool,
 ) -> Vec<crate::camera::Camera> {
 	let mut cameras = Vec::new();
 
@@ -23,7 +23,7 @@ pub(crate) fn load_gltf<P: AsRef<std::path::Path>>(
 
 		pool.scoped(|scope| {
 			for node in nodes {
-				scope.execute(|| check_node(&renderer, node, &buffers, &textures));
+				scope.execute(|| check_node(renderer, node, &glm::Mat4::identity(), &buffers, &textures, is_static));
 			}
 		});
 
@@ -35,31 +35,45 @@ pub(crate) fn load_gltf<P: AsRef<std::path::Path>>(
 	return cameras;
 }
 
+// The parent transform is the product of every ancestor's local matrix
 fn check_node(
 	renderer: &InternalRenderer,
 	node: gltf::Node<'_>,
+	parent_transform: &glm::Mat4,
 	buffers: &Vec<gltf::buffer::Data>,
 	textures: &Vec<gltf::image::Data>,
+	is_static: bool,
 ) {
+	let transform = parent_transform * glm::Mat4::from(node.transform().matrix());
 	let nodes: Vec<gltf::Node<'_>> = node.children().collect();
 
 	let pool = scoped_pool::Pool::new(nodes.len());
 
 	pool.scoped(|scope| {
 		for node in nodes {
-			scope.execute(move || check_node(&renderer, node, &buffers, &textures));
+			scope.execute(move || check_node(renderer, node, &transform, buffers, textures, is_static));
 		}
 	});
 
 	if node.mesh().is_some() {
-		let mesh = get_mesh(renderer, node, buffers, &textures);
-
-		let id = renderer.new_id();
-		renderer.meshes.insert(id, mesh);
+		// Meshes without triangles have nothing to draw or voxelize
+		if let Some(mesh) = get_mesh(renderer, node, &transform, buffers, textures, is_static) {
+			let id = renderer.new_id();
+			renderer.meshes.insert(id, mesh);
+		}
 	} else if let Some(light) = node.light() {
 		match light.kind() {
 			gltf::khr_lights_punctual::Kind::Directional => {}
-			gltf::khr_lights_punctual::Kind::Point => {}
+			gltf::khr_lights_punctual::Kind::Point => {
+				let light = crate::lights::PointLight {
+					position: transform.column(3).xyz().into(),
+					color: light.color(),
+					intensity: light.intensity(),
+				};
+
+				let id = renderer.new_id();
+				renderer.lights.insert(id, light);
+			}
 			gltf::khr_lights_punctual::Kind::Spot { .. } => {}
 		}
 	}
@@ -68,22 +82,35 @@ fn check_node(
 fn get_mesh(
 	renderer: &InternalRenderer,
 	node: gltf::Node<'_>,
+	transform: &glm::Mat4,
 	buffers: &Vec<gltf::buffer::Data>,
 	textures: &Vec<gltf::image::Data>,
-) -> Mesh {
+	is_static: bool,
+) -> Option<Mesh> {
 	const I8_MAX: f32 = i8::MAX as f32;
 	const U16_MAX: f32 = u16::MAX as f32;
 	// const U8_MAX: f32 = u8::MAX as f32;
 
 	use wgpu::util::DeviceExt;
 	let mesh = node.mesh().unwrap();
+	// Points and lines are skipped, as are primitives without positions
+	let triangle_primitives: Vec<gltf::Primitive<'_>> = mesh
+		.primitives()
+		.filter(|primitive| primitive.mode() == gltf::mesh::Mode::Triangles)
+		.filter(|primitive| primitive.get(&gltf::Semantic::Positions).is_some())
+		.collect();
+	if triangle_primitives.is_empty() {
+		return None;
+	}
+
 	let mut vertex_data: Vec<u8> = Vec::new();
 	let mut vertex_count = 0 as usize;
 
 	let mut indices = Vec::new();
 	let mut primitives = Vec::new();
+	let mut local_bounds: Option<Aabb> = None;
 
-	for gltf_primitive in mesh.primitives() {
+	for gltf_primitive in triangle_primitives.iter() {
 		let material = gltf_primitive.material();
 		let id = renderer.new_id();
 		get_material(renderer, id, &material, &textures);
@@ -104,26 +131,34 @@ fn get_mesh(
 		// 	index_data.extend_from_slice(bytemuck::cast_slice(&[index + index_offset as u32]))
 		// }
 
-		indices.append(
-			&mut reader
-				.read_indices()
-				.unwrap()
-				.into_u32()
-				.map(|v| (v + index_offset as u32))
-				.collect::<Vec<u32>>(),
-		);
+		let positions: Vec<[f32; 3]> = reader.read_positions().unwrap().collect();
+
+		// Non-indexed primitives draw their vertices in order
+		match reader.read_indices() {
+			Some(primitive_indices) => indices.extend(primitive_indices.into_u32().map(|v| v + index_offset as u32)),
+			None => indices.extend((0..positions.len() as u32).map(|v| v + index_offset as u32)),
+		}
 
 		let end = indices.len();
 
-		let positions = reader.read_positions().unwrap();
 		for position in positions {
 			vertex_count += 1;
 			vertex_data.extend_from_slice(bytemuck::cast_slice(&position));
+
+			let point = Aabb {
+				min: position.into(),
+				max: position.into(),
+			};
+			local_bounds = Some(match local_bounds {
+				Some(bounds) => bounds.union(&point),
+				None => point,
+			});
 		}
 
 		primitives.push(Primitive {
 			index: (start as u32)..(end as u32),
 			material: id,
+			uniform_offset: primitives.len() as u32 * renderer.device.limits().min_uniform_buffer_offset_alignment,
 		});
 	}
 
@@ -137,7 +172,7 @@ fn get_mesh(
 
 	// Normals
 	let normals_start = vertex_data.len() as wgpu::BufferAddress;
-	for gltf_primitive in mesh.primitives() {
+	for gltf_primitive in triangle_primitives.iter() {
 		let reader = gltf_primitive.reader(|buffer| Some(&buffers[buffer.index()]));
 
 		let tangents: Box<dyn std::iter::Iterator<Item = [f32; 4]>> = match reader.read_tangents() {
@@ -182,7 +217,7 @@ fn get_mesh(
 
 	// Colors
 	let colors_start = vertex_data.len() as wgpu::BufferAddress;
-	for gltf_primitive in mesh.primitives() {
+	for gltf_primitive in triangle_primitives.iter() {
 		let reader = gltf_primitive.reader(|buffer| Some(&buffers[buffer.index()]));
 
 		let colors: Box<dyn std::iter::Iterator<Item = [u8; 4]>> =
@@ -228,24 +263,85 @@ fn get_mesh(
 			usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::STORAGE,
 		});
 
+	let mut primitive_data = Vec::new();
+	for primitive in primitives.iter() {
+		align_vector(
+			&mut primitive_data,
+			renderer.device.limits().min_uniform_buffer_offset_alignment as usize,
+			0,
+		);
+
+		primitive_data.extend_from_slice(bytemuck::cast_slice(&[crate::mesh::PrimitiveUniform {
+			index_start: primitive.index.start,
+			triangle_count: (primitive.index.end - primitive.index.start) / 3,
+			_padding: [0; 2],
+		}]));
+	}
+
+	let primitive_buffer = renderer
+		.device
+		.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+			label: Some("A primitive buffer"),
+			contents: &primitive_data[..],
+			usage: wgpu::BufferUsages::UNIFORM,
+		});
+
+	let vertex_range = |range: &std::ops::Range<wgpu::BufferAddress>| {
+		wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+			buffer: &vertex_buffer,
+			offset: range.start,
+			size: wgpu::BufferSize::new(range.end - range.start),
+		})
+	};
+
+	let compute_bind_group = renderer
+		.device
+		.create_bind_group(&wgpu::BindGroupDescriptor {
+			label: Some("A compute mesh bind group"),
+			layout: crate::mesh::ComputeMeshBindGroup::get_bind_group_layout(&renderer.device),
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: index_buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 1,
+					resource: vertex_range(&positions),
+				},
+				wgpu::BindGroupEntry {
+					binding: 2,
+					resource: vertex_range(&normals),
+				},
+				wgpu::BindGroupEntry {
+					binding: 3,
+					resource: vertex_range(&colors),
+				},
+				wgpu::BindGroupEntry {
+					binding: 4,
+					resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+						buffer: &primitive_buffer,
+						offset: 0,
+						size: wgpu::BufferSize::new(std::mem::size_of::<crate::mesh::PrimitiveUniform>() as u64),
+					}),
+				},
+			],
+		});
+
 	let transform_buffer = renderer
 		.device
 		.create_buffer_init(&wgpu::util::BufferInitDescriptor {
 			label: Some("A transform buffer"),
-			contents: bytemuck::cast_slice(&node.transform().matrix()),
+			contents: bytemuck::cast_slice(transform.as_slice()),
 			usage: wgpu::BufferUsages::UNIFORM
 				| wgpu::BufferUsages::COPY_DST
 				| wgpu::BufferUsages::COPY_SRC,
 		});
 
-	let (_, rotation, _) = node.transform().decomposed();
+	let local_bounds = local_bounds.unwrap();
+	let bounds = local_bounds.transform(transform);
 
-	let normal_matrix: glm::Mat4x4 = glm::mat3_to_mat4(&glm::quat_to_mat3(&glm::quat(
-		rotation[0],
-		rotation[1],
-		rotation[2],
-		rotation[3],
-	)));
+	// Same as Renderer::set_mesh_transform, so scaled nodes keep their normals perpendicular
+	let normal_matrix: glm::Mat4x4 = glm::mat3_to_mat4(&glm::inverse_transpose(glm::mat4_to_mat3(transform)));
 
 	let normal_buffer = renderer
 		.device
@@ -274,16 +370,23 @@ fn get_mesh(
 			],
 		});
 
-	Mesh {
+	Some(Mesh {
 		vertex_buffer,
 		positions,
 		normals,
 		colors,
 		index_buffer,
 		transform_buffer,
+		normal_buffer,
 		model_bind_group,
+		compute_bind_group,
+		primitive_buffer,
 		primitives,
-	}
+		bounds,
+		local_bounds,
+		is_static,
+		transform_generation: 0,
+	})
 }
 
 fn get_material(
@@ -294,8 +397,6 @@ fn get_material(
 ) {
 	use image::GenericImageView;
 
-	const DEFAULT_DIFFUSE: &[u8] = include_bytes!("default_textures/DiffuseLargeMap.png");
-	const DEFAULT_METAL: &[u8] = include_bytes!("default_textures/MetallicRoughnessMap.png");
 	const DEFAULT_NORMAL: &[u8] = include_bytes!("default_textures/NormalMap.png");
 
 	let new_default_sampler = |renderer: &InternalRenderer| -> wgpu::Sampler {
@@ -325,24 +426,40 @@ fn get_material(
 		}
 	};
 
+	// glTF multiplies every texture by its factor, which is baked into the texels here
+	let base_color_factor = material.pbr_metallic_roughness().base_color_factor();
+	let metal_roughness_factor = [
+		1.0,
+		material.pbr_metallic_roughness().roughness_factor(),
+		material.pbr_metallic_roughness().metallic_factor(),
+		1.0,
+	];
+	let [emissive_r, emissive_g, emissive_b] = material.emissive_factor();
+	let emissive_factor = [emissive_r, emissive_g, emissive_b, 1.0];
+
 	let (diffuse_texture, diffuse_view, diffuse_sampler) =
 		if let Some(texture_info) = material.pbr_metallic_roughness().base_color_texture() {
 			let texture_data = &textures[texture_info.texture().source().index()];
-			let image = texture_data_to_dynamic_image(texture_data);
+			let image = scale_texels(texture_data_to_dynamic_image(texture_data), base_color_factor, true);
 			let (texture, view) = get_texture(renderer, image, wgpu::TextureFormat::Rgba8UnormSrgb);
 			(texture, view, new_default_sampler(renderer))
 		} else {
-			let image = image::load_from_memory(DEFAULT_DIFFUSE).unwrap();
-			let dimensions = image.dimensions();
+			let image = factor_image(base_color_factor, true);
 			let (texture, view) = get_texture(renderer, image, wgpu::TextureFormat::Rgba8UnormSrgb);
 			(texture, view, new_default_sampler(renderer))
 		};
 
-	let (metal_texture, metal_view, metal_sampler) = {
-		let image = image::load_from_memory(DEFAULT_METAL).unwrap();
-		let (texture, view) = get_texture(renderer, image, wgpu::TextureFormat::Rgba8Unorm);
-		(texture, view, new_default_sampler(renderer))
-	};
+	let (metal_texture, metal_view, metal_sampler) =
+		if let Some(texture_info) = material.pbr_metallic_roughness().metallic_roughness_texture() {
+			let texture_data = &textures[texture_info.texture().source().index()];
+			let image = scale_texels(texture_data_to_dynamic_image(texture_data), metal_roughness_factor, false);
+			let (texture, view) = get_texture(renderer, image, wgpu::TextureFormat::Rgba8Unorm);
+			(texture, view, new_default_sampler(renderer))
+		} else {
+			let image = factor_image(metal_roughness_factor, false);
+			let (texture, view) = get_texture(renderer, image, wgpu::TextureFormat::Rgba8Unorm);
+			(texture, view, new_default_sampler(renderer))
+		};
 
 	let (normal_texture, normal_view, normal_sampler) =
 		if let Some(texture_info) = material.normal_texture() {
@@ -359,6 +476,18 @@ fn get_material(
 			(texture, view, new_default_sampler(renderer))
 		};
 
+	let (emissive_texture, emissive_view, emissive_sampler) =
+		if let Some(texture_info) = material.emissive_texture() {
+			let texture_data = &textures[texture_info.texture().source().index()];
+			let image = scale_texels(texture_data_to_dynamic_image(texture_data), emissive_factor, true);
+			let (texture, view) = get_texture(renderer, image, wgpu::TextureFormat::Rgba8UnormSrgb);
+			(texture, view, new_default_sampler(renderer))
+		} else {
+			let image = factor_image(emissive_factor, true);
+			let (texture, view) = get_texture(renderer, image, wgpu::TextureFormat::Rgba8UnormSrgb);
+			(texture, view, new_default_sampler(renderer))
+		};
+
 	let bind_group = renderer
 		.device
 		.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -388,6 +517,14 @@ fn get_material(
 					binding: 5,
 					resource: wgpu::BindingResource::Sampler(&normal_sampler),
 				},
+				wgpu::BindGroupEntry {
+					binding: 6,
+					resource: wgpu::BindingResource::TextureView(&emissive_view),
+				},
+				wgpu::BindGroupEntry {
+					binding: 7,
+					resource: wgpu::BindingResource::Sampler(&emissive_sampler),
+				},
 			],
 			label: Some("material bind group"),
 		});
@@ -410,11 +547,60 @@ fn get_material(
 				view: normal_view,
 				sampler: normal_sampler,
 			},
+			emissive: Texture {
+				texture: emissive_texture,
+				view: emissive_view,
+				sampler: emissive_sampler,
+			},
 			bind_group,
 		},
 	);
 }
 
+// Colour channels of sRGB images are scaled in linear space
+fn scale_texels(image: image::DynamicImage, factor: [f32; 4], srgb: bool) -> image::DynamicImage {
+	if factor == [1.0; 4] {
+		return image;
+	}
+
+	let mut image = image.to_rgba8();
+	for pixel in image.pixels_mut() {
+		for (channel, value) in pixel.0.iter_mut().enumerate() {
+			let mut texel = *value as f32 / 255.0;
+			if srgb && channel < 3 {
+				texel = linear_to_srgb(srgb_to_linear(texel) * factor[channel]);
+			} else {
+				texel *= factor[channel];
+			}
+			*value = (texel.clamp(0.0, 1.0) * 255.0).round() as u8;
+		}
+	}
+
+	image::DynamicImage::ImageRgba8(image)
+}
+
+// Without a texture the factor is constant over the whole material
+fn factor_image(factor: [f32; 4], srgb: bool) -> image::DynamicImage {
+	let white = image::RgbaImage::from_pixel(1, 1, image::Rgba([255; 4]));
+	scale_texels(image::DynamicImage::ImageRgba8(white), factor, srgb)
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+	if value <= 0.04045 {
+		value / 12.92
+	} else {
+		((value + 0.055) / 1.055).powf(2.4)
+	}
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+	if value <= 0.0031308 {
+		value * 12.92
+	} else {
+		1.055 * value.powf(1.0 / 2.4) - 0.055
+	}
+}
+
 fn get_texture(
 	renderer: &InternalRenderer,
 	image: image::DynamicImage,
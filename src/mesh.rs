@@ -11,7 +11,10 @@ pub const COMPUTE_MESH_BIND_GROUP_LAYOUT: &'static wgpu::BindGroupLayoutDescript
 		entries: &[
 			wgpu::BindGroupLayoutEntry {
 				binding: 0,
-				visibility: wgpu::ShaderStages::COMPUTE,
+				visibility: wgpu::ShaderStages::union(
+					wgpu::ShaderStages::VERTEX,
+					wgpu::ShaderStages::COMPUTE,
+				),
 				ty: wgpu::BindingType::Buffer {
 					ty: wgpu::BufferBindingType::Storage { read_only: true },
 					has_dynamic_offset: false,
@@ -21,7 +24,10 @@ pub const COMPUTE_MESH_BIND_GROUP_LAYOUT: &'static wgpu::BindGroupLayoutDescript
 			},
 			wgpu::BindGroupLayoutEntry {
 				binding: 1,
-				visibility: wgpu::ShaderStages::COMPUTE,
+				visibility: wgpu::ShaderStages::union(
+					wgpu::ShaderStages::VERTEX,
+					wgpu::ShaderStages::COMPUTE,
+				),
 				ty: wgpu::BindingType::Buffer {
 					ty: wgpu::BufferBindingType::Storage { read_only: true },
 					has_dynamic_offset: false,
@@ -31,7 +37,10 @@ pub const COMPUTE_MESH_BIND_GROUP_LAYOUT: &'static wgpu::BindGroupLayoutDescript
 			},
 			wgpu::BindGroupLayoutEntry {
 				binding: 2,
-				visibility: wgpu::ShaderStages::COMPUTE,
+				visibility: wgpu::ShaderStages::union(
+					wgpu::ShaderStages::VERTEX,
+					wgpu::ShaderStages::COMPUTE,
+				),
 				ty: wgpu::BindingType::Buffer {
 					ty: wgpu::BufferBindingType::Storage { read_only: true },
 					has_dynamic_offset: false,
@@ -41,7 +50,10 @@ pub const COMPUTE_MESH_BIND_GROUP_LAYOUT: &'static wgpu::BindGroupLayoutDescript
 			},
 			wgpu::BindGroupLayoutEntry {
 				binding: 3,
-				visibility: wgpu::ShaderStages::COMPUTE,
+				visibility: wgpu::ShaderStages::union(
+					wgpu::ShaderStages::VERTEX,
+					wgpu::ShaderStages::COMPUTE,
+				),
 				ty: wgpu::BindingType::Buffer {
 					ty: wgpu::BufferBindingType::Storage { read_only: true },
 					has_dynamic_offset: false,
@@ -49,9 +61,31 @@ pub const COMPUTE_MESH_BIND_GROUP_LAYOUT: &'static wgpu::BindGroupLayoutDescript
 				},
 				count: None,
 			},
+			// One PrimitiveUniform per primitive, selected with a dynamic offset
+			wgpu::BindGroupLayoutEntry {
+				binding: 4,
+				visibility: wgpu::ShaderStages::union(
+					wgpu::ShaderStages::VERTEX,
+					wgpu::ShaderStages::COMPUTE,
+				),
+				ty: wgpu::BindingType::Buffer {
+					ty: wgpu::BufferBindingType::Uniform,
+					has_dynamic_offset: true,
+					min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<PrimitiveUniform>() as u64),
+				},
+				count: None,
+			},
 		],
 	};
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PrimitiveUniform {
+	pub index_start: u32,
+	pub triangle_count: u32,
+	pub _padding: [u32; 2],
+}
+
 #[derive(BindGroup)]
 #[layout(COMPUTE_MESH_BIND_GROUP_LAYOUT)]
 pub struct ComputeMeshBindGroup {}
@@ -63,20 +97,85 @@ pub struct Mesh {
 	pub colors: Range<wgpu::BufferAddress>,
 	pub index_buffer: wgpu::Buffer,
 	pub transform_buffer: wgpu::Buffer,
+	pub normal_buffer: wgpu::Buffer,
 	pub model_bind_group: wgpu::BindGroup,
+	// Binds the whole index and vertex buffers for compute and vertex pulling, see COMPUTE_MESH_BIND_GROUP_LAYOUT
+	pub compute_bind_group: wgpu::BindGroup,
+	pub primitive_buffer: wgpu::Buffer,
 	pub primitives: Vec<Primitive>,
+	// In world space, local_bounds holds them before the transform
+	pub bounds: Aabb,
+	pub local_bounds: Aabb,
+	// Static meshes are voxelized once, dynamic ones every frame
+	pub is_static: bool,
+	// Bumped by every Renderer::set_mesh_transform, so rotations that keep the bounds are noticed too
+	pub transform_generation: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+	pub min: glm::Vec3,
+	pub max: glm::Vec3,
+}
+
+impl Aabb {
+	pub fn from_points<I: IntoIterator<Item = glm::Vec3>>(points: I) -> Option<Self> {
+		let mut points = points.into_iter();
+		let first = points.next()?;
+
+		let mut aabb = Self {
+			min: first,
+			max: first,
+		};
+
+		for point in points {
+			aabb.min = glm::min2(&aabb.min, &point);
+			aabb.max = glm::max2(&aabb.max, &point);
+		}
+
+		Some(aabb)
+	}
+
+	pub fn union(&self, other: &Self) -> Self {
+		Self {
+			min: glm::min2(&self.min, &other.min),
+			max: glm::max2(&self.max, &other.max),
+		}
+	}
+
+	// Boxes that only share a face count as intersecting
+	pub fn intersects(&self, other: &Self) -> bool {
+		(0..3).all(|i| self.min[i] <= other.max[i] && other.min[i] <= self.max[i])
+	}
+
+	pub fn transform(&self, matrix: &glm::Mat4) -> Self {
+		let corners = (0..8).map(|i| {
+			let corner = glm::vec3(
+				if i & 1 == 0 { self.min.x } else { self.max.x },
+				if i & 2 == 0 { self.min.y } else { self.max.y },
+				if i & 4 == 0 { self.min.z } else { self.max.z },
+			);
+
+			(matrix * glm::vec4(corner.x, corner.y, corner.z, 1.0)).xyz()
+		});
+
+		Self::from_points(corners).unwrap()
+	}
 }
 
 pub struct Primitive {
 	pub index: Range<u32>,
 	// pub skin: Range<wgpu::BufferAddress>,
 	pub material: crate::Id,
+	// Dynamic offset of its PrimitiveUniform in Mesh::primitive_buffer
+	pub uniform_offset: wgpu::DynamicOffset,
 }
 
 pub struct Material {
 	pub diffuse: Texture,
 	pub metallic_roughness: Texture,
 	pub normal: Texture,
+	pub emissive: Texture,
 	pub bind_group: wgpu::BindGroup,
 }
 
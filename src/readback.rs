@@ -0,0 +1,240 @@
+// Formats TextureReadback turns into an RgbaImage
+pub fn is_readable_format(format: wgpu::TextureFormat) -> bool {
+	matches!(
+		format,
+		wgpu::TextureFormat::Rgba8Unorm
+			| wgpu::TextureFormat::Rgba8UnormSrgb
+			| wgpu::TextureFormat::Bgra8Unorm
+			| wgpu::TextureFormat::Bgra8UnormSrgb
+	)
+}
+
+#[derive(Debug)]
+pub enum ReadbackError {
+	// See is_readable_format
+	UnreadableFormat(wgpu::TextureFormat),
+	// Surfaces only get COPY_SRC on platforms that support it
+	NotCopyable,
+	Map(wgpu::BufferAsyncError),
+}
+
+impl std::fmt::Display for ReadbackError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ReadbackError::UnreadableFormat(format) => write!(f, "Targets of {format:?} can't be read back"),
+			ReadbackError::NotCopyable => write!(f, "The target can't be copied from"),
+			ReadbackError::Map(error) => error.fmt(f),
+		}
+	}
+}
+
+impl std::error::Error for ReadbackError {}
+
+impl From<wgpu::BufferAsyncError> for ReadbackError {
+	fn from(error: wgpu::BufferAsyncError) -> Self {
+		ReadbackError::Map(error)
+	}
+}
+
+pub(crate) struct TextureReadback {
+	buffer: wgpu::Buffer,
+	unpadded_bytes_per_row: u32,
+	padded_bytes_per_row: u32,
+	size: wgpu::Extent3d,
+	format: wgpu::TextureFormat,
+}
+
+impl TextureReadback {
+	pub fn new(
+		device: &wgpu::Device,
+		encoder: &mut wgpu::CommandEncoder,
+		texture: &wgpu::Texture,
+	) -> Result<Self, ReadbackError> {
+		let size = texture.size();
+		let format = texture.format();
+		if !is_readable_format(format) {
+			return Err(ReadbackError::UnreadableFormat(format));
+		}
+		if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+			return Err(ReadbackError::NotCopyable);
+		}
+		let bytes_per_pixel = format.block_copy_size(None).unwrap();
+
+		// Rows of a texture copy have to be aligned to COPY_BYTES_PER_ROW_ALIGNMENT
+		let unpadded_bytes_per_row = size.width * bytes_per_pixel;
+		let padded_bytes_per_row = unpadded_bytes_per_row
+			.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+			* wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+		let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Texture Readback Buffer"),
+			size: (padded_bytes_per_row * size.height) as u64,
+			usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+			mapped_at_creation: false,
+		});
+
+		encoder.copy_texture_to_buffer(
+			wgpu::ImageCopyTexture {
+				texture,
+				mip_level: 0,
+				origin: wgpu::Origin3d::ZERO,
+				aspect: wgpu::TextureAspect::All,
+			},
+			wgpu::ImageCopyBuffer {
+				buffer: &buffer,
+				layout: wgpu::ImageDataLayout {
+					offset: 0,
+					bytes_per_row: Some(padded_bytes_per_row),
+					rows_per_image: Some(size.height),
+				},
+			},
+			wgpu::Extent3d {
+				depth_or_array_layers: 1,
+				..size
+			},
+		);
+
+		Ok(Self {
+			buffer,
+			unpadded_bytes_per_row,
+			padded_bytes_per_row,
+			size,
+			format,
+		})
+	}
+
+	pub fn read(self, device: &wgpu::Device) -> Result<image::RgbaImage, ReadbackError> {
+		wgpu_helper::map_slice_sync(self.buffer.slice(..), wgpu::MapMode::Read, device)?;
+
+		let unpadded_bytes_per_row = self.unpadded_bytes_per_row as usize;
+		let mut pixels = Vec::with_capacity(unpadded_bytes_per_row * self.size.height as usize);
+
+		{
+			let view = self.buffer.slice(..).get_mapped_range();
+
+			for row in view.chunks(self.padded_bytes_per_row as usize) {
+				pixels.extend_from_slice(&row[..unpadded_bytes_per_row]);
+			}
+		}
+
+		self.buffer.unmap();
+
+		if matches!(
+			self.format,
+			wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+		) {
+			for pixel in pixels.chunks_mut(4) {
+				pixel.swap(0, 2);
+			}
+		}
+
+		Ok(image::RgbaImage::from_raw(self.size.width, self.size.height, pixels).unwrap())
+	}
+}
+
+// Copies one mip level of a 3D texture with 4 byte texels, rows end up tightly packed
+pub(crate) struct VolumeReadback {
+	buffer: wgpu::Buffer,
+	padded_bytes_per_row: u32,
+	size: wgpu::Extent3d,
+}
+
+impl VolumeReadback {
+	pub fn new(
+		device: &wgpu::Device,
+		encoder: &mut wgpu::CommandEncoder,
+		texture: &wgpu::Texture,
+		mip_level: u32,
+	) -> Self {
+		let size = texture.size().mip_level_size(mip_level, wgpu::TextureDimension::D3);
+		assert_eq!(texture.format().block_copy_size(None), Some(4));
+
+		let padded_bytes_per_row = (size.width * 4).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+			* wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+		let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Volume Readback Buffer"),
+			size: (padded_bytes_per_row * size.height * size.depth_or_array_layers) as u64,
+			usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+			mapped_at_creation: false,
+		});
+
+		encoder.copy_texture_to_buffer(
+			wgpu::ImageCopyTexture {
+				texture,
+				mip_level,
+				origin: wgpu::Origin3d::ZERO,
+				aspect: wgpu::TextureAspect::All,
+			},
+			wgpu::ImageCopyBuffer {
+				buffer: &buffer,
+				layout: wgpu::ImageDataLayout {
+					offset: 0,
+					bytes_per_row: Some(padded_bytes_per_row),
+					rows_per_image: Some(size.height),
+				},
+			},
+			size,
+		);
+
+		Self {
+			buffer,
+			padded_bytes_per_row,
+			size,
+		}
+	}
+
+	pub fn read(self, device: &wgpu::Device) -> Result<(wgpu::Extent3d, Vec<[u8; 4]>), wgpu::BufferAsyncError> {
+		let bytes = BufferReadback { buffer: self.buffer }.read(device)?;
+
+		let unpadded_bytes_per_row = (self.size.width * 4) as usize;
+		let texels = bytes
+			.chunks(self.padded_bytes_per_row as usize)
+			.flat_map(|row| bytemuck::cast_slice::<u8, [u8; 4]>(&row[..unpadded_bytes_per_row]).iter().copied())
+			.collect();
+
+		Ok((self.size, texels))
+	}
+}
+
+pub(crate) struct BufferReadback {
+	buffer: wgpu::Buffer,
+}
+
+impl BufferReadback {
+	pub fn new(
+		device: &wgpu::Device,
+		encoder: &mut wgpu::CommandEncoder,
+		buffer: &wgpu::Buffer,
+		size: wgpu::BufferAddress,
+	) -> Self {
+		let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Buffer Readback Buffer"),
+			size,
+			usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+			mapped_at_creation: false,
+		});
+
+		encoder.copy_buffer_to_buffer(buffer, 0, &readback_buffer, 0, size);
+
+		Self {
+			buffer: readback_buffer,
+		}
+	}
+
+	pub fn read(self, device: &wgpu::Device) -> Result<Vec<u8>, wgpu::BufferAsyncError> {
+		wgpu_helper::map_slice_sync(self.buffer.slice(..), wgpu::MapMode::Read, device)?;
+
+		let bytes = self.buffer.slice(..).get_mapped_range().to_vec();
+		self.buffer.unmap();
+
+		Ok(bytes)
+	}
+}
+
+pub fn save_png<P: AsRef<std::path::Path>>(
+	image: &image::RgbaImage,
+	path: P,
+) -> image::ImageResult<()> {
+	image.save_with_format(path, image::ImageFormat::Png)
+}
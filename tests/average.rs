@@ -0,0 +1,79 @@
+extern crate nalgebra_glm as glm;
+
+mod common;
+
+use vct::command_encoder::voxelization::*;
+
+// The same triangle from (-1, -1, 0) to (1, 1, 0) in a red and in a blue mesh
+const OVERLAPPING_GLTF: &str = r#"{
+	"asset": { "version": "2.0" },
+	"scene": 0,
+	"scenes": [{ "nodes": [0, 1] }],
+	"nodes": [{ "mesh": 0 }, { "mesh": 1 }],
+	"meshes": [
+		{ "primitives": [{ "attributes": { "POSITION": 0 }, "material": 0 }] },
+		{ "primitives": [{ "attributes": { "POSITION": 0 }, "material": 1 }] }
+	],
+	"materials": [
+		{ "pbrMetallicRoughness": { "baseColorFactor": [1, 0, 0, 1] } },
+		{ "pbrMetallicRoughness": { "baseColorFactor": [0, 0, 1, 1] } }
+	],
+	"buffers": [{ "byteLength": 36, "uri": "data:application/octet-stream;base64,AACAvwAAgL8AAAAAAACAPwAAgL8AAAAAAACAvwAAgD8AAAAA" }],
+	"bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+	"accessors": [{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [-1, -1, 0], "max": [1, 1, 0] }]
+}"#;
+
+#[test]
+fn averaged_overlap() {
+	// Naga can't write the result of atomicCompareExchangeWeak for GLSL, so VoxelWriteMode::Average needs another backend
+	if common::is_gl_adapter() {
+		eprintln!("skipped, VoxelWriteMode::Average doesn't compile for the GL backend");
+		return;
+	}
+
+	let path = std::env::temp_dir().join(format!("vct_overlapping_{}.gltf", std::process::id()));
+	std::fs::write(&path, OVERLAPPING_GLTF).unwrap();
+
+	let mut renderer = common::new_headless_renderer([4, 4]);
+	renderer.load_gltf(&path, true);
+	std::fs::remove_file(&path).unwrap();
+
+	// The triangles lie in the first slice
+	let volume = VoxelVolumeDescriptor {
+		min: glm::vec3(-1.0, -1.0, -0.1),
+		max: glm::vec3(1.0, 1.0, 1.9),
+		resolution: 8,
+	};
+
+	// Each mode voxelizes into new volumes
+	let [last_writer, average] = [VoxelWriteMode::LastWriter, VoxelWriteMode::Average].map(|write_mode| {
+		let mut voxelization_pass = VoxelizationPass::new(&renderer, &volume);
+		voxelization_pass.set_settings(VoxelizationSettings {
+			write_mode,
+			..voxelization_pass.settings()
+		});
+
+		renderer.update();
+		let mut command_encoder = renderer.new_command_encoder(None);
+		command_encoder.begin_pass(&mut voxelization_pass);
+		command_encoder.finish();
+
+		let voxels = renderer.get_resource::<VoxelsResource>().unwrap();
+		let data = voxels.read_color(&renderer, 0).unwrap();
+		let slice: Vec<_> = data.occupied().filter(|(position, _)| position[2] == 0).collect();
+		assert!(!slice.is_empty());
+		slice
+	});
+
+	// One of the triangles wins every voxel when the last writer does, the average blends both into the same voxels
+	assert!(last_writer.iter().all(|(_, voxel)| voxel[1] == 0 && (voxel[0] == 0) != (voxel[2] == 0)), "{last_writer:?}");
+	assert_eq!(
+		average.iter().map(|(position, _)| *position).collect::<Vec<_>>(),
+		last_writer.iter().map(|(position, _)| *position).collect::<Vec<_>>()
+	);
+	let winner = last_writer[0].1[0].max(last_writer[0].1[2]);
+	assert!(
+		average.iter().all(|(_, voxel)| voxel[1] == 0 && voxel[0] > 0 && voxel[0] < winner && voxel[0].abs_diff(voxel[2]) <= 2),
+		"{average:?}"
+	);
+}
@@ -0,0 +1,57 @@
+extern crate nalgebra_glm as glm;
+
+mod common;
+
+use vct::command_encoder::voxelization::*;
+
+#[test]
+fn cached_voxels() {
+	let (mut renderer, camera, volume) = common::box_scene();
+	let mut voxelization_pass = VoxelizationPass::new(&renderer, &volume);
+
+	renderer.update();
+	let mut command_encoder = renderer.new_command_encoder(Some(&camera));
+	command_encoder.begin_pass(&mut voxelization_pass);
+	command_encoder.finish();
+
+	let source_hash = VoxelCache::source_hash(["examples/Box.glb"]).unwrap();
+	assert_eq!(VoxelCache::source_hash(["examples/Box.glb"]).unwrap(), source_hash);
+	assert_ne!(VoxelCache::source_hash(["examples/Box.glb", "examples/Box.glb"]).unwrap(), source_hash);
+
+	let cache = {
+		let voxels_resource = renderer.get_resource::<VoxelsResource>().unwrap();
+		VoxelCache::capture(&renderer, &voxels_resource, source_hash).unwrap()
+	};
+	assert_eq!(cache.channels[0].len(), 5);
+	assert!(cache.matches(&cache.volume, source_hash) && !cache.matches(&cache.volume, source_hash + 1));
+
+	let mut bytes = Vec::new();
+	cache.write(&mut bytes).unwrap();
+	assert_eq!(VoxelCache::read(&mut bytes.as_slice()).unwrap(), cache);
+	assert!(VoxelCache::read(&mut &bytes[..bytes.len() - 1]).is_err());
+
+	// The resolution follows the magic, version, source hash and bounds
+	let mut oversized = bytes.clone();
+	oversized[40..44].copy_from_slice(&u32::MAX.to_le_bytes());
+	assert!(VoxelCache::read(&mut oversized.as_slice()).is_err());
+
+	// A new pass starts from the cache and has nothing left to voxelize
+	let mut voxelization_pass = VoxelizationPass::new(&renderer, &cache.volume);
+	assert!(!voxelization_pass.load_cache(&renderer, &cache, source_hash + 1));
+	assert!(voxelization_pass.load_cache(&renderer, &cache, source_hash));
+
+	let mut command_encoder = renderer.new_command_encoder(Some(&camera));
+	command_encoder.begin_pass(&mut voxelization_pass);
+	command_encoder.finish();
+	assert_eq!(voxelization_pass.dirty_region(), None);
+
+	{
+		let voxels_resource = renderer.get_resource::<VoxelsResource>().unwrap();
+		assert_eq!(VoxelCache::capture(&renderer, &voxels_resource, source_hash).unwrap(), cache);
+	}
+
+	// Dynamic meshes would be baked into the static voxels, so the cache is refused
+	renderer.load_gltf("examples/Box.glb", false);
+	let mut voxelization_pass = VoxelizationPass::new(&renderer, &cache.volume);
+	assert!(!voxelization_pass.load_cache(&renderer, &cache, source_hash));
+}
@@ -0,0 +1,38 @@
+extern crate nalgebra_glm as glm;
+
+mod common;
+
+#[test]
+fn clipmap() {
+	use vct::command_encoder::{forward::{self, ForwardRenderingPass}, voxelization::*};
+
+	let (renderer, camera, _) = common::box_scene();
+
+	let mut clipmap_pass = ClipmapVoxelizationPass::new(
+		&renderer,
+		&VoxelClipmapDescriptor {
+			level_count: 3,
+			resolution: 16,
+			voxel_size: 0.125,
+		},
+	);
+	let mut radiance_injection_pass = RadianceInjectionPass::new(&renderer);
+	let mut forward_render_pass = ForwardRenderingPass::new(&renderer);
+	forward_render_pass.set_voxel_backend(forward::VoxelBackend::Clipmap);
+
+	// The second frame scrolls every level, only re-voxelizing the exposed slabs
+	for position in [[0.0, 0.0, -3.0], [0.5, 0.25, -2.5]] {
+		camera.set_position(position.into());
+		renderer.update();
+
+		let mut command_encoder = renderer.new_command_encoder(Some(&camera));
+		command_encoder.begin_pass(&mut clipmap_pass);
+		command_encoder.begin_pass(&mut radiance_injection_pass);
+		command_encoder.begin_pass(&mut forward_render_pass);
+		let image = command_encoder.finish_and_read().unwrap();
+
+		// The same red box as the dense volume shows in the centre
+		let center = image.get_pixel(16, 8).0;
+		assert!(center[0] > 0 && center[1..3] == [0, 0], "{center:?}");
+	}
+}
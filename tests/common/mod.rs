@@ -0,0 +1,61 @@
+// Setup shared by the test binaries. Bind group layouts are cached globally for the first device of a process,
+// so every binary creates a single renderer and runs its checks from one test
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+
+use vct::{camera::CameraDescriptor, command_encoder::voxelization::VoxelVolumeDescriptor, *};
+
+pub fn new_headless_renderer(resolution: [u32; 2]) -> Renderer {
+	let render_settings = RendererSettings {
+		render_scale: 1.0,
+		extras: HashMap::new(),
+	};
+
+	pollster::block_on(Renderer::new_headless(
+		resolution,
+		wgpu::TextureFormat::Rgba8Unorm,
+		render_settings,
+	)).unwrap()
+}
+
+// Whether Renderer::new_headless lands on the GL backend, which can't compile every shader and drops some texture writes.
+// Has to be asked before the renderer is created, the EGL display is shared between instances
+pub fn is_gl_adapter() -> bool {
+	let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+		backends: wgpu::Backends::all(),
+		..Default::default()
+	});
+	let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+		power_preference: wgpu::PowerPreference::HighPerformance,
+		..Default::default()
+	}));
+	adapter.is_none_or(|adapter| adapter.get_info().backend == wgpu::Backend::Gl)
+}
+
+pub fn new_camera(renderer: &mut Renderer) -> camera::Camera {
+	renderer.new_camera(&CameraDescriptor {
+		position: [0.0, 0.0, -3.0].into(),
+		rotation: glm::Quat::identity(),
+		resolution: camera::Resolution::UseGlobalResolution,
+		fovy: 90.0,
+		znear: 0.001,
+		zfar: 1000.0,
+	})
+}
+
+// The static red Box.glb under a point light, in a 32x16 frame. Returns the 16 voxel volume fitted around it
+pub fn box_scene() -> (Renderer, camera::Camera, VoxelVolumeDescriptor) {
+	let mut renderer = new_headless_renderer([32, 16]);
+	let camera = new_camera(&mut renderer);
+
+	renderer.load_gltf("examples/Box.glb", true);
+	renderer.add_point_light(vct::lights::PointLight {
+		position: [0.0, 1.5, 0.0],
+		color: [1.0, 1.0, 1.0],
+		intensity: 1.0,
+	});
+
+	let volume = VoxelVolumeDescriptor::fit_to_meshes(&renderer, 16).unwrap();
+	(renderer, camera, volume)
+}
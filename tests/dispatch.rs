@@ -0,0 +1,107 @@
+extern crate nalgebra_glm as glm;
+
+mod common;
+
+use vct::command_encoder::voxelization::*;
+
+// One row of voxelizer workgroups holds 64 triangles for each of up to 65535 workgroups, this needs a second row
+const TRIANGLE_COUNT: u32 = 1 << 22;
+const _: () = assert!(TRIANGLE_COUNT > 65535 * 64);
+const CELLS: u32 = 16;
+
+// Every triangle lies inside one voxel of the first slice, where it writes a fragment for each of its edges.
+// The triangles go round the 16x16 cells of the slice, sharing the three vertices of their cell
+fn write_gltf(directory: &std::path::Path) -> std::path::PathBuf {
+	let positions: Vec<[f32; 3]> = (0..CELLS * CELLS)
+		.flat_map(|cell| {
+			let (x, y) = ((cell % CELLS) as f32 + 0.25, (cell / CELLS) as f32 + 0.25);
+			[[x, y, 0.5], [x + 0.5, y, 0.5], [x, y + 0.5, 0.5]]
+		})
+		.collect();
+	let indices: Vec<u32> = (0..TRIANGLE_COUNT)
+		.flat_map(|triangle| {
+			let first = (triangle % (CELLS * CELLS)) * 3;
+			[first, first + 1, first + 2]
+		})
+		.collect();
+
+	let mut bytes: Vec<u8> = bytemuck::cast_slice(&positions).to_vec();
+	let indices_offset = bytes.len();
+	bytes.extend_from_slice(bytemuck::cast_slice(&indices));
+
+	let name = format!("vct_dispatch_{}", std::process::id());
+	std::fs::write(directory.join(format!("{name}.bin")), &bytes).unwrap();
+
+	let gltf = format!(
+		r#"{{
+	"asset": {{ "version": "2.0" }},
+	"scene": 0,
+	"scenes": [{{ "nodes": [0] }}],
+	"nodes": [{{ "mesh": 0 }}],
+	"meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0 }}, "indices": 1 }}] }}],
+	"buffers": [{{ "byteLength": {}, "uri": "{name}.bin" }}],
+	"bufferViews": [
+		{{ "buffer": 0, "byteLength": {indices_offset} }},
+		{{ "buffer": 0, "byteOffset": {indices_offset}, "byteLength": {} }}
+	],
+	"accessors": [
+		{{ "bufferView": 0, "componentType": 5126, "count": {}, "type": "VEC3", "min": [0.25, 0.25, 0.5], "max": [15.75, 15.75, 0.5] }},
+		{{ "bufferView": 1, "componentType": 5125, "count": {}, "type": "SCALAR" }}
+	]
+}}"#,
+		bytes.len(),
+		bytes.len() - indices_offset,
+		positions.len(),
+		indices.len(),
+	);
+	let path = directory.join(format!("{name}.gltf"));
+	std::fs::write(&path, gltf).unwrap();
+	path
+}
+
+#[test]
+fn second_dispatch_row() {
+	let path = write_gltf(&std::env::temp_dir());
+	let mut renderer = common::new_headless_renderer([4, 4]);
+	renderer.load_gltf(&path, true);
+	std::fs::remove_file(&path).unwrap();
+	std::fs::remove_file(path.with_extension("bin")).unwrap();
+
+	let volume = VoxelVolumeDescriptor {
+		min: glm::vec3(0.0, 0.0, 0.0),
+		max: glm::vec3(16.0, 16.0, 16.0),
+		resolution: CELLS,
+	};
+	let mut voxelization_pass = VoxelizationPass::new(&renderer, &volume);
+	voxelization_pass.set_settings(VoxelizationSettings {
+		fragment_capacity: Some(4096),
+		..voxelization_pass.settings()
+	});
+
+	renderer.update();
+	let mut command_encoder = renderer.new_command_encoder(None);
+	command_encoder.begin_pass(&mut voxelization_pass);
+	command_encoder.finish();
+
+	// The list is far too small for every fragment, but the counter keeps counting past its capacity
+	let voxels = renderer.get_resource::<VoxelsResource>().unwrap();
+	let fragments = voxels.fragments.as_ref().unwrap();
+	let counter_size = std::mem::size_of::<VoxelFragmentCounter>() as u64;
+	let readback = renderer.device().create_buffer(&wgpu::BufferDescriptor {
+		label: None,
+		size: counter_size,
+		usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+		mapped_at_creation: false,
+	});
+	let mut encoder = renderer.device().create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+	encoder.copy_buffer_to_buffer(&fragments.counter, 0, &readback, 0, counter_size);
+	renderer.queue().submit(Some(encoder.finish()));
+	wgpu_helper::map_slice_sync(readback.slice(..), wgpu::MapMode::Read, renderer.device()).unwrap();
+	let counter: VoxelFragmentCounter = bytemuck::pod_read_unaligned(&readback.slice(..).get_mapped_range());
+	assert_eq!(counter.count, TRIANGLE_COUNT * 3);
+
+	// Every cell of the slice is hit, and nothing beyond it
+	let cells: std::collections::HashSet<_> = fragments.read(&renderer).unwrap().iter().map(|fragment| fragment.position()).collect();
+	assert_eq!(cells.len(), (CELLS * CELLS) as usize);
+	assert!(cells.iter().all(|cell| cell[2] == 0));
+}
@@ -0,0 +1,77 @@
+extern crate nalgebra_glm as glm;
+
+mod common;
+
+#[test]
+fn dynamic_meshes() {
+	use std::collections::HashSet;
+	use vct::command_encoder::voxelization::*;
+
+	let (mut renderer, camera, volume) = common::box_scene();
+	let mut voxelization_pass = VoxelizationPass::new(&renderer, &volume);
+	voxelization_pass.set_settings(VoxelizationSettings {
+		fragment_capacity: Some(8192),
+		..Default::default()
+	});
+
+	let mut voxelize = |renderer: &vct::Renderer| -> HashSet<[u32; 3]> {
+		renderer.update();
+		let mut command_encoder = renderer.new_command_encoder(Some(&camera));
+		command_encoder.begin_pass(&mut voxelization_pass);
+		command_encoder.finish();
+
+		let voxels_resource = renderer.get_resource::<VoxelsResource>().unwrap();
+		let fragments = voxels_resource.fragments.as_ref().unwrap().read(renderer).unwrap();
+		fragments.iter().map(|fragment| fragment.position()).collect()
+	};
+
+	let static_voxels = voxelize(&renderer);
+
+	renderer.load_gltf("examples/Box.glb", false);
+	let dynamic_meshes = renderer.dynamic_meshes();
+	assert_eq!(dynamic_meshes.len(), 1);
+
+	// The static box stays cached while the dynamic copy is voxelized wherever it was moved
+	let mut occupancy = Vec::new();
+	for offset in [0.25, -0.25] {
+		renderer.set_mesh_transform(dynamic_meshes[0], &glm::translation(&glm::vec3(offset, 0.0, 0.0)));
+
+		let voxels = voxelize(&renderer);
+		assert!(voxels.is_superset(&static_voxels) && voxels.len() > static_voxels.len());
+		occupancy.push(voxels);
+	}
+
+	assert_ne!(occupancy[0], occupancy[1]);
+
+	// Without fragments only the voxels around the moved mesh are voxelized again
+	voxelization_pass.set_settings(VoxelizationSettings {
+		fragment_capacity: None,
+		..voxelization_pass.settings()
+	});
+
+	let mut dirty_region = |renderer: &vct::Renderer| {
+		renderer.update();
+		let mut command_encoder = renderer.new_command_encoder(Some(&camera));
+		command_encoder.begin_pass(&mut voxelization_pass);
+		command_encoder.finish();
+
+		voxelization_pass.dirty_region()
+	};
+
+	assert_eq!(dirty_region(&renderer), Some(([0; 3], [16; 3])));
+	assert_eq!(dirty_region(&renderer), None);
+
+	// A quarter turn keeps the bounds of the cube, but its voxels still have to be rebuilt
+	let quarter_turn = glm::rotate_y(&glm::translation(&glm::vec3(-0.25, 0.0, 0.0)), std::f32::consts::FRAC_PI_2);
+	renderer.set_mesh_transform(dynamic_meshes[0], &quarter_turn);
+	assert!(dirty_region(&renderer).is_some());
+
+	for offset in [0.5, 0.75] {
+		renderer.set_mesh_transform(dynamic_meshes[0], &glm::translation(&glm::vec3(offset, 0.0, 0.0)));
+		assert!(dirty_region(&renderer).is_some());
+	}
+
+	// Moving from 0.5 to 0.75 leaves the -x half of the volume untouched
+	let (min, max) = voxelization_pass.dirty_region().unwrap();
+	assert!(min[0] > 4 && max[0] == 16);
+}
@@ -0,0 +1,70 @@
+extern crate nalgebra_glm as glm;
+
+mod common;
+
+#[test]
+fn exported_voxels() {
+	use vct::command_encoder::voxelization::*;
+
+	let (renderer, camera, volume) = common::box_scene();
+	let mut voxelization_pass = VoxelizationPass::new(&renderer, &volume);
+
+	renderer.update();
+	let mut command_encoder = renderer.new_command_encoder(Some(&camera));
+	command_encoder.begin_pass(&mut voxelization_pass);
+	command_encoder.finish();
+
+	// GL only writes the first slice of 3D storage textures, so only the shape of the readback is checked
+	let voxels_resource = renderer.get_resource::<VoxelsResource>().unwrap();
+	for (mip_level, size) in [(0, 16), (2, 4)] {
+		let data = voxels_resource.read_color(&renderer, mip_level).unwrap();
+		assert_eq!(data.size, [size; 3]);
+		assert_eq!(data.voxels.len(), size as usize * size as usize * size as usize);
+	}
+
+	let mut voxels = vec![[0; 4]; 3 * 2 * 4];
+	voxels[0] = [255, 0, 0, 255];
+	voxels[3 * 2 * 4 - 1] = [0, 0, 255, 128];
+	let data = VoxelVolumeData {
+		size: [3, 2, 4],
+		voxels,
+		volume: VoxelVolumeDescriptor {
+			min: glm::vec3(0.0, 0.0, 0.0),
+			max: glm::vec3(6.0, 2.0, 2.0),
+			resolution: 8,
+		},
+		mip_level: 1,
+	};
+	assert_eq!(data.occupied().map(|(position, _)| position).collect::<Vec<_>>(), [[0, 0, 0], [2, 1, 3]]);
+
+	let mut vox = Vec::new();
+	data.write_vox(&mut vox).unwrap();
+	assert_eq!(&vox[..4], b"VOX ");
+	assert_eq!(&vox[8..12], b"MAIN");
+	// Y and Z swap places and Z is flipped
+	assert_eq!(&vox[20..24], b"SIZE");
+	assert_eq!(bytemuck::pod_collect_to_vec::<u8, u32>(&vox[32..44]), [3, 4, 2]);
+	assert_eq!(&vox[44..48], b"XYZI");
+	assert_eq!(&vox[56..68], [2, 0, 0, 0, 0, 3, 0, 1, 2, 0, 1, 2]);
+	assert_eq!(&vox[68..72], b"RGBA");
+	assert_eq!(&vox[80..88], [255, 0, 0, 255, 0, 0, 255, 255]);
+	assert_eq!(vox.len(), 80 + 1024);
+
+	let (mut data_bytes, mut header) = (Vec::new(), Vec::new());
+	data.write_raw(&mut data_bytes, &mut header).unwrap();
+	assert_eq!(data_bytes.len(), 3 * 2 * 4 * 4);
+	let header = String::from_utf8(header).unwrap();
+	assert!(header.contains("\"size\": [3, 2, 4]"));
+	assert!(header.contains("\"voxel_size\": [2.0, 1.0, 0.5]"), "{header}");
+
+	let mut sparse = Vec::new();
+	data.write_sparse(&mut sparse).unwrap();
+	assert_eq!(String::from_utf8(sparse).unwrap(), "0 0 0 255 0 0 255\n2 1 3 0 0 255 128\n");
+
+	let too_large = VoxelVolumeData {
+		size: [512, 1, 1],
+		voxels: vec![[0; 4]; 512],
+		..data
+	};
+	assert!(too_large.write_vox(&mut Vec::new()).is_err());
+}
@@ -0,0 +1,113 @@
+extern crate nalgebra_glm as glm;
+
+mod common;
+
+use vct::*;
+
+#[test]
+fn forward_frame() {
+	use vct::command_encoder::{forward::{self, ForwardRenderingPass}, voxelization::*};
+
+	let (renderer, camera, volume) = common::box_scene();
+	assert!(volume.voxel_size() > 0.0);
+	assert!(volume.min.iter().all(|v| *v < -0.5) && volume.max.iter().all(|v| *v > 0.5));
+
+	let mut voxelization_pass = VoxelizationPass::new(&renderer, &volume);
+	voxelization_pass.set_settings(VoxelizationSettings {
+		bounce_count: 2,
+		mip_mode: VoxelMipMode::Anisotropic,
+		..Default::default()
+	});
+	let mut radiance_injection_pass = RadianceInjectionPass::new(&renderer);
+	radiance_injection_pass.set_bounce_settings(RadianceBounceSettings {
+		cone_count: 9,
+		..Default::default()
+	});
+	let mut forward_render_pass = ForwardRenderingPass::new(&renderer);
+	forward_render_pass.set_cone_tracing_settings(forward::ConeTracingSettings {
+		diffuse_cone_count: 9,
+		..Default::default()
+	});
+
+	renderer.update();
+	let mut command_encoder = renderer.new_command_encoder(Some(&camera));
+	command_encoder.begin_pass(&mut voxelization_pass);
+	command_encoder.begin_pass(&mut radiance_injection_pass);
+	command_encoder.begin_pass(&mut forward_render_pass);
+	let image = command_encoder.finish_and_read().unwrap();
+
+	// Box.glb is pure red, so whatever light reaches it stays red, while the background is left black
+	let center = image.get_pixel(16, 8).0;
+	assert!(center[0] > 0 && center[1..3] == [0, 0], "{center:?}");
+	assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0, 255]);
+
+	forward_debug_views(&renderer, &camera, &mut forward_render_pass, &image);
+	deferred_frame(&renderer, &camera, &image);
+}
+
+fn forward_debug_views(
+	renderer: &Renderer,
+	camera: &camera::Camera,
+	forward_render_pass: &mut vct::command_encoder::forward::ForwardRenderingPass,
+	shaded: &image::RgbaImage,
+) {
+	use vct::command_encoder::forward::ForwardDebugView;
+
+	let mut render = |debug_view| {
+		forward_render_pass.set_debug_view(debug_view);
+		let mut command_encoder = renderer.new_command_encoder(Some(camera));
+		command_encoder.begin_pass(forward_render_pass);
+		command_encoder.finish_and_read().unwrap()
+	};
+
+	// Box.glb has no metallic roughness texture, so the metalness in blue is its factor of 0 and the roughness its default of 1
+	let image = render(ForwardDebugView::MetallicRoughness);
+	assert_eq!(image.get_pixel(16, 8).0[..3], [0, 255, 0]);
+
+	for debug_view in [
+		ForwardDebugView::Uv0,
+		ForwardDebugView::VertexColor,
+		ForwardDebugView::ShadowOcclusion,
+		ForwardDebugView::DiffuseGi,
+		ForwardDebugView::Specular,
+	] {
+		render(debug_view);
+	}
+
+	// The face of the box in the centre has a normal of +z, mapped to 0.5, 0.5 and 1.
+	// Box.glb has no normal map, so the mapped normal is the vertex normal
+	let image = render(ForwardDebugView::WorldNormal);
+	assert_ne!(image, *shaded);
+	let center = image.get_pixel(16, 8).0;
+	assert!(center[..3].iter().zip([128, 128, 255]).all(|(channel, value)| channel.abs_diff(value) <= 1), "{center:?}");
+	assert_eq!(render(ForwardDebugView::MappedNormal), image);
+
+	assert_eq!(render(ForwardDebugView::Shaded), *shaded);
+}
+
+// Lit once per pixel from the G-buffer, the frame should match the forward pass up to precision
+fn deferred_frame(renderer: &Renderer, camera: &camera::Camera, forward: &image::RgbaImage) {
+	use vct::command_encoder::deferred::*;
+
+	let mut gbuffer_pass = GBufferPass::new(renderer);
+	let mut deferred_lighting_pass = DeferredLightingPass::new(renderer);
+	deferred_lighting_pass.set_cone_tracing_settings(vct::command_encoder::forward::ConeTracingSettings {
+		diffuse_cone_count: 9,
+		..Default::default()
+	});
+
+	let mut command_encoder = renderer.new_command_encoder(Some(camera));
+	command_encoder.begin_pass(&mut gbuffer_pass);
+	command_encoder.begin_pass(&mut deferred_lighting_pass);
+	let image = command_encoder.finish_and_read().unwrap();
+
+	assert!(renderer.get_resource::<GBufferResource>().is_some());
+	assert!(image.pixels().any(|pixel| pixel.0[..3] != [0, 0, 0]));
+
+	let difference = image
+		.pixels()
+		.zip(forward.pixels())
+		.flat_map(|(a, b)| a.0.into_iter().zip(b.0).map(|(a, b)| a.abs_diff(b)))
+		.max();
+	assert!(difference <= Some(1));
+}
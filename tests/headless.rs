@@ -0,0 +1,47 @@
+extern crate nalgebra_glm as glm;
+
+mod common;
+
+use std::collections::HashMap;
+
+use vct::*;
+
+// Rejected before a device is requested, so it doesn't share the cached bind group layouts
+#[test]
+fn headless_unreadable_format() {
+	let renderer = pollster::block_on(Renderer::new_headless(
+		[4, 4],
+		wgpu::TextureFormat::Rgba16Float,
+		RendererSettings {
+			render_scale: 1.0,
+			extras: HashMap::new(),
+		},
+	));
+	assert!(matches!(
+		renderer,
+		Err(readback::ReadbackError::UnreadableFormat(wgpu::TextureFormat::Rgba16Float))
+	));
+}
+
+// The offscreen target and its readback, the scene is checked by the other binaries
+#[test]
+fn headless() {
+	// 70 * 4 bytes per row is not a multiple of COPY_BYTES_PER_ROW_ALIGNMENT
+	let mut renderer = common::new_headless_renderer([70, 20]);
+	let camera = common::new_camera(&mut renderer);
+
+	renderer.update();
+	let command_encoder = renderer.new_command_encoder(Some(&camera));
+	let image = command_encoder.finish_and_read().unwrap();
+
+	assert_eq!(image.dimensions(), (70, 20));
+	assert!(image.pixels().all(|pixel| pixel.0 == [0, 0, 0, 0]));
+
+	renderer.resize(32, 16);
+	renderer.update();
+	let command_encoder = renderer.new_command_encoder(Some(&camera));
+	command_encoder.finish();
+
+	let image = renderer.read_frame().unwrap().unwrap();
+	assert_eq!(image.dimensions(), (32, 16));
+}
@@ -0,0 +1,48 @@
+extern crate nalgebra_glm as glm;
+
+use std::collections::HashMap;
+
+use vct::*;
+
+// One triangle from (0, 0, 0) to (1, 1, 0), drawn once as triangles under a scaled and translated parent and once as points
+const NESTED_GLTF: &str = r#"{
+	"asset": { "version": "2.0" },
+	"scene": 0,
+	"scenes": [{ "nodes": [0, 2] }],
+	"nodes": [
+		{ "translation": [10, 0, 0], "scale": [2, 2, 2], "children": [1] },
+		{ "translation": [0, 5, 0], "mesh": 0 },
+		{ "translation": [-100, 0, 0], "mesh": 1 }
+	],
+	"meshes": [
+		{ "primitives": [{ "attributes": { "POSITION": 0 } }] },
+		{ "primitives": [{ "attributes": { "POSITION": 0 }, "mode": 0 }] }
+	],
+	"buffers": [{ "byteLength": 36, "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA" }],
+	"bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+	"accessors": [{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0] }]
+}"#;
+
+// A separate binary from tests/headless.rs, bind group layouts are cached for the first device of a process
+#[test]
+fn nested_nodes() {
+	let path = std::env::temp_dir().join(format!("vct_nested_{}.gltf", std::process::id()));
+	std::fs::write(&path, NESTED_GLTF).unwrap();
+
+	let mut renderer = pollster::block_on(Renderer::new_headless(
+		[4, 4],
+		wgpu::TextureFormat::Rgba8Unorm,
+		RendererSettings {
+			render_scale: 1.0,
+			extras: HashMap::new(),
+		},
+	)).unwrap();
+	renderer.load_gltf(&path, true);
+	std::fs::remove_file(&path).unwrap();
+
+	// The points mesh is skipped, the triangle spans (10, 10, 0) to (12, 12, 0) once both transforms apply
+	let volume = vct::command_encoder::voxelization::VoxelVolumeDescriptor::fit_to_meshes(&renderer, 16).unwrap();
+	let center = (volume.min + volume.max) * 0.5;
+	assert!((center - glm::vec3(11.0, 11.0, 0.0)).norm() < 1e-4, "{center:?}");
+	assert!((volume.size().x - 2.0 * 16.0 / 14.0).abs() < 1e-4);
+}
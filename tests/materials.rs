@@ -0,0 +1,71 @@
+extern crate nalgebra_glm as glm;
+
+mod common;
+
+use vct::command_encoder::voxelization::*;
+
+#[test]
+fn voxel_materials() {
+	let is_gl = common::is_gl_adapter();
+	let (renderer, _, _) = common::box_scene();
+
+	// Storage writes on the GL backend only reach the first slice, so that slice is placed just inside the front face
+	let volume = VoxelVolumeDescriptor {
+		min: glm::vec3(-1.0, -1.0, -0.45),
+		max: glm::vec3(1.0, 1.0, 1.55),
+		resolution: 16,
+	};
+	let mut voxelization_pass = VoxelizationPass::new(&renderer, &volume);
+
+	renderer.update();
+	let mut command_encoder = renderer.new_command_encoder(None);
+	command_encoder.begin_pass(&mut voxelization_pass);
+	command_encoder.finish();
+
+	let voxels = renderer.get_resource::<VoxelsResource>().unwrap();
+	let read = |texture, mip_level| VoxelVolumeData::read(&renderer, texture, volume, mip_level).unwrap();
+	let [color, normal, emissive, material] =
+		[&voxels.color, &voxels.normal, &voxels.emissive, &voxels.material].map(|volume| read(&volume.texture, 0));
+
+	// The top face at y = 0.5 and the right face at x = 0.5, both lying along the first slice
+	let top: Vec<_> = (5..=10).map(|x| [x, 12, 0]).collect();
+	let right: Vec<_> = (5..=6).map(|y| [12, y, 0]).collect();
+	let bottom: Vec<_> = (5..=10).map(|x| [x, 4, 0]).collect();
+
+	// Box.glb is untextured with a base colour of 0.8, 0, 0 and a metallicFactor of 0, roughness defaults to 1
+	let occupied: Vec<_> = color.occupied().map(|(position, _)| position).collect();
+	assert!(top.iter().chain(&right).chain(&bottom).all(|position| occupied.contains(position)));
+	assert!(color.occupied().all(|(_, voxel)| voxel[1..] == [0, 0, 255] && voxel[0].abs_diff(204) <= 1));
+	assert_eq!(material.occupied().map(|(position, _)| position).collect::<Vec<_>>(), occupied);
+	assert!(material.occupied().all(|(_, voxel)| voxel == [0, 255, 0, 255]));
+	assert_eq!(emissive.occupied().map(|(position, _)| position).collect::<Vec<_>>(), occupied);
+	assert!(emissive.occupied().all(|(_, voxel)| voxel[..3] == [0, 0, 0]));
+
+	// Snorm texels, 127 is 1 and 129 is -1
+	assert!(top.iter().all(|position| normal.voxel(*position) == [0, 127, 0, 127]));
+	assert!(right.iter().all(|position| normal.voxel(*position) == [127, 0, 0, 127]));
+	if is_gl {
+		// Copies on the GL backend clamp negative snorm texels to 0
+		assert!(bottom.iter().all(|position| normal.voxel(*position) == [0, 0, 0, 127]));
+	} else {
+		assert!(bottom.iter().all(|position| normal.voxel(*position) == [0, 129, 0, 127]));
+	}
+
+	// Every volume keeps a full mip chain down to a single voxel
+	for volume in [&voxels.color, &voxels.normal, &voxels.emissive, &voxels.material] {
+		assert_eq!(volume.texture.mip_level_count(), 5);
+		for mip_level in 0..5 {
+			assert_eq!(read(&volume.texture, mip_level).size, [16 >> mip_level; 3]);
+		}
+	}
+
+	// The mip maps of the GL backend come out empty, elsewhere half of the voxels under the top face are filled
+	if !is_gl {
+		let top = [3, 6, 0];
+		let normal = read(&voxels.normal.texture, 1).voxel(top);
+		assert!(normal[0] == 0 && (1..=127).contains(&normal[1]) && normal[2] == 0, "{normal:?}");
+		let material = read(&voxels.material.texture, 1).voxel(top);
+		assert!(material[0] == 0 && material[1] > 0 && material[1] == material[3], "{material:?}");
+		assert_eq!(read(&voxels.emissive.texture, 1).voxel(top)[..3], [0, 0, 0]);
+	}
+}
@@ -0,0 +1,192 @@
+extern crate nalgebra_glm as glm;
+
+mod common;
+
+#[test]
+fn voxel_mesh() {
+	use vct::command_encoder::voxelization::*;
+
+	let (renderer, camera, volume) = common::box_scene();
+	let mut voxelization_pass = VoxelizationPass::new(&renderer, &volume);
+
+	renderer.update();
+	let mut command_encoder = renderer.new_command_encoder(Some(&camera));
+	command_encoder.begin_pass(&mut voxelization_pass);
+	command_encoder.finish();
+
+	let mut meshify_pass = MeshifyPass::new(&renderer);
+	let mut render_meshify_pass = RenderMeshifyPass::new(&renderer);
+
+	// The voxel mesh is rebuilt on the GPU every frame without reading the count back in between
+	write_voxel_block(&renderer, [1, 1, 1], [7, 7, 7]);
+	for _ in 0..2 {
+		let mut command_encoder = renderer.new_command_encoder(Some(&camera));
+		command_encoder.begin_pass(&mut meshify_pass);
+		command_encoder.begin_pass(&mut render_meshify_pass);
+		let image = command_encoder.finish_and_read().unwrap();
+
+		assert!(image.pixels().any(|pixel| pixel.0[..3] != [0, 0, 0]));
+	}
+
+	let counts = renderer.get_resource::<VoxelsMeshResource>().unwrap().read_counts(&renderer).unwrap();
+	assert_eq!(counts, (152 * 6, 152 * 6 * 6));
+
+	// Each side of the 6x6x6 block has 36 faces, merged into a single quad
+	for (mode, quads) in [(MeshifyMode::ExposedFaces, 6 * 36), (MeshifyMode::Greedy, 6)] {
+		meshify_pass.set_settings(MeshifySettings {
+			mode,
+			..Default::default()
+		});
+		let mut command_encoder = renderer.new_command_encoder(Some(&camera));
+		command_encoder.begin_pass(&mut meshify_pass);
+		command_encoder.begin_pass(&mut render_meshify_pass);
+		let image = command_encoder.finish_and_read().unwrap();
+
+		assert!(image.pixels().any(|pixel| pixel.0[..3] != [0, 0, 0]));
+		let counts = renderer.get_resource::<VoxelsMeshResource>().unwrap().read_counts(&renderer).unwrap();
+		assert_eq!(counts, (quads, quads * 6));
+	}
+
+	exported_voxel_mesh(&renderer);
+
+	// Quads past the capacity are counted but left out of the draw
+	let mut meshify_pass = MeshifyPass::with_capacity(&renderer, 1);
+	let mut command_encoder = renderer.new_command_encoder(Some(&camera));
+	command_encoder.begin_pass(&mut meshify_pass);
+	command_encoder.finish();
+
+	let counts = renderer.get_resource::<VoxelsMeshResource>().unwrap().read_counts(&renderer).unwrap();
+	assert_eq!(counts, (152 * 6, 6));
+
+	let mesh = renderer.get_resource::<VoxelsMeshResource>().unwrap().read_mesh(&renderer).unwrap();
+	assert_eq!((mesh.indices.len(), mesh.dropped_quads), (6, 152 * 6 - 1));
+
+	voxel_debug_views(&renderer, &camera);
+}
+
+fn exported_voxel_mesh(renderer: &vct::Renderer) {
+	use vct::command_encoder::voxelization::*;
+
+	let mesh = renderer.get_resource::<VoxelsMeshResource>().unwrap().read_mesh(renderer).unwrap();
+	assert_eq!((mesh.vertices.len(), mesh.indices.len(), mesh.dropped_quads), (6 * 4, 6 * 6, 0));
+
+	// Every triangle winds counter-clockwise around its normal
+	for triangle in mesh.indices.chunks_exact(3) {
+		let [a, b, c] = [0, 1, 2].map(|i| glm::Vec3::from(mesh.vertices[triangle[i] as usize].position));
+		let normal = glm::Vec3::from(mesh.vertices[triangle[0] as usize].normal);
+		assert!((b - a).cross(&(c - a)).normalize().dot(&normal) > 0.99);
+	}
+
+	let mut glb = Vec::new();
+	mesh.write_glb(&mut glb).unwrap();
+	let (document, buffers, _) = gltf::import_slice(&glb).unwrap();
+	let primitive = document.meshes().next().unwrap().primitives().next().unwrap();
+	let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+	assert_eq!(reader.read_positions().unwrap().count(), 24);
+	assert_eq!(reader.read_normals().unwrap().count(), 24);
+	assert!(reader.read_colors(0).unwrap().into_rgb_f32().all(|color| color == [1.0, 0.0, 0.0]));
+	assert!(reader.read_indices().unwrap().into_u32().eq(mesh.indices.iter().copied()));
+
+	let mut ply = Vec::new();
+	mesh.write_ply(&mut ply).unwrap();
+	let header = b"end_header\n";
+	let body = ply.windows(header.len()).position(|window| window == header).unwrap() + header.len();
+	assert!(ply.starts_with(b"ply\nformat binary_little_endian 1.0\n"));
+	assert_eq!(ply.len() - body, 24 * (6 * 4 + 3) + 12 * (1 + 3 * 4));
+
+	let mut obj = Vec::new();
+	mesh.write_obj(&mut obj).unwrap();
+	let obj = String::from_utf8(obj).unwrap();
+	assert_eq!(obj.lines().filter(|line| line.starts_with("v ")).count(), 24);
+	assert_eq!(obj.lines().filter(|line| line.starts_with("vn ")).count(), 24);
+	assert_eq!(obj.lines().filter(|line| line.starts_with("f ")).count(), 12);
+
+	let empty = VoxelMesh {
+		vertices: Vec::new(),
+		indices: Vec::new(),
+		dropped_quads: 0,
+	};
+	assert!(empty.write_glb(&mut Vec::new()).is_err());
+}
+
+fn voxel_debug_views(renderer: &vct::Renderer, camera: &vct::camera::Camera) {
+	use vct::command_encoder::voxelization::*;
+
+	let mut voxel_debug_pass = VoxelDebugPass::new(renderer);
+	let mut render = |settings| {
+		voxel_debug_pass.set_settings(settings);
+		let mut command_encoder = renderer.new_command_encoder(Some(camera));
+		command_encoder.begin_pass(&mut voxel_debug_pass);
+		command_encoder.finish_and_read().unwrap()
+	};
+
+	// The red block of write_voxel_block, as its albedo and as its opacity
+	for style in [VoxelDebugStyle::Cubes, VoxelDebugStyle::Points, VoxelDebugStyle::RayMarch] {
+		let image = render(VoxelDebugSettings {
+			style,
+			..Default::default()
+		});
+		assert!(image.pixels().any(|pixel| pixel.0[0] > 0));
+		assert!(image.pixels().all(|pixel| pixel.0[1..3] == [0, 0]));
+
+		let image = render(VoxelDebugSettings {
+			style,
+			channel: VoxelChannel::Opacity,
+			..Default::default()
+		});
+		assert!(image.pixels().any(|pixel| pixel.0[0] > 0));
+		assert!(image.pixels().all(|pixel| pixel.0[0] == pixel.0[1] && pixel.0[1] == pixel.0[2]));
+	}
+
+	// The block spans the slices 1 to 6
+	for (index, hit) in [(0, false), (3, true)] {
+		let image = render(VoxelDebugSettings {
+			style: VoxelDebugStyle::RayMarch,
+			slice: Some(VoxelSlice { axis: 2, index }),
+			..Default::default()
+		});
+		assert_eq!(image.pixels().any(|pixel| pixel.0[..3] != [0, 0, 0]), hit);
+	}
+
+	// Levels past the coarsest one are clamped
+	render(VoxelDebugSettings {
+		mip_level: 100,
+		style: VoxelDebugStyle::Points,
+		..Default::default()
+	});
+}
+
+// GL only writes the first slice of 3D storage textures, so the mip level the MeshifyPass reads is filled directly
+fn write_voxel_block(renderer: &vct::Renderer, min: [u32; 3], max: [u32; 3]) {
+	use vct::command_encoder::voxelization::VoxelsResource;
+
+	let voxels_resource = renderer.get_resource::<VoxelsResource>().unwrap();
+	let texture = &voxels_resource.color.texture;
+	let size = texture.size().mip_level_size(1, wgpu::TextureDimension::D3);
+
+	let mut voxels = Vec::new();
+	for z in 0..size.depth_or_array_layers {
+		for y in 0..size.height {
+			for x in 0..size.width {
+				let inside = (0..3).all(|i| (min[i]..max[i]).contains(&[x, y, z][i]));
+				voxels.push(if inside { [255u8, 0, 0, 255] } else { [0; 4] });
+			}
+		}
+	}
+
+	renderer.queue().write_texture(
+		wgpu::ImageCopyTexture {
+			texture,
+			mip_level: 1,
+			origin: wgpu::Origin3d::ZERO,
+			aspect: wgpu::TextureAspect::All,
+		},
+		bytemuck::cast_slice(&voxels),
+		wgpu::ImageDataLayout {
+			offset: 0,
+			bytes_per_row: Some(size.width * 4),
+			rows_per_image: Some(size.height),
+		},
+		size,
+	);
+}
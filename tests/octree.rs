@@ -0,0 +1,50 @@
+extern crate nalgebra_glm as glm;
+
+mod common;
+
+#[test]
+fn octree() {
+	use vct::command_encoder::{forward::{self, ForwardRenderingPass}, voxelization::*};
+
+	let (renderer, camera, volume) = common::box_scene();
+	let mut voxelization_pass = VoxelizationPass::new(&renderer, &volume);
+	let mut radiance_injection_pass = RadianceInjectionPass::new(&renderer);
+	let mut octree_pass = SparseVoxelOctreePass::new(
+		&renderer,
+		&SparseVoxelOctreeDescriptor {
+			depth: 4,
+			tile_capacity: 1024,
+		},
+	);
+	let mut forward_render_pass = ForwardRenderingPass::new(&renderer);
+	forward_render_pass.set_voxel_backend(forward::VoxelBackend::Octree);
+
+	// Only the fragment list is read, so the octree is the same with or without the dense volume
+	let mut roots = Vec::new();
+	for dense_volume in [true, false] {
+		voxelization_pass.set_settings(VoxelizationSettings {
+			fragment_capacity: Some(4096),
+			dense_volume,
+			..voxelization_pass.settings()
+		});
+
+		renderer.update();
+		let mut command_encoder = renderer.new_command_encoder(Some(&camera));
+		command_encoder.begin_pass(&mut voxelization_pass);
+		command_encoder.begin_pass(&mut radiance_injection_pass);
+		command_encoder.begin_pass(&mut octree_pass);
+		command_encoder.begin_pass(&mut forward_render_pass);
+		let image = command_encoder.finish_and_read().unwrap();
+
+		assert!(image.pixels().any(|pixel| pixel.0[..3] != [0, 0, 0]));
+
+		let (tile_count, root) = renderer.get_resource::<SparseVoxelOctreeResource>().unwrap().read_root(&renderer).unwrap();
+		assert!(tile_count > 1 && tile_count <= 1024);
+		roots.push(root);
+	}
+
+	// The box fills every octant of the root, lit from above it averages to red
+	assert_eq!(roots[0], roots[1]);
+	assert!(roots[0].iter().all(|value| value[3] > 0));
+	assert!(roots[0].iter().any(|value| value[0] > 0 && value[1] == 0 && value[2] == 0), "{:?}", roots[0]);
+}
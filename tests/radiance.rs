@@ -0,0 +1,57 @@
+extern crate nalgebra_glm as glm;
+
+mod common;
+
+use vct::command_encoder::voxelization::*;
+
+#[test]
+fn injected_light() {
+	let (renderer, _, _) = common::box_scene();
+
+	// Storage writes on the GL backend only reach the first slice, so that slice is placed just inside the front face
+	let volume = VoxelVolumeDescriptor {
+		min: glm::vec3(-1.0, -1.0, -0.45),
+		max: glm::vec3(1.0, 1.0, 1.55),
+		resolution: 16,
+	};
+	let mut voxelization_pass = VoxelizationPass::new(&renderer, &volume);
+	let mut radiance_injection_pass = RadianceInjectionPass::new(&renderer);
+
+	renderer.update();
+	let mut command_encoder = renderer.new_command_encoder(None);
+	command_encoder.begin_pass(&mut voxelization_pass);
+	command_encoder.begin_pass(&mut radiance_injection_pass);
+	command_encoder.finish();
+
+	let radiance = {
+		let voxels = renderer.get_resource::<VoxelsResource>().unwrap();
+		VoxelVolumeData::read(&renderer, &voxels.radiance.texture, volume, 0).unwrap()
+	};
+
+	// The top face at y = 0.5 is lit from above, brightest under the light and dimmer towards its edges
+	let top: Vec<_> = (5..=10).map(|x| radiance.voxel([x, 12, 0])).collect();
+	assert!(top.iter().all(|voxel| voxel[0] > 0 && voxel[3] > 0), "{top:?}");
+	assert!(top[2][0] > top[0][0] && top[3][0] > top[5][0], "{top:?}");
+
+	// The bottom face at y = -0.5 faces away from the light
+	assert!((5..=10).all(|x| radiance.voxel([x, 4, 0])[..3] == [0, 0, 0]));
+
+	// A second light outgrows the lights buffer of the pass, which has to pick it up
+	renderer.add_point_light(vct::lights::PointLight {
+		position: [0.0, 1.5, 0.0],
+		color: [1.0, 1.0, 1.0],
+		intensity: 1.0,
+	});
+
+	// Injected into new volumes, as storage writes on the GL backend don't reach a radiance texture that was already written
+	let mut voxelization_pass = VoxelizationPass::new(&renderer, &volume);
+	let mut command_encoder = renderer.new_command_encoder(None);
+	command_encoder.begin_pass(&mut voxelization_pass);
+	command_encoder.begin_pass(&mut radiance_injection_pass);
+	command_encoder.finish();
+
+	let voxels = renderer.get_resource::<VoxelsResource>().unwrap();
+	let radiance = VoxelVolumeData::read(&renderer, &voxels.radiance.texture, volume, 0).unwrap();
+	let brighter_top: Vec<_> = (5..=10).map(|x| radiance.voxel([x, 12, 0])).collect();
+	assert!(brighter_top.iter().zip(&top).all(|(brighter, voxel)| brighter[0] > voxel[0]), "{brighter_top:?}");
+}
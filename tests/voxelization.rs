@@ -0,0 +1,47 @@
+extern crate nalgebra_glm as glm;
+
+mod common;
+
+#[test]
+fn rasterized_voxelization() {
+	use std::collections::HashSet;
+	use vct::command_encoder::{forward::ForwardRenderingPass, voxelization::*};
+
+	let (renderer, camera, volume) = common::box_scene();
+	let mut voxelization_pass = VoxelizationPass::new(&renderer, &volume);
+	let mut radiance_injection_pass = RadianceInjectionPass::new(&renderer);
+	let mut forward_render_pass = ForwardRenderingPass::new(&renderer);
+
+	// Occupancy is compared through the fragment lists, as they hold every voxel either path wrote
+	let occupancy: Vec<HashSet<[u32; 3]>> = [VoxelizationMethod::Compute, VoxelizationMethod::Rasterization]
+		.into_iter()
+		.map(|method| {
+			voxelization_pass.set_settings(VoxelizationSettings {
+				fragment_capacity: Some(4096),
+				method,
+				..voxelization_pass.settings()
+			});
+
+			renderer.update();
+			let mut command_encoder = renderer.new_command_encoder(Some(&camera));
+			command_encoder.begin_pass(&mut voxelization_pass);
+			command_encoder.begin_pass(&mut radiance_injection_pass);
+			command_encoder.begin_pass(&mut forward_render_pass);
+			let image = command_encoder.finish_and_read().unwrap();
+
+			assert!(image.pixels().any(|pixel| pixel.0[..3] != [0, 0, 0]));
+
+			let voxels_resource = renderer.get_resource::<VoxelsResource>().unwrap();
+			let fragments = voxels_resource.fragments.as_ref().unwrap().read(&renderer).unwrap();
+			assert!(fragments.len() < 4096);
+
+			fragments.iter().map(|fragment| fragment.position()).collect()
+		})
+		.collect();
+
+	// The scanlines of the compute path leave holes the rasterizer fills, so only its voxels have to be shared
+	let (compute, rasterized) = (&occupancy[0], &occupancy[1]);
+	let shared = compute.intersection(rasterized).count();
+	assert!(!compute.is_empty() && shared * 10 >= compute.len() * 9);
+	assert!(rasterized.len() <= compute.len() * 2);
+}
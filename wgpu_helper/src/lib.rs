@@ -33,6 +33,23 @@ pub trait BindGroupItem: Sized {
 	fn get_binding<'a>(&'a self) -> wgpu::BindingResource<'a>;
 }
 
+// Blocks until the slice is mapped, a lost device drops the callback and counts as a failed map.
+// Also used for buffers whose size is only known at runtime, which can't be a BufferTrait
+pub fn map_slice_sync(
+	slice: wgpu::BufferSlice,
+	mode: wgpu::MapMode,
+	device: &wgpu::Device,
+) -> Result<(), wgpu::BufferAsyncError> {
+	let (tx, rx) = std::sync::mpsc::channel::<Result<(), wgpu::BufferAsyncError>>();
+
+	slice.map_async(mode, move |res| {
+		let _ = tx.send(res);
+	});
+
+	device.poll(wgpu::MaintainBase::Wait);
+	rx.recv().unwrap_or(Err(wgpu::BufferAsyncError))
+}
+
 pub trait BufferTrait: Sized + BindGroupItem {
 	type Source: HostShareable;
 
@@ -63,45 +80,19 @@ pub trait BufferTrait: Sized + BindGroupItem {
 	}
 
 	fn map_sync<'a>(&'a self, device: &wgpu::Device) -> &'a Self::Source {
-		let (tx, rx) = std::sync::mpsc::channel::<Result<(), wgpu::BufferAsyncError>>();
-
-		self.map_async(wgpu::MapMode::Read, move |res| {
-			tx.send(res).unwrap();
-		});
-
-		loop {
-			device.poll(wgpu::MaintainBase::Wait);
-
-			match rx.recv() {
-				Ok(_) => {
-					return self.get_mapped_data();
-				}
-				Err(e) => {
-					panic!("{:#?}", e);
-				}
-			}
+		if let Err(e) = map_slice_sync(self.get_slice(), wgpu::MapMode::Read, device) {
+			panic!("{:#?}", e);
 		}
+
+		self.get_mapped_data()
 	}
 
 	fn map_sync_mut<'a>(&'a mut self, device: &wgpu::Device) -> &'a mut Self::Source {
-		let (tx, rx) = std::sync::mpsc::channel::<Result<(), wgpu::BufferAsyncError>>();
-
-		self.map_async(wgpu::MapMode::Read, move |res| {
-			tx.send(res).unwrap();
-		});
-
-		loop {
-			device.poll(wgpu::MaintainBase::Wait);
-
-			match rx.recv() {
-				Ok(_) => {
-					return self.get_mapped_data_mut();
-				}
-				Err(e) => {
-					panic!("{:#?}", e);
-				}
-			}
+		if let Err(e) = map_slice_sync(self.get_slice(), wgpu::MapMode::Read, device) {
+			panic!("{:#?}", e);
 		}
+
+		self.get_mapped_data_mut()
 	}
 
 	fn get_mapped_data<'a>(&'a self) -> &'a Self::Source {